@@ -0,0 +1,41 @@
+use tonic_build::manual::{Builder, Method, Service};
+
+fn main() {
+    let geyser = Service::builder()
+        .name("Geyser")
+        .package("fusion_engine")
+        .method(
+            Method::builder()
+                .name("subscribe")
+                .route_name("Subscribe")
+                .input_type("crate::grpc::proto::SubscribeRequest")
+                .output_type("crate::grpc::proto::SubscribeUpdate")
+                .codec_path("tonic::codec::ProstCodec")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("subscribe_accounts")
+                .route_name("SubscribeAccounts")
+                .input_type("crate::grpc::proto::AccountsFilter")
+                .output_type("crate::grpc::proto::SubscribeUpdate")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("subscribe_transactions")
+                .route_name("SubscribeTransactions")
+                .input_type("crate::grpc::proto::TransactionsFilter")
+                .output_type("crate::grpc::proto::SubscribeUpdate")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    Builder::new().build_client(false).compile(&[geyser]);
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{AccTx, AccountUpdate, PluginMetrics};
+
+/// Rough bookkeeping cost of an entry on top of its byte vectors.
+const ENTRY_OVERHEAD: usize = mem::size_of::<Entry>() + 2 * mem::size_of::<Vec<u8>>() + 8;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccountCacheConfig {
+    /// Least recently updated accounts are evicted beyond this many.
+    pub max_entries: usize,
+    /// Accounts not updated for this long are dropped when set.
+    pub ttl_secs: Option<u64>,
+}
+
+impl Default for AccountCacheConfig {
+    fn default() -> Self {
+        AccountCacheConfig {
+            max_entries: 1_000_000,
+            ttl_secs: None,
+        }
+    }
+}
+
+/// Latest state of an account seen by the writer.
+#[derive(Debug, Clone)]
+pub struct CachedAccount {
+    pub owner: Vec<u8>,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub slot: u64,
+    pub write_version: u64,
+}
+
+impl CachedAccount {
+    fn is_newer_than(&self, other: &CachedAccount) -> bool {
+        (self.slot, self.write_version) > (other.slot, other.write_version)
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheUpdate {
+    /// The update is older than the cached state, which is kept.
+    Stale,
+    /// The update is the latest state; carries the state it replaced when
+    /// that was cached.
+    Applied(Option<CachedAccount>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    account: CachedAccount,
+    used: u64,
+    updated_at: Instant,
+}
+
+/// Latest state of every account passing the filters, bounded by count
+/// and age, for stages that compare updates with the previous state.
+///
+/// Updates only replace the cached state when their slot and write
+/// version are newer, so replays and out of order updates never roll an
+/// account back. Hits, misses, entries and an estimate of the memory held
+/// are kept in [`PluginMetrics`].
+#[derive(Debug)]
+pub struct AccountCache {
+    max_entries: usize,
+    ttl: Option<Duration>,
+    entries: HashMap<Vec<u8>, Entry>,
+    /// Pubkeys by the counter value of their last update, least recent
+    /// first.
+    recency: BTreeMap<u64, Vec<u8>>,
+    counter: u64,
+    bytes: usize,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl AccountCache {
+    pub fn new(config: &AccountCacheConfig, metrics: Arc<PluginMetrics>) -> Self {
+        AccountCache {
+            max_entries: config.max_entries.max(1),
+            ttl: config.ttl_secs.map(Duration::from_secs),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            counter: 0,
+            bytes: 0,
+            metrics,
+        }
+    }
+
+    /// Applies an account update; closed accounts are removed. Other
+    /// events are ignored.
+    pub fn observe(&mut self, event: &AccTx) -> Option<CacheUpdate> {
+        let (pubkey, account) = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                data,
+                slot,
+                write_version,
+                ..
+            }) => (
+                pubkey,
+                CachedAccount {
+                    owner: owner.clone(),
+                    lamports: *lamports,
+                    data: data.clone(),
+                    slot: *slot,
+                    write_version: *write_version,
+                },
+            ),
+            _ => return None,
+        };
+
+        let previous = self.get(pubkey);
+        if previous.is_some_and(|previous| previous.is_newer_than(&account)) {
+            return Some(CacheUpdate::Stale);
+        }
+
+        let previous = if account.lamports == 0 {
+            self.remove(pubkey)
+        } else {
+            self.insert(pubkey.clone(), account)
+        };
+        self.report();
+        Some(CacheUpdate::Applied(previous))
+    }
+
+    /// The cached state, counted as a hit or miss.
+    pub fn get(&mut self, pubkey: &[u8]) -> Option<&CachedAccount> {
+        if self
+            .entries
+            .get(pubkey)
+            .is_some_and(|entry| self.expired(entry))
+        {
+            self.remove(pubkey);
+        }
+
+        match self.entries.get(pubkey) {
+            Some(entry) => {
+                PluginMetrics::increment(&self.metrics.account_cache_hits);
+                Some(&entry.account)
+            }
+            None => {
+                PluginMetrics::increment(&self.metrics.account_cache_misses);
+                None
+            }
+        }
+    }
+
+    /// Drops the accounts older than the TTL, called once a second by the
+    /// writer.
+    pub fn expire(&mut self) {
+        if self.ttl.is_none() {
+            return;
+        }
+
+        let expired: Vec<Vec<u8>> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| self.expired(entry))
+            .map(|(pubkey, _)| pubkey.clone())
+            .collect();
+        for pubkey in expired {
+            self.remove(&pubkey);
+        }
+        self.report();
+    }
+
+    fn expired(&self, entry: &Entry) -> bool {
+        self.ttl
+            .is_some_and(|ttl| entry.updated_at.elapsed() >= ttl)
+    }
+
+    fn insert(&mut self, pubkey: Vec<u8>, account: CachedAccount) -> Option<CachedAccount> {
+        self.counter += 1;
+        self.bytes += entry_bytes(&pubkey, &account);
+        self.recency.insert(self.counter, pubkey.clone());
+        let previous = self.entries.insert(
+            pubkey.clone(),
+            Entry {
+                account,
+                used: self.counter,
+                updated_at: Instant::now(),
+            },
+        );
+        let previous = previous.map(|previous| {
+            self.recency.remove(&previous.used);
+            self.bytes -= entry_bytes(&pubkey, &previous.account);
+            previous.account
+        });
+
+        while self.entries.len() > self.max_entries {
+            match self.recency.first_key_value() {
+                Some((_, oldest)) => {
+                    let oldest = oldest.clone();
+                    self.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        previous
+    }
+
+    fn remove(&mut self, pubkey: &[u8]) -> Option<CachedAccount> {
+        let entry = self.entries.remove(pubkey)?;
+        self.recency.remove(&entry.used);
+        self.bytes -= entry_bytes(pubkey, &entry.account);
+        Some(entry.account)
+    }
+
+    fn report(&self) {
+        self.metrics
+            .account_cache_entries
+            .store(self.entries.len() as u64, Ordering::Relaxed);
+        self.metrics
+            .account_cache_bytes
+            .store(self.bytes as u64, Ordering::Relaxed);
+    }
+}
+
+fn entry_bytes(pubkey: &[u8], account: &CachedAccount) -> usize {
+    ENTRY_OVERHEAD + pubkey.len() + account.owner.len() + account.data.len()
+}
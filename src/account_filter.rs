@@ -0,0 +1,355 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, ReplicaAccountInfoVersions, ReplicaTransactionInfoVersions,
+    Result as GeyserResult,
+};
+use solana_sdk::{pubkey::Pubkey, sysvar};
+
+use crate::config::parse_pubkey;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccountFilterConfig {
+    /// Programs whose accounts are always delivered, e.g. SPL Token.
+    pub owners: Vec<String>,
+    /// Programs whose accounts are never delivered, whatever other rules
+    /// match.
+    pub exclude_owners: Vec<String>,
+    /// Accounts always delivered.
+    pub accounts: Vec<String>,
+    /// File listing more accounts to deliver, one pubkey per line; blank
+    /// lines and lines starting with `#` are skipped.
+    pub accounts_file: Option<PathBuf>,
+    /// Programs whose accounts are tracked from the first update seen while
+    /// owned by them, until the account is closed.
+    pub discover_programs: Vec<String>,
+    /// Rules that start tracking the writable accounts of transactions
+    /// invoking a program.
+    pub transaction_triggers: Vec<TransactionTriggerConfig>,
+    /// Drops updates of sysvar accounts, which change every slot.
+    pub drop_sysvars: bool,
+    /// Sysvars still delivered when `drop_sysvars` is set.
+    pub keep_sysvars: Vec<String>,
+}
+
+impl Default for AccountFilterConfig {
+    fn default() -> Self {
+        AccountFilterConfig {
+            owners: Vec::new(),
+            exclude_owners: Vec::new(),
+            accounts: Vec::new(),
+            accounts_file: None,
+            discover_programs: Vec::new(),
+            transaction_triggers: Vec::new(),
+            drop_sysvars: true,
+            keep_sysvars: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionTriggerConfig {
+    pub program: String,
+    /// Number of slots after the triggering transaction during which the
+    /// touched accounts stay tracked.
+    pub track_slots: u64,
+}
+
+/// Decides which account updates are sent down the pipeline.
+///
+/// Passes everything but dropped sysvars and excluded owners until an owner
+/// or account allowlist or a tracking rule is configured.
+#[derive(Debug, Default)]
+pub struct AccountFilter {
+    owners: HashSet<Pubkey>,
+    exclude_owners: HashSet<Pubkey>,
+    accounts: HashSet<Pubkey>,
+    drop_sysvars: bool,
+    keep_sysvars: HashSet<Pubkey>,
+    discover_programs: HashSet<Pubkey>,
+    tracked: HashSet<Pubkey>,
+    transaction_triggers: HashMap<Pubkey, u64>,
+    /// Accounts tracked because of a trigger, with the last slot they are
+    /// tracked for.
+    triggered: HashMap<Pubkey, u64>,
+    pruned_slot: u64,
+}
+
+impl AccountFilter {
+    pub fn new(config: &AccountFilterConfig) -> GeyserResult<Self> {
+        let discover_programs = config
+            .discover_programs
+            .iter()
+            .map(|program| parse_pubkey(program))
+            .collect::<GeyserResult<_>>()?;
+
+        let mut transaction_triggers = HashMap::new();
+        for trigger in &config.transaction_triggers {
+            let track_slots = transaction_triggers
+                .entry(parse_pubkey(&trigger.program)?)
+                .or_insert(0);
+            *track_slots = trigger.track_slots.max(*track_slots);
+        }
+
+        let keep_sysvars = config
+            .keep_sysvars
+            .iter()
+            .map(|sysvar| parse_pubkey(sysvar))
+            .collect::<GeyserResult<_>>()?;
+        let owners = config
+            .owners
+            .iter()
+            .map(|owner| parse_pubkey(owner))
+            .collect::<GeyserResult<_>>()?;
+        let exclude_owners = config
+            .exclude_owners
+            .iter()
+            .map(|owner| parse_pubkey(owner))
+            .collect::<GeyserResult<_>>()?;
+
+        let mut accounts = config
+            .accounts
+            .iter()
+            .map(|account| parse_pubkey(account))
+            .collect::<GeyserResult<HashSet<_>>>()?;
+        if let Some(accounts_file) = &config.accounts_file {
+            let contents = std::fs::read_to_string(accounts_file).map_err(|error| {
+                GeyserPluginError::ConfigFileReadError {
+                    msg: format!("Failed to read {:?}: {}", accounts_file, error),
+                }
+            })?;
+            for line in contents.lines().map(str::trim) {
+                if !line.is_empty() && !line.starts_with('#') {
+                    accounts.insert(parse_pubkey(line)?);
+                }
+            }
+        }
+
+        Ok(AccountFilter {
+            owners,
+            exclude_owners,
+            accounts,
+            drop_sysvars: config.drop_sysvars,
+            keep_sysvars,
+            discover_programs,
+            transaction_triggers,
+            ..AccountFilter::default()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.owners.is_empty()
+            || !self.accounts.is_empty()
+            || !self.discover_programs.is_empty()
+            || !self.transaction_triggers.is_empty()
+    }
+
+    /// Starts tracking the writable accounts of `transaction` if it invokes
+    /// one of the trigger programs.
+    pub fn observe_transaction(&mut self, slot: u64, transaction: &ReplicaTransactionInfoVersions) {
+        if self.transaction_triggers.is_empty() {
+            return;
+        }
+
+        if slot > self.pruned_slot {
+            self.triggered.retain(|_, last_slot| *last_slot >= slot);
+            self.pruned_slot = slot;
+        }
+
+        let message = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(inner_tx) => inner_tx.transaction.message(),
+            ReplicaTransactionInfoVersions::V0_0_2(inner_tx) => inner_tx.transaction.message(),
+        };
+
+        let track_slots = message
+            .program_instructions_iter()
+            .filter_map(|(program_id, _)| self.transaction_triggers.get(program_id))
+            .max();
+        let last_slot = match track_slots {
+            Some(track_slots) => slot.saturating_add(*track_slots),
+            None => return,
+        };
+
+        for (index, pubkey) in message.account_keys().iter().enumerate() {
+            if message.is_writable(index) {
+                let tracked_until = self.triggered.entry(*pubkey).or_insert(last_slot);
+                *tracked_until = last_slot.max(*tracked_until);
+            }
+        }
+    }
+
+    pub fn matches(&mut self, slot: u64, account: &ReplicaAccountInfoVersions) -> bool {
+        let (pubkey, owner, lamports) = match account {
+            ReplicaAccountInfoVersions::V0_0_1(inner_account) => (
+                inner_account.pubkey,
+                inner_account.owner,
+                inner_account.lamports,
+            ),
+            ReplicaAccountInfoVersions::V0_0_2(inner_account) => (
+                inner_account.pubkey,
+                inner_account.owner,
+                inner_account.lamports,
+            ),
+        };
+
+        self.matches_parts(slot, pubkey, owner, lamports)
+    }
+
+    fn matches_parts(&mut self, slot: u64, pubkey: &[u8], owner: &[u8], lamports: u64) -> bool {
+        let (pubkey, owner) = match (Pubkey::try_from(pubkey), Pubkey::try_from(owner)) {
+            (Ok(pubkey), Ok(owner)) => (pubkey, owner),
+            // Matches no rule, whether or not any is configured.
+            _ => return false,
+        };
+
+        if self.drop_sysvars && sysvar::check_id(&owner) && !self.keep_sysvars.contains(&pubkey) {
+            return false;
+        }
+        if self.exclude_owners.contains(&owner) {
+            return false;
+        }
+        if self.owners.contains(&owner) || self.accounts.contains(&pubkey) {
+            return true;
+        }
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let triggered =
+            matches!(self.triggered.get(&pubkey), Some(last_slot) if *last_slot >= slot);
+
+        // A closed account is still delivered once so consumers see the
+        // closure, then it stops being tracked.
+        if lamports == 0 {
+            return self.tracked.remove(&pubkey) || triggered;
+        }
+
+        if triggered || self.tracked.contains(&pubkey) {
+            return true;
+        }
+
+        if self.discover_programs.contains(&owner) {
+            self.tracked.insert(pubkey);
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(config: AccountFilterConfig) -> AccountFilter {
+        AccountFilter::new(&config).unwrap()
+    }
+
+    fn matches(filter: &mut AccountFilter, pubkey: &Pubkey, owner: &Pubkey) -> bool {
+        filter.matches_parts(1, pubkey.as_ref(), owner.as_ref(), 1)
+    }
+
+    #[test]
+    fn allows_owners() {
+        let (owner, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut filter = build(AccountFilterConfig {
+            owners: vec![owner.to_string()],
+            ..AccountFilterConfig::default()
+        });
+        assert!(matches(&mut filter, &Pubkey::new_unique(), &owner));
+        assert!(!matches(&mut filter, &Pubkey::new_unique(), &other));
+    }
+
+    #[test]
+    fn excluded_owners_win() {
+        let (account, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut filter = build(AccountFilterConfig {
+            owners: vec![owner.to_string()],
+            exclude_owners: vec![owner.to_string()],
+            accounts: vec![account.to_string()],
+            ..AccountFilterConfig::default()
+        });
+        assert!(!matches(&mut filter, &account, &owner));
+        assert!(matches(&mut filter, &account, &Pubkey::new_unique()));
+
+        // Without an allowlist, everything else passes.
+        let mut filter = build(AccountFilterConfig {
+            exclude_owners: vec![owner.to_string()],
+            ..AccountFilterConfig::default()
+        });
+        assert!(!matches(&mut filter, &account, &owner));
+        assert!(matches(&mut filter, &account, &Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn reads_accounts_file() {
+        let (listed, unlisted) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let path = std::env::temp_dir().join(format!("fusion-accounts-{}", std::process::id()));
+        std::fs::write(&path, format!("# accounts\n\n  {}  \n", listed)).unwrap();
+        let mut filter = build(AccountFilterConfig {
+            accounts_file: Some(path.clone()),
+            ..AccountFilterConfig::default()
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        let owner = Pubkey::new_unique();
+        assert!(matches(&mut filter, &listed, &owner));
+        assert!(!matches(&mut filter, &unlisted, &owner));
+    }
+
+    #[test]
+    fn rejects_invalid_accounts_file_lines() {
+        let path = std::env::temp_dir().join(format!("fusion-invalid-{}", std::process::id()));
+        std::fs::write(&path, "not a pubkey\n").unwrap();
+        let built = AccountFilter::new(&AccountFilterConfig {
+            accounts_file: Some(path.clone()),
+            ..AccountFilterConfig::default()
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn drops_sysvars_by_default() {
+        let mut filter = build(AccountFilterConfig::default());
+        assert!(!matches(&mut filter, &sysvar::clock::id(), &sysvar::id()));
+        assert!(matches(
+            &mut filter,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique()
+        ));
+
+        let mut filter = build(AccountFilterConfig {
+            keep_sysvars: vec![sysvar::clock::id().to_string()],
+            ..AccountFilterConfig::default()
+        });
+        assert!(matches(&mut filter, &sysvar::clock::id(), &sysvar::id()));
+        assert!(!matches(&mut filter, &sysvar::rent::id(), &sysvar::id()));
+
+        let mut filter = build(AccountFilterConfig {
+            drop_sysvars: false,
+            ..AccountFilterConfig::default()
+        });
+        assert!(matches(&mut filter, &sysvar::rent::id(), &sysvar::id()));
+    }
+
+    #[test]
+    fn drops_unparseable_keys() {
+        let owner = Pubkey::new_unique();
+        let mut filter = build(AccountFilterConfig::default());
+        assert!(!filter.is_enabled());
+        assert!(!filter.matches_parts(1, &[1; 31], owner.as_ref(), 1));
+        assert!(!filter.matches_parts(1, owner.as_ref(), &[], 1));
+
+        let mut filter = build(AccountFilterConfig {
+            owners: vec![owner.to_string()],
+            ..AccountFilterConfig::default()
+        });
+        assert!(!filter.matches_parts(1, &[1; 31], owner.as_ref(), 1));
+    }
+}
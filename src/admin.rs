@@ -0,0 +1,414 @@
+//! Authenticated HTTP/1.1 API for inspecting and poking the running plugin.
+//!
+//! Every request needs an `Authorization: Bearer <token>` header.
+//!
+//! - `GET /config`: the config file, with secrets redacted
+//! - `GET /status`: sinks, queue depths, subscriptions, counters, account
+//!   cache usage and recent errors
+//! - `GET /schema/protobuf`, `GET /schema/envelope`: see [`crate::schema`]
+//! - `POST /flush`: syncs the output files to disk
+//! - `POST /rotate`: renames the output files aside and starts new ones
+//! - `GET /gaps`: slot ranges sinks dropped events of
+//! - `POST /gaps/<id>/backfill`: replays a gap from the archives
+//! - `POST /gaps/<id>/unrecoverable`: gives up on a gap
+//! - `GET /tokens/<owner>`: token accounts of a wallet, see
+//!   [`crate::TokenOwnerIndex`]
+//! - `GET /capture`: pubkeys under verbose capture, see [`crate::capture`]
+//! - `POST /capture/<pubkey>?ttl_secs=N`: captures a pubkey for a while
+//! - `DELETE /capture/<pubkey>`: stops capturing it early
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use smol::{
+    channel::{self, Receiver, Sender},
+    net::{TcpListener, TcpStream},
+    Task,
+};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    envelope_json_schema, protobuf_schema, AccTx, Gap, GapStatus, PluginMetrics, Subscribers,
+    TokenOwnerIndex, VerboseCapture,
+};
+
+const MAX_REQUEST_LEN: usize = 16 << 10;
+/// Config keys whose values are never shown.
+const SECRET_KEYS: [&str; 5] = [
+    "token",
+    "secret",
+    "password",
+    "connection_string",
+    "container_url",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminConfig {
+    pub address: SocketAddr,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AdminCommand {
+    Flush,
+    RotateFiles,
+    /// Replays the gap with this id, see [`crate::backfill`].
+    Backfill(u64),
+}
+
+/// Shared between the admin API and the background writer, which reports
+/// its sinks and runs the commands.
+#[derive(Debug)]
+pub struct AdminState {
+    sinks: Mutex<Value>,
+    commands: Sender<AdminCommand>,
+    command_receiver: Receiver<AdminCommand>,
+}
+
+impl Default for AdminState {
+    fn default() -> Self {
+        let (commands, command_receiver) = channel::unbounded();
+        AdminState {
+            sinks: Mutex::new(Value::Null),
+            commands,
+            command_receiver,
+        }
+    }
+}
+
+impl AdminState {
+    pub fn report(&self, sinks: Value) {
+        *self.sinks.lock().unwrap() = sinks;
+    }
+
+    pub async fn next_command(&self) -> Option<AdminCommand> {
+        self.command_receiver.recv().await.ok()
+    }
+}
+
+/// Everything the API reads from.
+#[derive(Debug)]
+pub struct AdminSources {
+    pub config_file: String,
+    pub metrics: Arc<PluginMetrics>,
+    pub subscribers: Option<Arc<Subscribers>>,
+    pub queue: Sender<AccTx>,
+    pub token_index: Option<Arc<TokenOwnerIndex>>,
+    pub capture: Arc<VerboseCapture>,
+    pub state: Arc<AdminState>,
+}
+
+/// Accept loop of the admin API, stopped on drop.
+#[derive(Debug)]
+pub struct AdminServer {
+    _listener: Task<()>,
+}
+
+impl AdminServer {
+    pub fn start(config: &AdminConfig, sources: AdminSources) -> GeyserResult<Self> {
+        let listener = smol::block_on(TcpListener::bind(config.address)).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to listen for admin requests on {}: {}",
+                    config.address, error
+                )
+                .into(),
+            )
+        })?;
+        let api = Arc::new(AdminApi {
+            token: config.token.clone(),
+            config: redacted_config(&sources.config_file),
+            sources,
+        });
+
+        let listener = smol::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let api = api.clone();
+                        smol::spawn(async move {
+                            if let Err(error) = api.serve(stream).await {
+                                warn!("Admin connection failed: {}", error);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(error) => warn!("Failed to accept admin connection: {}", error),
+                }
+            }
+        });
+        info!("Serving the admin API on http://{}", config.address);
+
+        Ok(AdminServer {
+            _listener: listener,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct AdminApi {
+    token: String,
+    config: Value,
+    sources: AdminSources,
+}
+
+impl AdminApi {
+    async fn serve(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 || request.len() + read > MAX_REQUEST_LEN {
+                return Ok(());
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut lines = request.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let method = request_line.next().unwrap_or_default();
+        let path = request_line.next().unwrap_or_default();
+        let authorized = lines.any(|line| {
+            line.split_once(':').is_some_and(|(name, value)| {
+                name.eq_ignore_ascii_case("authorization")
+                    && value.trim().strip_prefix("Bearer ") == Some(self.token.as_str())
+            })
+        });
+
+        let (status, body) = if authorized {
+            self.handle(method, path)
+        } else {
+            ("401 Unauthorized", json!({ "error": "unauthorized" }))
+        };
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    fn handle(&self, method: &str, path: &str) -> (&'static str, Value) {
+        match (method, path) {
+            ("GET", "/config") => ("200 OK", self.config.clone()),
+            ("GET", "/status") => ("200 OK", self.status()),
+            ("GET", "/schema/protobuf") => ("200 OK", Value::String(protobuf_schema())),
+            ("GET", "/schema/envelope") => ("200 OK", envelope_json_schema()),
+            ("POST", "/flush") => self.command(AdminCommand::Flush),
+            ("POST", "/rotate") => self.command(AdminCommand::RotateFiles),
+            ("GET", "/gaps") => (
+                "200 OK",
+                self.sources
+                    .metrics
+                    .gaps
+                    .list()
+                    .iter()
+                    .map(Gap::to_json)
+                    .collect(),
+            ),
+            ("GET", "/capture") => ("200 OK", self.captured()),
+            ("POST", path) if path.starts_with("/capture/") => self.capture(path),
+            ("DELETE", path) => match path.strip_prefix("/capture/") {
+                Some(pubkey) => self.release(pubkey),
+                None => not_found(),
+            },
+            ("POST", path) => self.gap_action(path),
+            ("GET", path) => match path.strip_prefix("/tokens/") {
+                Some(owner) => self.holdings(owner),
+                None => not_found(),
+            },
+            _ => not_found(),
+        }
+    }
+
+    fn gap_action(&self, path: &str) -> (&'static str, Value) {
+        let (id, action) = match path
+            .strip_prefix("/gaps/")
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(id, action)| Some((id.parse::<u64>().ok()?, action)))
+        {
+            Some(gap_action) => gap_action,
+            None => return not_found(),
+        };
+
+        match action {
+            "backfill" => self.command(AdminCommand::Backfill(id)),
+            "unrecoverable" => {
+                let gaps = &self.sources.metrics.gaps;
+                if gaps.resolve(id, GapStatus::Unrecoverable, 0) {
+                    ("200 OK", json!({ "gap": id, "status": "unrecoverable" }))
+                } else {
+                    not_found()
+                }
+            }
+            _ => not_found(),
+        }
+    }
+
+    fn captured(&self) -> Value {
+        self.sources
+            .capture
+            .list()
+            .into_iter()
+            .map(|(pubkey, remaining_secs)| {
+                json!({ "pubkey": pubkey.to_string(), "remaining_secs": remaining_secs })
+            })
+            .collect()
+    }
+
+    /// `path` is `/capture/<pubkey>`, optionally with `?ttl_secs=N`.
+    fn capture(&self, path: &str) -> (&'static str, Value) {
+        let path = path.trim_start_matches("/capture/");
+        let (pubkey, query) = path.split_once('?').unwrap_or((path, ""));
+        let pubkey = match pubkey.parse::<Pubkey>() {
+            Ok(pubkey) => pubkey,
+            Err(_) => return not_found(),
+        };
+        let ttl_secs = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("ttl_secs="))
+            .and_then(|ttl_secs| ttl_secs.parse().ok());
+
+        match self.sources.capture.enable(pubkey, ttl_secs) {
+            Ok(ttl_secs) => {
+                info!("Capturing {} verbosely for {}s", pubkey, ttl_secs);
+                (
+                    "200 OK",
+                    json!({ "pubkey": pubkey.to_string(), "ttl_secs": ttl_secs }),
+                )
+            }
+            Err(error) => ("409 Conflict", json!({ "error": error })),
+        }
+    }
+
+    fn release(&self, pubkey: &str) -> (&'static str, Value) {
+        match pubkey.parse::<Pubkey>() {
+            Ok(pubkey) if self.sources.capture.disable(&pubkey) => {
+                ("200 OK", json!({ "pubkey": pubkey.to_string() }))
+            }
+            _ => not_found(),
+        }
+    }
+
+    fn holdings(&self, owner: &str) -> (&'static str, Value) {
+        let (token_index, owner) = match (&self.sources.token_index, owner.parse::<Pubkey>()) {
+            (Some(token_index), Ok(owner)) => (token_index, owner),
+            _ => return not_found(),
+        };
+        let token_accounts: Vec<Value> = token_index
+            .holdings(&owner)
+            .into_iter()
+            .map(|(account, mint)| {
+                json!({ "account": account.to_string(), "mint": mint.to_string() })
+            })
+            .collect();
+
+        (
+            "200 OK",
+            json!({ "owner": owner.to_string(), "token_accounts": token_accounts }),
+        )
+    }
+
+    fn status(&self) -> Value {
+        let metrics = &self.sources.metrics;
+        let subscriptions: Vec<Value> = self
+            .sources
+            .subscribers
+            .iter()
+            .flat_map(|subscribers| subscribers.describe())
+            .map(|(id, filter)| json!({ "id": id, "filter": filter }))
+            .collect();
+
+        json!({
+            "sinks": self.sources.state.sinks.lock().unwrap().clone(),
+            "queues": {
+                "writer": self.sources.queue.len(),
+            },
+            "subscriptions": subscriptions,
+            "counters": {
+                "account_updates": PluginMetrics::get(&metrics.account_updates),
+                "transactions": PluginMetrics::get(&metrics.transactions),
+                "dropped_events": PluginMetrics::get(&metrics.dropped_events),
+                "quota_dropped_events": PluginMetrics::get(&metrics.quota_dropped_events),
+                "overflow_dropped_events": PluginMetrics::get(&metrics.overflow_dropped_events),
+                "startup_accounts": PluginMetrics::get(&metrics.startup_accounts),
+            },
+            "account_cache": account_cache_status(metrics),
+            "token_accounts": self.sources.token_index.as_deref().map(TokenOwnerIndex::len),
+            "recent_errors": metrics.recent_errors(),
+        })
+    }
+
+    fn command(&self, command: AdminCommand) -> (&'static str, Value) {
+        match self.sources.state.commands.try_send(command) {
+            Ok(()) => (
+                "202 Accepted",
+                json!({ "queued": format!("{:?}", command) }),
+            ),
+            Err(_) => (
+                "503 Service Unavailable",
+                json!({ "error": "writer stopped" }),
+            ),
+        }
+    }
+}
+
+fn account_cache_status(metrics: &PluginMetrics) -> Value {
+    let hits = PluginMetrics::get(&metrics.account_cache_hits);
+    let misses = PluginMetrics::get(&metrics.account_cache_misses);
+    let hit_rate = match hits + misses {
+        0 => None,
+        lookups => Some(hits as f64 / lookups as f64),
+    };
+
+    json!({
+        "hits": hits,
+        "misses": misses,
+        "hit_rate": hit_rate,
+        "entries": PluginMetrics::get(&metrics.account_cache_entries),
+        "bytes": PluginMetrics::get(&metrics.account_cache_bytes),
+    })
+}
+
+fn not_found() -> (&'static str, Value) {
+    ("404 Not Found", json!({ "error": "not found" }))
+}
+
+fn redacted_config(config_file: &str) -> Value {
+    let mut config = std::fs::read_to_string(config_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or(Value::Null);
+    redact(&mut config);
+    config
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = key.to_ascii_lowercase();
+                if SECRET_KEYS.iter().any(|secret| key.contains(secret)) {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
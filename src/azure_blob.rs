@@ -0,0 +1,476 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    io,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult, SlotStatus,
+};
+
+use crate::{
+    AccTx, Codec, CodecRegistry, EncodedEvent, PluginMetrics, Sink, SinkConfig, SlotUpdate,
+    Undelivered,
+};
+
+const API_VERSION: &str = "2021-08-06";
+/// Largest block Append Block accepts.
+const MAX_APPEND_BLOCK: usize = 4 << 20;
+/// Blocks an append blob holds before a new blob is started.
+const MAX_APPEND_BLOCKS: u32 = 50_000;
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Archives encoded events to an Azure Blob Storage container.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzureBlobConfig {
+    /// Container URL including a SAS token with create and write
+    /// permissions, e.g. `https://<account>.blob.core.windows.net/<container>?sv=...`.
+    pub container_url: String,
+    /// Prepended to blob names.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub mode: BlobMode,
+    /// Codec and projection of the records; use `"framed": true` so the
+    /// blobs can be read back with `fusion-cat`.
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+    /// Buffered bytes that trigger an upload.
+    #[serde(default = "default_batch_bytes")]
+    pub batch_bytes: usize,
+    /// Longest time records wait before an upload.
+    #[serde(default = "default_batch_secs")]
+    pub batch_secs: u64,
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Upload block blobs as `<name>.pending` and only write them under
+    /// their final name, without the events of slots that were not rooted,
+    /// once every slot they contain is rooted or skipped by a root.
+    #[serde(default)]
+    pub finalize_on_root: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobMode {
+    /// One block blob per batch, named after its slot range.
+    #[default]
+    Block,
+    /// Batches appended to an append blob, started anew on load and whenever
+    /// the blob is full.
+    Append,
+}
+
+fn default_batch_bytes() -> usize {
+    MAX_APPEND_BLOCK
+}
+
+fn default_batch_secs() -> u64 {
+    60
+}
+
+fn default_queue_capacity() -> usize {
+    100_000
+}
+
+struct BlobRecord {
+    bytes: Arc<[u8]>,
+    slot: u64,
+}
+
+enum BlobMessage {
+    Record(BlobRecord),
+    Rooted(u64),
+}
+
+/// Uploads batches of encoded events from a dedicated thread.
+#[derive(Debug)]
+pub struct AzureBlobSink {
+    codec: Arc<dyn Codec>,
+    finalize_on_root: bool,
+    sender: Option<SyncSender<BlobMessage>>,
+    thread: Option<JoinHandle<()>>,
+    undelivered: Undelivered,
+}
+
+impl AzureBlobSink {
+    pub fn start(
+        config: &AzureBlobConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let codec = codecs.build(&config.sink, "protobuf")?;
+        if config.finalize_on_root && config.mode == BlobMode::Append {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "Azure Blob finalize_on_root requires the block mode".to_string(),
+            });
+        }
+        let (base_url, sas) = match config.container_url.split_once('?') {
+            Some((base_url, sas)) => (base_url.trim_end_matches('/').to_string(), sas.to_string()),
+            None => {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: "Azure Blob container_url must include a SAS token".to_string(),
+                })
+            }
+        };
+        let client = Client::builder()
+            .build()
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+        let undelivered = Undelivered::default();
+        let worker = BlobWorker {
+            undelivered: undelivered.clone(),
+            client,
+            base_url,
+            sas,
+            prefix: config.prefix.clone(),
+            mode: config.mode,
+            batch_bytes: config.batch_bytes.max(1),
+            batch_timeout: Duration::from_secs(config.batch_secs.max(1)),
+            metrics,
+            batch: 0,
+            append_blob: None,
+            finalize_on_root: config.finalize_on_root,
+            pending: VecDeque::new(),
+            rooted: BTreeSet::new(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("fusionAzureBlob".to_string())
+            .spawn(move || worker.run(receiver))
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        info!("Archiving events to Azure Blob Storage");
+
+        Ok(AzureBlobSink {
+            codec,
+            finalize_on_root: config.finalize_on_root,
+            sender: Some(sender),
+            thread: Some(thread),
+            undelivered,
+        })
+    }
+
+    /// Queues `event` for upload, waiting while the queue is full.
+    pub fn send(&self, event: &EncodedEvent) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+        if let Some(bytes) = event.encode(&self.codec) {
+            self.undelivered.add(event.event.slot());
+            let _ = sender.send(BlobMessage::Record(BlobRecord {
+                bytes,
+                slot: event.event.slot(),
+            }));
+        }
+        if let AccTx::Slot(SlotUpdate {
+            slot,
+            status: SlotStatus::Rooted,
+            ..
+        }) = event.event
+        {
+            if self.finalize_on_root {
+                let _ = sender.send(BlobMessage::Rooted(*slot));
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        // Closing the queue makes the thread upload what is left and exit.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Sink for AzureBlobSink {
+    fn name(&self) -> &'static str {
+        "azure_blob"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        self.send(event);
+        Ok(())
+    }
+
+    fn pending(&self) -> u64 {
+        self.undelivered.get()
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.undelivered.oldest()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl Drop for AzureBlobSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct AppendBlob {
+    name: String,
+    blocks: u32,
+}
+
+/// A block blob uploaded as `<name>.pending` until its slots are rooted.
+struct PendingBlob {
+    name: String,
+    number: u64,
+    batch: Batch,
+}
+
+struct BlobWorker {
+    client: Client,
+    base_url: String,
+    sas: String,
+    prefix: String,
+    mode: BlobMode,
+    batch_bytes: usize,
+    batch_timeout: Duration,
+    metrics: Arc<PluginMetrics>,
+    undelivered: Undelivered,
+    batch: u64,
+    append_blob: Option<AppendBlob>,
+    finalize_on_root: bool,
+    pending: VecDeque<PendingBlob>,
+    /// Rooted slots that pending blobs may still contain.
+    rooted: BTreeSet<u64>,
+}
+
+#[derive(Default)]
+struct Batch {
+    records: Vec<BlobRecord>,
+    size: usize,
+    first_slot: Option<u64>,
+    last_slot: u64,
+    /// Lowest and highest slots of the records, which arrive out of order
+    /// across slots.
+    span: Option<(u64, u64)>,
+}
+
+impl Batch {
+    fn push(&mut self, record: BlobRecord) {
+        self.size += record.bytes.len();
+        self.first_slot.get_or_insert(record.slot);
+        self.last_slot = record.slot;
+        self.span = Some(match self.span {
+            Some((lowest, highest)) => (lowest.min(record.slot), highest.max(record.slot)),
+            None => (record.slot, record.slot),
+        });
+        self.records.push(record);
+    }
+
+    /// The records of the slots `keep` accepts, concatenated.
+    fn bytes(&self, keep: impl Fn(u64) -> bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size);
+        for record in self.records.iter().filter(|record| keep(record.slot)) {
+            bytes.extend_from_slice(&record.bytes);
+        }
+        bytes
+    }
+}
+
+impl BlobWorker {
+    fn run(mut self, receiver: Receiver<BlobMessage>) {
+        let mut closed = false;
+        while !closed {
+            let mut batch = Batch::default();
+            let deadline = Instant::now() + self.batch_timeout;
+            while batch.size < self.batch_bytes {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(BlobMessage::Record(record)) => batch.push(record),
+                    Ok(BlobMessage::Rooted(slot)) => self.finalize(slot, &batch),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+
+            if !batch.records.is_empty() {
+                self.upload(batch);
+            }
+        }
+
+        if !self.pending.is_empty() {
+            info!(
+                "Leaving {} Azure blobs pending with unrooted slots, their events undelivered",
+                self.pending.len()
+            );
+        }
+    }
+
+    /// Takes the events of `batch` off once they are written, or dropped as
+    /// a gap. Those of a pending blob are only once it is finalized.
+    fn upload(&mut self, batch: Batch) {
+        self.batch += 1;
+        let uploaded = match self.mode {
+            BlobMode::Block => {
+                let name = format!(
+                    "{}{}-{}-{}.bin",
+                    self.prefix,
+                    batch.first_slot.unwrap_or_default(),
+                    batch.last_slot,
+                    self.batch
+                );
+                if self.finalize_on_root {
+                    let pending = format!("{}.pending", name);
+                    if let Err(error) = self.put(&pending, "", "BlockBlob", batch.bytes(|_| true)) {
+                        warn!("Failed to upload {} ahead of its root: {}", pending, error);
+                    }
+                    self.pending.push_back(PendingBlob {
+                        name,
+                        number: self.batch,
+                        batch,
+                    });
+                    return;
+                }
+                self.put(&name, "", "BlockBlob", batch.bytes(|_| true))
+            }
+            BlobMode::Append => self.append(&batch),
+        };
+
+        if let Err(error) = uploaded {
+            self.dropped(self.batch, &batch, error);
+        }
+        self.undelivered
+            .done(batch.records.iter().map(|record| record.slot));
+    }
+
+    /// Writes the pending blobs whose slots are all at or below the rooted
+    /// `slot` under their final names, keeping the events of rooted slots
+    /// only, and deletes their `.pending` uploads. `collecting` is the batch
+    /// not uploaded yet, whose rooted slots are remembered as well.
+    fn finalize(&mut self, slot: u64, collecting: &Batch) {
+        self.rooted.insert(slot);
+        while self
+            .pending
+            .front()
+            .and_then(|pending| pending.batch.span)
+            .is_some_and(|(_, highest)| highest <= slot)
+        {
+            let pending = self.pending.pop_front().unwrap();
+            let bytes = pending.batch.bytes(|slot| self.rooted.contains(&slot));
+            match self.put(&pending.name, "", "BlockBlob", bytes) {
+                Ok(()) => self.delete(&format!("{}.pending", pending.name)),
+                Err(error) => self.dropped(pending.number, &pending.batch, error),
+            }
+            // Events of slots that were never rooted are left out for good.
+            self.undelivered
+                .done(pending.batch.records.iter().map(|record| record.slot));
+        }
+
+        let oldest = self
+            .pending
+            .iter()
+            .map(|pending| &pending.batch)
+            .chain([collecting])
+            .filter_map(|batch| batch.span)
+            .fold(slot, |oldest, (lowest, _)| oldest.min(lowest));
+        self.rooted = self.rooted.split_off(&oldest);
+    }
+
+    fn dropped(&self, number: u64, batch: &Batch, error: String) {
+        let records = batch.records.len() as u64;
+        let error = format!(
+            "Dropping batch {} of {} events after Azure Blob error: {}",
+            number, records, error
+        );
+        error!("{}", error);
+        self.metrics.record_error(error);
+        self.metrics.gaps.record(
+            "azure_blob",
+            batch.first_slot.unwrap_or_default(),
+            batch.last_slot,
+            records,
+        );
+        for _ in 0..records {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+        }
+    }
+
+    fn append(&mut self, batch: &Batch) -> Result<(), String> {
+        for block in batch.bytes(|_| true).chunks(MAX_APPEND_BLOCK) {
+            let full = self
+                .append_blob
+                .as_ref()
+                .is_none_or(|blob| blob.blocks >= MAX_APPEND_BLOCKS);
+            if full {
+                let name = format!(
+                    "{}{}-{}.bin",
+                    self.prefix,
+                    batch.first_slot.unwrap_or_default(),
+                    self.batch
+                );
+                self.put(&name, "", "AppendBlob", Vec::new())?;
+                self.append_blob = Some(AppendBlob { name, blocks: 0 });
+            }
+
+            let blob = self.append_blob.as_mut().unwrap();
+            let name = blob.name.clone();
+            blob.blocks += 1;
+            self.put(&name, "comp=appendblock&", "", block.to_vec())?;
+        }
+
+        Ok(())
+    }
+
+    /// PUTs `body` to the blob, retrying failed requests.
+    fn put(&self, name: &str, query: &str, blob_type: &str, body: Vec<u8>) -> Result<(), String> {
+        let url = format!("{}/{}?{}{}", self.base_url, name, query, self.sas);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut request = self
+                .client
+                .put(&url)
+                .header("x-ms-version", API_VERSION)
+                .body(body.clone());
+            if !blob_type.is_empty() {
+                request = request.header("x-ms-blob-type", blob_type);
+            }
+
+            let error = match request.send() {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => format!("{} from {}", response.status(), name),
+                Err(error) => error.to_string(),
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            warn!("Retrying Azure Blob upload: {}", error);
+            std::thread::sleep(Duration::from_secs(1 << attempt));
+        }
+    }
+
+    /// Deletes the blob, leaving it behind with a warning on failure.
+    fn delete(&self, name: &str) {
+        let url = format!("{}/{}?{}", self.base_url, name, self.sas);
+        let response = self
+            .client
+            .delete(&url)
+            .header("x-ms-version", API_VERSION)
+            .send();
+        match response {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!("Failed to delete {}: {}", name, response.status()),
+            Err(error) => warn!("Failed to delete {}: {}", name, error),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Slot ranges sinks failed to deliver, and their repair from the framed
+//! protobuf files the plugin itself wrote.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use log::warn;
+use prost::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    grpc::proto::{subscribe_update::Update, SubscribeUpdate},
+    Sink,
+};
+
+/// Gaps kept, oldest dropped first.
+const MAX_GAPS: usize = 1_000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// Files written with `"codec": "protobuf", "framed": true` that gaps
+    /// are replayed from, e.g. `["./accs.txt", "./txs.txt", "./slots.txt"]`.
+    pub archives: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapStatus {
+    Open,
+    Backfilled,
+    /// Replayed in part, the archives holding fewer events than dropped.
+    Partial,
+    Unrecoverable,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gap {
+    pub id: u64,
+    /// Sink that dropped the events, e.g. `kafka`.
+    pub sink: &'static str,
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub dropped_events: u64,
+    pub status: GapStatus,
+    pub replayed_events: u64,
+}
+
+impl Gap {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "sink": self.sink,
+            "first_slot": self.first_slot,
+            "last_slot": self.last_slot,
+            "dropped_events": self.dropped_events,
+            "status": match self.status {
+                GapStatus::Open => "open",
+                GapStatus::Backfilled => "backfilled",
+                GapStatus::Partial => "partial",
+                GapStatus::Unrecoverable => "unrecoverable",
+            },
+            "replayed_events": self.replayed_events,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GapLog {
+    gaps: Mutex<VecDeque<Gap>>,
+    next_id: AtomicU64,
+}
+
+impl GapLog {
+    /// Records dropped events, extending the latest open gap of the sink
+    /// when the slots touch it.
+    pub fn record(&self, sink: &'static str, first_slot: u64, last_slot: u64, dropped_events: u64) {
+        let mut gaps = self.gaps.lock().unwrap();
+        let open = gaps.iter_mut().rev().find(|gap| {
+            gap.sink == sink
+                && gap.status == GapStatus::Open
+                && first_slot <= gap.last_slot.saturating_add(1)
+                && last_slot.saturating_add(1) >= gap.first_slot
+        });
+        if let Some(gap) = open {
+            gap.first_slot = gap.first_slot.min(first_slot);
+            gap.last_slot = gap.last_slot.max(last_slot);
+            gap.dropped_events += dropped_events;
+            return;
+        }
+
+        if gaps.len() == MAX_GAPS {
+            gaps.pop_front();
+        }
+        gaps.push_back(Gap {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            sink,
+            first_slot,
+            last_slot,
+            dropped_events,
+            status: GapStatus::Open,
+            replayed_events: 0,
+        });
+    }
+
+    pub fn list(&self) -> Vec<Gap> {
+        self.gaps.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<Gap> {
+        self.gaps
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|gap| gap.id == id)
+            .cloned()
+    }
+
+    pub fn resolve(&self, id: u64, status: GapStatus, replayed_events: u64) -> bool {
+        let mut gaps = self.gaps.lock().unwrap();
+        match gaps.iter_mut().find(|gap| gap.id == id) {
+            Some(gap) => {
+                gap.status = status;
+                gap.replayed_events = replayed_events;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Replays an open gap from the archives to the sink that dropped it. It is
+/// backfilled once as many events were replayed as dropped, partial when
+/// fewer were, and unrecoverable when none were. A gap whose archives failed
+/// to be read stays open to be backfilled again. Only gaps of sinks that
+/// replay archived updates, such as Kafka, can be backfilled.
+pub(crate) fn backfill(
+    gaps: &GapLog,
+    id: u64,
+    config: Option<&BackfillConfig>,
+    sinks: &mut [Box<dyn Sink>],
+) {
+    let gap = match gaps.get(id) {
+        Some(gap) if gap.status == GapStatus::Open => gap,
+        _ => return,
+    };
+
+    let mut replayed = 0;
+    let sink = sinks.iter_mut().find(|sink| sink.name() == gap.sink);
+    if let (Some(config), Some(sink)) = (config, sink) {
+        let replayed_all =
+            replay_archives(&config.archives, gap.first_slot, gap.last_slot, |update| {
+                if sink.replay(update) {
+                    replayed += 1;
+                }
+            });
+        if let Err(error) = replayed_all {
+            warn!(
+                "Failed to backfill gap {} after {} of {} events: {}",
+                id, replayed, gap.dropped_events, error
+            );
+            gaps.resolve(id, GapStatus::Open, replayed);
+            return;
+        }
+    }
+
+    let status = if replayed >= gap.dropped_events {
+        GapStatus::Backfilled
+    } else if replayed > 0 {
+        GapStatus::Partial
+    } else {
+        GapStatus::Unrecoverable
+    };
+    gaps.resolve(id, status, replayed);
+}
+
+/// Calls `replay` with every archived update of the slot range, file by
+/// file in the order given.
+fn replay_archives(
+    archives: &[PathBuf],
+    first_slot: u64,
+    last_slot: u64,
+    mut replay: impl FnMut(&SubscribeUpdate),
+) -> io::Result<()> {
+    for archive in archives {
+        replay_archive(archive, first_slot, last_slot, &mut replay)?;
+    }
+    Ok(())
+}
+
+fn replay_archive(
+    archive: &Path,
+    first_slot: u64,
+    last_slot: u64,
+    replay: &mut impl FnMut(&SubscribeUpdate),
+) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(archive)?);
+    loop {
+        // A frame cut short is still being written.
+        let mut len = [0; 4];
+        let mut frame = Vec::new();
+        let read = reader.read_exact(&mut len).and_then(|()| {
+            frame = vec![0; u32::from_be_bytes(len) as usize];
+            reader.read_exact(&mut frame)
+        });
+        match read {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error),
+        }
+
+        let update = SubscribeUpdate::decode(frame.as_slice())
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        let slot = match &update.update {
+            Some(Update::Account(account)) => account.slot,
+            Some(Update::Transaction(transaction)) => transaction.slot,
+            Some(Update::Slot(slot)) => slot.slot,
+            _ => continue,
+        };
+        if (first_slot..=last_slot).contains(&slot) {
+            replay(&update);
+        }
+    }
+}
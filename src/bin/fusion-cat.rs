@@ -0,0 +1,350 @@
+//! Prints the records of framed protobuf archives, as written by sinks with
+//! `"codec": "protobuf", "framed": true`, or converts them: `json` writes
+//! JSON lines, `csv` a summary table, and `framed` a new archive holding the
+//! selected records.
+//!
+//! ```text
+//! fusion-cat [--format text|json|csv|framed] [--kind account|transaction|slot]
+//!            [--from-slot SLOT] [--to-slot SLOT] [--key PUBKEY|SIGNATURE] FILE...
+//! ```
+//!
+//! Reads stdin when no file is given. Files are skipped without being read
+//! when their manifest, written with `signature_bloom`, shows they hold
+//! nothing in the slot range or not the signature given as `--key`.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use fusion_engine_geyser::{
+    proto::{subscribe_update::Update, SlotStatus, SubscribeUpdate},
+    SegmentManifest,
+};
+use prost::Message;
+use serde_json::json;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+const USAGE: &str =
+    "Usage: fusion-cat [--format text|json|csv|framed] [--kind account|transaction|slot] \
+                     [--from-slot SLOT] [--to-slot SLOT] [--key PUBKEY|SIGNATURE] [FILE...]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+    Framed,
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    format: Option<Format>,
+    kind: Option<String>,
+    from_slot: Option<u64>,
+    to_slot: Option<u64>,
+    key: Option<Vec<u8>>,
+    files: Vec<String>,
+}
+
+/// The fields shared by every output format.
+struct Record {
+    kind: &'static str,
+    slot: u64,
+    key: String,
+    json: serde_json::Value,
+    text: String,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("fusion-cat: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                parsed.format = Some(match value()?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    "framed" => Format::Framed,
+                    other => return Err(format!("Unknown format {:?}", other)),
+                })
+            }
+            "--kind" => parsed.kind = Some(value()?),
+            "--from-slot" => parsed.from_slot = Some(parse_slot(&value()?)?),
+            "--to-slot" => parsed.to_slot = Some(parse_slot(&value()?)?),
+            "--key" => {
+                let key = value()?;
+                let bytes = match key.parse::<Pubkey>() {
+                    Ok(pubkey) => pubkey.to_bytes().to_vec(),
+                    Err(_) => key
+                        .parse::<Signature>()
+                        .map_err(|_| format!("Invalid pubkey or signature {:?}", key))?
+                        .as_ref()
+                        .to_vec(),
+                };
+                parsed.key = Some(bytes);
+            }
+            "-h" | "--help" => return Err("Prints framed protobuf archives.".to_string()),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ => parsed.files.push(arg),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_slot(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid slot {:?}", value))
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let format = args.format.unwrap_or(Format::Text);
+    let mut out = BufWriter::new(io::stdout().lock());
+    if format == Format::Csv {
+        writeln!(out, "kind,slot,key,summary")?;
+    }
+
+    if args.files.is_empty() {
+        cat(&mut io::stdin().lock(), args, format, &mut out)?;
+    }
+    for path in &args.files {
+        if skipped(Path::new(path), args)? {
+            continue;
+        }
+        let mut file = BufReader::new(File::open(path)?);
+        cat(&mut file, args, format, &mut out)?;
+    }
+
+    out.flush()
+}
+
+fn cat(input: &mut impl Read, args: &Args, format: Format, out: &mut impl Write) -> io::Result<()> {
+    while let Some(frame) = read_frame(input)? {
+        let update = SubscribeUpdate::decode(frame.as_slice())
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        let record = match update.update.and_then(|update| to_record(update, args)) {
+            Some(record) => record,
+            None => continue,
+        };
+        if args.kind.as_deref().is_some_and(|kind| kind != record.kind)
+            || args.from_slot.is_some_and(|slot| record.slot < slot)
+            || args.to_slot.is_some_and(|slot| record.slot > slot)
+        {
+            continue;
+        }
+
+        match format {
+            Format::Text => writeln!(
+                out,
+                "{:>10} {:<12} {}",
+                record.slot, record.kind, record.text
+            )?,
+            Format::Json => writeln!(out, "{}", record.json)?,
+            Format::Csv => writeln!(
+                out,
+                "{},{},{},{}",
+                record.kind,
+                record.slot,
+                record.key,
+                csv_field(&record.text)
+            )?,
+            Format::Framed => {
+                out.write_all(&(frame.len() as u32).to_be_bytes())?;
+                out.write_all(&frame)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the manifest of `path` rules out every record selected.
+fn skipped(path: &Path, args: &Args) -> io::Result<bool> {
+    let manifest = match SegmentManifest::read(path)? {
+        Some(manifest) => manifest,
+        None => return Ok(false),
+    };
+    let signature = args
+        .key
+        .as_deref()
+        .and_then(|key| Signature::try_from(key).ok());
+    let outside = |(first_slot, last_slot): (u64, u64)| {
+        args.from_slot.is_some_and(|slot| last_slot < slot)
+            || args.to_slot.is_some_and(|slot| first_slot > slot)
+    };
+    Ok(
+        signature.is_some_and(|signature| !manifest.signatures.contains(&signature))
+            || manifest.slots.is_some_and(outside),
+    )
+}
+
+/// `None` at a clean end of input.
+fn read_frame(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+    input.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn to_record(update: Update, args: &Args) -> Option<Record> {
+    let record = match update {
+        Update::Account(account) => {
+            if args.key.as_ref().is_some_and(|key| *key != account.pubkey) {
+                return None;
+            }
+            let pubkey = base58_pubkey(&account.pubkey);
+            let owner = base58_pubkey(&account.owner);
+            Record {
+                kind: "account",
+                slot: account.slot,
+                text: format!(
+                    "{} owner={} lamports={} data={}B",
+                    pubkey,
+                    owner,
+                    account.lamports,
+                    account.data.len()
+                ),
+                json: json!({
+                    "kind": "account",
+                    "slot": account.slot,
+                    "pubkey": pubkey,
+                    "owner": owner,
+                    "lamports": account.lamports,
+                    "executable": account.executable,
+                    "rent_epoch": account.rent_epoch,
+                    "data_len": account.data_len.unwrap_or(account.data.len() as u64),
+                    "write_version": account.write_version,
+                    "txn_signature": account.txn_signature.as_deref().map(base58_signature),
+                    "is_startup": account.is_startup,
+                    "data_hash": account.data_hash.as_deref().map(base58_pubkey),
+                    "trace_id": account.trace_id,
+                }),
+                key: pubkey,
+            }
+        }
+        Update::Transaction(transaction) => {
+            if args
+                .key
+                .as_ref()
+                .is_some_and(|key| *key != transaction.signature)
+            {
+                return None;
+            }
+            let signature = base58_signature(&transaction.signature);
+            Record {
+                kind: "transaction",
+                slot: transaction.slot,
+                text: format!(
+                    "{} {} fee={}",
+                    signature,
+                    transaction.err.as_deref().unwrap_or("ok"),
+                    transaction.fee
+                ),
+                json: json!({
+                    "kind": "transaction",
+                    "slot": transaction.slot,
+                    "signature": signature,
+                    "is_vote": transaction.is_vote,
+                    "index": transaction.index,
+                    "err": transaction.err,
+                    "fee": transaction.fee,
+                    "log_messages": transaction.log_messages,
+                    "compute_units_consumed": transaction.compute_units_consumed,
+                    "trace_id": transaction.trace_id,
+                    "recent_blockhash": base58_pubkey(&transaction.recent_blockhash),
+                    "account_keys": transaction
+                        .account_keys
+                        .iter()
+                        .map(|key| base58_pubkey(key))
+                        .collect::<Vec<_>>(),
+                    "instructions": transaction
+                        .instructions
+                        .iter()
+                        .map(|instruction| json!({
+                            "program_id_index": instruction.program_id_index,
+                            "accounts": instruction.accounts,
+                            "data_len": instruction.data.len(),
+                        }))
+                        .collect::<Vec<_>>(),
+                }),
+                key: signature,
+            }
+        }
+        Update::Slot(slot) => {
+            if args.key.is_some() {
+                return None;
+            }
+            let status = SlotStatus::try_from(slot.status)
+                .map(|status| format!("{:?}", status))
+                .unwrap_or_else(|_| slot.status.to_string());
+            Record {
+                kind: "slot",
+                slot: slot.slot,
+                text: format!("{} parent={:?}", status, slot.parent),
+                json: json!({
+                    "kind": "slot",
+                    "slot": slot.slot,
+                    "parent": slot.parent,
+                    "status": status,
+                }),
+                key: String::new(),
+            }
+        }
+        Update::FiltersApplied(_) => return None,
+    };
+
+    Some(record)
+}
+
+fn base58_pubkey(bytes: &[u8]) -> String {
+    Pubkey::try_from(bytes)
+        .map(|pubkey| pubkey.to_string())
+        .unwrap_or_default()
+}
+
+fn base58_signature(bytes: &[u8]) -> String {
+    Signature::try_from(bytes)
+        .map(|signature| signature.to_string())
+        .unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,24 @@
+//! Prints the schemas of what this build of the plugin emits.
+//!
+//! ```text
+//! fusion-schema protobuf|envelope
+//! ```
+
+use std::process::ExitCode;
+
+use fusion_engine_geyser::{envelope_json_schema, protobuf_schema};
+
+const USAGE: &str = "Usage: fusion-schema protobuf|envelope";
+
+fn main() -> ExitCode {
+    match std::env::args().nth(1).as_deref() {
+        Some("protobuf") => print!("{}", protobuf_schema()),
+        Some("envelope") => println!("{:#}", envelope_json_schema()),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
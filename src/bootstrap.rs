@@ -0,0 +1,131 @@
+use log::{error, info};
+use serde::Deserialize;
+use smol::channel::Sender;
+use solana_account_decoder::UiAccountEncoding;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    client_error::Error as ClientError,
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+};
+
+use crate::{config::parse_pubkey, AccTx, AccountUpdate};
+
+/// Most accounts `getMultipleAccounts` returns per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Fetches the current state of accounts over RPC at load, for plugins
+/// loaded into a running validator that replays no startup accounts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BootstrapConfig {
+    pub rpc_url: String,
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Every account owned by these programs is fetched.
+    #[serde(default)]
+    pub programs: Vec<String>,
+    #[serde(default)]
+    pub commitment: CommitmentLevel,
+}
+
+/// Emits the fetched accounts as startup updates from a background thread,
+/// interleaved with the live updates that arrive meanwhile.
+pub fn spawn_bootstrap(config: &BootstrapConfig, sender: Sender<AccTx>) -> GeyserResult<()> {
+    let accounts = parse_pubkeys(&config.accounts)?;
+    let programs = parse_pubkeys(&config.programs)?;
+    let client = RpcClient::new_with_commitment(
+        config.rpc_url.clone(),
+        CommitmentConfig {
+            commitment: config.commitment,
+        },
+    );
+
+    std::thread::Builder::new()
+        .name("fusionBootstrap".to_string())
+        .spawn(
+            move || match bootstrap(&client, &accounts, &programs, &sender) {
+                Ok(count) => info!("Bootstrapped {} accounts over RPC", count),
+                Err(error) => error!("Failed to bootstrap accounts over RPC: {}", error),
+            },
+        )
+        .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+
+    Ok(())
+}
+
+fn bootstrap(
+    client: &RpcClient,
+    accounts: &[Pubkey],
+    programs: &[Pubkey],
+    sender: &Sender<AccTx>,
+) -> Result<usize, Box<ClientError>> {
+    let mut count = 0;
+    let mut emit = |pubkey: &Pubkey, account: Account, slot: u64| {
+        count += 1;
+        let _ = smol::block_on(sender.send(snapshot(pubkey, account, slot)));
+    };
+
+    for chunk in accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let response = client.get_multiple_accounts_with_commitment(chunk, client.commitment())?;
+        for (pubkey, account) in chunk.iter().zip(response.value) {
+            if let Some(account) = account {
+                emit(pubkey, account, response.context.slot);
+            }
+        }
+    }
+
+    for program in programs {
+        // getProgramAccounts reports no context slot, so take one just
+        // before; the accounts are at least this recent.
+        let slot = client.get_slot()?;
+        let program_accounts = client.get_program_accounts_with_config(
+            program,
+            RpcProgramAccountsConfig {
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(client.commitment()),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+        for (pubkey, account) in program_accounts {
+            emit(&pubkey, account, slot);
+        }
+    }
+
+    Ok(count)
+}
+
+fn snapshot(pubkey: &Pubkey, account: Account, slot: u64) -> AccTx {
+    AccTx::Acc(AccountUpdate {
+        pubkey: pubkey.to_bytes().to_vec(),
+        lamports: account.lamports,
+        owner: account.owner.to_bytes().to_vec(),
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        data: account.data,
+        write_version: 0,
+        txn_signature: None,
+        slot,
+        is_startup: true,
+        data_hash: None,
+        data_len: None,
+        capture_ns: None,
+        wall_clock_ms: None,
+        unix_timestamp: None,
+        trace_id: None,
+        labels: Vec::new(),
+    })
+}
+
+fn parse_pubkeys(pubkeys: &[String]) -> GeyserResult<Vec<Pubkey>> {
+    pubkeys.iter().map(|pubkey| parse_pubkey(pubkey)).collect()
+}
@@ -0,0 +1,170 @@
+use solana_sdk::{hash::hash, pubkey, pubkey::Pubkey};
+
+use crate::{instructions::resolve_instructions, AccTx, TransactionUpdate};
+
+pub const BUBBLEGUM_PROGRAM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+pub const NOOP_PROGRAM_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedNftAction {
+    Mint,
+    Transfer,
+    Burn,
+}
+
+/// Tree, sequence number and leaf index of a `ChangeLogEvent::V1` logged by
+/// spl-account-compression through the Noop program.
+#[derive(Debug)]
+struct ChangeLog {
+    tree: Pubkey,
+    seq: u64,
+    index: u32,
+}
+
+impl ChangeLog {
+    fn unpack(data: &[u8]) -> Option<Self> {
+        // AccountCompressionEvent::ChangeLog, ChangeLogEvent::V1
+        if data.get(0..2)? != [0, 0] {
+            return None;
+        }
+        let tree = Pubkey::try_from(data.get(2..34)?).ok()?;
+        let path_len = u32::from_le_bytes(data.get(34..38)?.try_into().ok()?) as usize;
+        let offset = 38 + path_len.checked_mul(36)?;
+        let seq = u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?);
+        let index = u32::from_le_bytes(data.get(offset + 8..offset + 12)?.try_into().ok()?);
+
+        Some(ChangeLog { tree, seq, index })
+    }
+}
+
+/// Decodes Bubblegum mint, transfer and burn instructions into
+/// [`AccTx::CompressedNft`] events.
+#[derive(Debug)]
+pub struct BubblegumParser {
+    discriminators: Vec<([u8; 8], CompressedNftAction)>,
+}
+
+impl Default for BubblegumParser {
+    fn default() -> Self {
+        let discriminator = |name: &str| {
+            let mut discriminator = [0; 8];
+            discriminator
+                .copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+            discriminator
+        };
+
+        BubblegumParser {
+            discriminators: vec![
+                (discriminator("mint_v1"), CompressedNftAction::Mint),
+                (
+                    discriminator("mint_to_collection_v1"),
+                    CompressedNftAction::Mint,
+                ),
+                (discriminator("transfer"), CompressedNftAction::Transfer),
+                (discriminator("burn"), CompressedNftAction::Burn),
+            ],
+        }
+    }
+}
+
+impl BubblegumParser {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let (slot, signature, transaction, meta) = match event {
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => (*slot, signature, transaction, transaction_status_meta),
+            _ => return Vec::new(),
+        };
+        if meta.status.is_err() {
+            return Vec::new();
+        }
+
+        let instructions = resolve_instructions(transaction, meta);
+        let mut events = Vec::new();
+        for (position, instruction) in instructions.iter().enumerate() {
+            if *instruction.program_id != BUBBLEGUM_PROGRAM_ID {
+                continue;
+            }
+            let action = match instruction.data.get(0..8).and_then(|discriminator| {
+                self.discriminators
+                    .iter()
+                    .find(|(known, _)| known == discriminator)
+            }) {
+                Some((_, action)) => *action,
+                None => continue,
+            };
+
+            let change_log = instructions[position + 1..]
+                .iter()
+                .take_while(|next| *next.program_id != BUBBLEGUM_PROGRAM_ID)
+                .filter(|next| *next.program_id == NOOP_PROGRAM_ID)
+                .find_map(|next| ChangeLog::unpack(next.data));
+
+            // Transfer and burn carry root, data hash and creator hash ahead
+            // of the nonce; a mint's nonce is the leaf it was appended at.
+            let (owner, new_owner, tree_index, nonce) = match action {
+                CompressedNftAction::Mint => (
+                    instruction.account(1),
+                    None,
+                    3,
+                    change_log
+                        .as_ref()
+                        .map(|change_log| change_log.index as u64),
+                ),
+                CompressedNftAction::Transfer => (
+                    instruction.account(1),
+                    instruction.account(3),
+                    4,
+                    read_nonce(instruction.data),
+                ),
+                CompressedNftAction::Burn => (
+                    instruction.account(1),
+                    None,
+                    3,
+                    read_nonce(instruction.data),
+                ),
+            };
+            let tree = match change_log
+                .as_ref()
+                .map(|change_log| change_log.tree)
+                .or_else(|| instruction.account(tree_index).copied())
+            {
+                Some(tree) => tree,
+                None => continue,
+            };
+
+            events.push(AccTx::CompressedNft {
+                action,
+                tree,
+                asset_id: nonce.map(|nonce| asset_id(&tree, nonce)),
+                leaf_index: change_log.as_ref().map(|change_log| change_log.index),
+                seq: change_log.as_ref().map(|change_log| change_log.seq),
+                owner: owner.copied(),
+                new_owner: new_owner.copied(),
+                slot,
+                signature: *signature,
+            });
+        }
+
+        events
+    }
+}
+
+fn read_nonce(data: &[u8]) -> Option<u64> {
+    let offset = 8 + 32 * 3;
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn asset_id(tree: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"asset", tree.as_ref(), &nonce.to_le_bytes()],
+        &BUBBLEGUM_PROGRAM_ID,
+    )
+    .0
+}
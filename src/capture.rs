@@ -0,0 +1,138 @@
+//! Temporary in-depth capture of a few pubkeys, switched on through the
+//! admin API while investigating an incident.
+//!
+//! Updates of a captured account, and transactions mentioning one, skip the
+//! account and transaction filters, program quotas, degraded-mode shedding
+//! and executable data stripping, and are logged in full under the
+//! `fusion_engine::capture` target. Each pubkey expires on its own.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VerboseCaptureConfig {
+    /// Pubkeys captured at once, as capture bypasses the filters.
+    pub max_pubkeys: usize,
+    pub default_ttl_secs: u64,
+    pub max_ttl_secs: u64,
+}
+
+impl Default for VerboseCaptureConfig {
+    fn default() -> Self {
+        VerboseCaptureConfig {
+            max_pubkeys: 16,
+            default_ttl_secs: 300,
+            max_ttl_secs: 3_600,
+        }
+    }
+}
+
+/// Shared between the plugin callbacks and the admin API.
+#[derive(Debug, Default)]
+pub struct VerboseCapture {
+    config: VerboseCaptureConfig,
+    /// Checked first, so callbacks pay one atomic load while nothing is
+    /// captured.
+    active: AtomicBool,
+    /// When each pubkey stops being captured.
+    pubkeys: Mutex<HashMap<Pubkey, Instant>>,
+}
+
+impl VerboseCapture {
+    pub fn new(config: &VerboseCaptureConfig) -> Self {
+        VerboseCapture {
+            config: config.clone(),
+            ..VerboseCapture::default()
+        }
+    }
+
+    /// Captures `pubkey` for `ttl_secs`, the default when unset, up to the
+    /// maximum, and returns the seconds granted.
+    pub fn enable(&self, pubkey: Pubkey, ttl_secs: Option<u64>) -> Result<u64, String> {
+        let ttl_secs = ttl_secs
+            .unwrap_or(self.config.default_ttl_secs)
+            .min(self.config.max_ttl_secs);
+        let mut pubkeys = self.pubkeys.lock().unwrap();
+        expire(&mut pubkeys);
+        if !pubkeys.contains_key(&pubkey) && pubkeys.len() >= self.config.max_pubkeys {
+            return Err(format!(
+                "Already capturing {} pubkeys, the maximum",
+                pubkeys.len()
+            ));
+        }
+        pubkeys.insert(pubkey, Instant::now() + Duration::from_secs(ttl_secs));
+        self.active.store(true, Ordering::Relaxed);
+        Ok(ttl_secs)
+    }
+
+    /// Whether `pubkey` was being captured.
+    pub fn disable(&self, pubkey: &Pubkey) -> bool {
+        let mut pubkeys = self.pubkeys.lock().unwrap();
+        let removed = pubkeys.remove(pubkey).is_some();
+        self.active.store(!pubkeys.is_empty(), Ordering::Relaxed);
+        removed
+    }
+
+    /// The captured pubkeys with their remaining seconds.
+    pub fn list(&self) -> Vec<(Pubkey, u64)> {
+        let mut pubkeys = self.pubkeys.lock().unwrap();
+        expire(&mut pubkeys);
+        let now = Instant::now();
+        let mut captured: Vec<(Pubkey, u64)> = pubkeys
+            .iter()
+            .map(|(pubkey, until)| (*pubkey, until.duration_since(now).as_secs()))
+            .collect();
+        captured.sort_unstable();
+        captured
+    }
+
+    pub fn captures(&self, pubkey: &[u8]) -> bool {
+        if !self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+        match Pubkey::try_from(pubkey) {
+            Ok(pubkey) => self.any(&[pubkey]),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether the transaction mentions a captured pubkey.
+    pub fn captures_transaction(&self, transaction: &ReplicaTransactionInfoVersions) -> bool {
+        if !self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+        let message = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(transaction) => {
+                transaction.transaction.message()
+            }
+            ReplicaTransactionInfoVersions::V0_0_2(transaction) => {
+                transaction.transaction.message()
+            }
+        };
+        let keys: Vec<Pubkey> = message.account_keys().iter().copied().collect();
+        self.any(&keys)
+    }
+
+    fn any(&self, keys: &[Pubkey]) -> bool {
+        let mut pubkeys = self.pubkeys.lock().unwrap();
+        expire(&mut pubkeys);
+        self.active.store(!pubkeys.is_empty(), Ordering::Relaxed);
+        keys.iter().any(|key| pubkeys.contains_key(key))
+    }
+}
+
+fn expire(pubkeys: &mut HashMap<Pubkey, Instant>) {
+    let now = Instant::now();
+    pubkeys.retain(|_, until| *until > now);
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use libloading::{Library, Symbol};
+use log::{info, warn};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPlugin, GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
+    ReplicaTransactionInfoVersions, Result as GeyserResult, SlotStatus,
+};
+
+/// Another Geyser plugin this one loads and forwards notifications to, so
+/// plugins without filters of their own only see matching accounts and
+/// transactions, and blocks and slots only when their notifications are
+/// enabled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainedPluginConfig {
+    /// Shared library exporting `_create_plugin`, built against the same
+    /// plugin interface and compiler as this plugin.
+    pub libpath: PathBuf,
+    /// Passed to the chained plugin's `on_load`.
+    pub config_file: String,
+}
+
+type PluginConstructor = unsafe fn() -> *mut dyn GeyserPlugin;
+
+/// A loaded plugin, unloaded on drop before its library.
+#[derive(Debug)]
+pub struct ChainedPlugin {
+    plugin: Box<dyn GeyserPlugin>,
+    _library: Library,
+}
+
+impl ChainedPlugin {
+    pub fn load(config: &ChainedPluginConfig) -> GeyserResult<Self> {
+        let load_error = |error: libloading::Error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to load chained plugin {:?}: {}",
+                    config.libpath, error
+                )
+                .into(),
+            )
+        };
+
+        // Safety: the library is trusted configuration, and like the
+        // validator's plugin manager we rely on it matching this interface.
+        let (library, mut plugin) = unsafe {
+            let library = Library::new(&config.libpath).map_err(load_error)?;
+            let constructor: Symbol<PluginConstructor> =
+                library.get(b"_create_plugin").map_err(load_error)?;
+            let plugin = Box::from_raw(constructor());
+            (library, plugin)
+        };
+        plugin.on_load(&config.config_file)?;
+        info!(
+            "Loaded chained plugin {:?} from {:?}",
+            plugin.name(),
+            config.libpath
+        );
+
+        Ok(ChainedPlugin {
+            plugin,
+            _library: library,
+        })
+    }
+
+    pub fn update_account(
+        &mut self,
+        account: &ReplicaAccountInfoVersions,
+        slot: u64,
+        is_startup: bool,
+    ) {
+        let account = match account {
+            ReplicaAccountInfoVersions::V0_0_1(account) => {
+                ReplicaAccountInfoVersions::V0_0_1(account)
+            }
+            ReplicaAccountInfoVersions::V0_0_2(account) => {
+                ReplicaAccountInfoVersions::V0_0_2(account)
+            }
+        };
+        if self.plugin.account_data_notifications_enabled() {
+            let result = self.plugin.update_account(account, slot, is_startup);
+            self.log_error(result);
+        }
+    }
+
+    pub fn notify_transaction(&mut self, transaction: &ReplicaTransactionInfoVersions, slot: u64) {
+        let transaction = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(transaction) => {
+                ReplicaTransactionInfoVersions::V0_0_1(transaction)
+            }
+            ReplicaTransactionInfoVersions::V0_0_2(transaction) => {
+                ReplicaTransactionInfoVersions::V0_0_2(transaction)
+            }
+        };
+        if self.plugin.transaction_notifications_enabled() {
+            let result = self.plugin.notify_transaction(transaction, slot);
+            self.log_error(result);
+        }
+    }
+
+    pub fn notify_block_metadata(&mut self, blockinfo: &ReplicaBlockInfoVersions) {
+        let blockinfo = match blockinfo {
+            ReplicaBlockInfoVersions::V0_0_1(blockinfo) => {
+                ReplicaBlockInfoVersions::V0_0_1(blockinfo)
+            }
+            ReplicaBlockInfoVersions::V0_0_2(blockinfo) => {
+                ReplicaBlockInfoVersions::V0_0_2(blockinfo)
+            }
+        };
+        let result = self.plugin.notify_block_metadata(blockinfo);
+        self.log_error(result);
+    }
+
+    pub fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) {
+        let result = self.plugin.update_slot_status(slot, parent, status);
+        self.log_error(result);
+    }
+
+    pub fn notify_end_of_startup(&mut self) {
+        let result = self.plugin.notify_end_of_startup();
+        self.log_error(result);
+    }
+
+    /// Errors of a chained plugin never fail this one.
+    fn log_error(&self, result: GeyserResult<()>) {
+        if let Err(error) = result {
+            warn!("Chained plugin {:?} failed: {}", self.plugin.name(), error);
+        }
+    }
+}
+
+impl Drop for ChainedPlugin {
+    fn drop(&mut self) {
+        self.plugin.on_unload();
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_sdk::{
+    clock::{Clock, DEFAULT_MS_PER_SLOT},
+    sysvar,
+};
+
+use crate::{AccTx, AccountUpdate, TransactionUpdate};
+
+const MAX_SAMPLES: usize = 1024;
+
+/// Which timestamps account and transaction events carry. Any combination
+/// can be enabled; each fills its own field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TimestampsConfig {
+    /// `capture_ns`: monotonic nanoseconds since the plugin loaded, taken
+    /// when the validator notified the plugin.
+    pub capture: bool,
+    /// `wall_clock_ms`: unix milliseconds of the same instant.
+    pub wall_clock: bool,
+    /// `unix_timestamp`: the block time, estimated from Clock sysvar
+    /// updates.
+    pub block_time: bool,
+}
+
+/// Stamps events with their capture time in the Geyser callbacks.
+#[derive(Debug, Clone)]
+pub struct CaptureClock {
+    loaded: Instant,
+    capture: bool,
+    wall_clock: bool,
+}
+
+impl Default for CaptureClock {
+    fn default() -> Self {
+        CaptureClock::new(&TimestampsConfig::default())
+    }
+}
+
+impl CaptureClock {
+    pub fn new(config: &TimestampsConfig) -> Self {
+        CaptureClock {
+            loaded: Instant::now(),
+            capture: config.capture,
+            wall_clock: config.wall_clock,
+        }
+    }
+
+    pub fn stamp(&self, event: &mut AccTx) {
+        if let AccTx::Acc(AccountUpdate {
+            capture_ns,
+            wall_clock_ms,
+            ..
+        })
+        | AccTx::Tx(TransactionUpdate {
+            capture_ns,
+            wall_clock_ms,
+            ..
+        }) = event
+        {
+            if self.capture {
+                *capture_ns = Some(self.loaded.elapsed().as_nanos() as u64);
+            }
+            if self.wall_clock {
+                *wall_clock_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|elapsed| elapsed.as_millis() as i64);
+            }
+        }
+    }
+}
+
+/// Unix timestamps read from Clock sysvar updates, shared between the Geyser
+/// callbacks and the background writer so events can be timestamped before
+/// block metadata arrives.
+#[derive(Debug, Default)]
+pub struct SlotClock {
+    samples: Mutex<BTreeMap<u64, i64>>,
+}
+
+impl SlotClock {
+    pub fn observe(&self, account: &ReplicaAccountInfoVersions) {
+        let (pubkey, data) = match account {
+            ReplicaAccountInfoVersions::V0_0_1(inner_account) => {
+                (inner_account.pubkey, inner_account.data)
+            }
+            ReplicaAccountInfoVersions::V0_0_2(inner_account) => {
+                (inner_account.pubkey, inner_account.data)
+            }
+        };
+        if pubkey != sysvar::clock::id().as_ref() {
+            return;
+        }
+
+        if let Ok(clock) = bincode::deserialize::<Clock>(data) {
+            let mut samples = self.samples.lock().unwrap();
+            samples.insert(clock.slot, clock.unix_timestamp);
+            while samples.len() > MAX_SAMPLES {
+                samples.pop_first();
+            }
+        }
+    }
+
+    /// The Clock timestamp of `slot`, or one extrapolated from the closest
+    /// sampled slot at the nominal slot duration.
+    pub fn estimate(&self, slot: u64) -> Option<i64> {
+        let samples = self.samples.lock().unwrap();
+        let (sampled_slot, unix_timestamp) = samples
+            .range(..=slot)
+            .next_back()
+            .or_else(|| samples.range(slot..).next())?;
+
+        let elapsed_ms = (slot as i64 - *sampled_slot as i64) * DEFAULT_MS_PER_SLOT as i64;
+        Some(unix_timestamp + elapsed_ms / 1000)
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use prost::Message;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::{
+    grpc::proto::SubscribeUpdate, AccTx, BincodeCodec, FieldProjection, JsonCodec, PrettyCodec,
+    SinkConfig, ZstdCodec, ZstdDictionaries,
+};
+
+/// Version of the event schemas, bumped on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Serializes events for a sink.
+pub trait Codec: fmt::Debug + Send + Sync {
+    /// `None` when the event has no representation in this format.
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>>;
+
+    /// Whether records are already length-prefixed, so `framed` sinks use
+    /// them as they are.
+    fn framed(&self) -> bool {
+        false
+    }
+
+    /// Whether records leave out the fields of the sink's projection; sinks
+    /// with a projection only take codecs that do.
+    fn projects(&self) -> bool {
+        false
+    }
+}
+
+/// Constructors should only depend on the sink's projection, since sinks
+/// selecting the same codec, framing and projection share one instance.
+pub type CodecConstructor = fn(&SinkConfig) -> Arc<dyn Codec>;
+
+#[derive(Debug)]
+struct BuiltCodec {
+    name: String,
+    fallback: Option<String>,
+    framed: bool,
+    zstd: bool,
+    projection: FieldProjection,
+    codec: Arc<dyn Codec>,
+}
+
+/// Codecs sinks can select by name in their config.
+#[derive(Debug, Clone)]
+pub struct CodecRegistry {
+    codecs: BTreeMap<&'static str, CodecConstructor>,
+    built: Arc<Mutex<Vec<BuiltCodec>>>,
+    dictionaries: Arc<ZstdDictionaries>,
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        let mut registry = CodecRegistry {
+            codecs: BTreeMap::new(),
+            built: Arc::default(),
+            dictionaries: Arc::default(),
+        };
+        registry.register("debug", |sink| {
+            Arc::new(DebugCodec {
+                projection: sink.projection.clone(),
+            })
+        });
+        registry.register("pretty", |_| Arc::new(PrettyCodec::default()));
+        registry.register("protobuf", |sink| {
+            Arc::new(ProtobufCodec {
+                projection: sink.projection.clone(),
+            })
+        });
+        registry.register("json", |sink| {
+            Arc::new(JsonCodec::new(sink.projection.clone()))
+        });
+        registry.register("bincode", |sink| {
+            Arc::new(BincodeCodec::new(sink.projection.clone()))
+        });
+        registry
+    }
+}
+
+impl CodecRegistry {
+    pub fn register(&mut self, name: &'static str, constructor: CodecConstructor) {
+        self.codecs.insert(name, constructor);
+    }
+
+    /// Used by the codecs of sinks with `zstd` set, which otherwise compress
+    /// without dictionaries.
+    pub fn use_dictionaries(&mut self, dictionaries: Arc<ZstdDictionaries>) {
+        self.dictionaries = dictionaries;
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.codecs.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.codecs.keys().copied()
+    }
+
+    /// Builds the codec selected by `sink`, or `default` when it names none,
+    /// or returns the one already built for the same selection so that
+    /// [`EncodedEvent`] encodes each event once for all of its sinks.
+    pub fn build(&self, sink: &SinkConfig, default: &str) -> GeyserResult<Arc<dyn Codec>> {
+        let name = sink.codec.as_deref().unwrap_or(default);
+        let mut built = self.built.lock().unwrap();
+        let existing = built.iter().find(|built| {
+            built.name == name
+                && built.fallback == sink.fallback_codec
+                && built.framed == sink.framed
+                && built.zstd == sink.zstd
+                && built.projection == sink.projection
+        });
+        if let Some(existing) = existing {
+            return Ok(existing.codec.clone());
+        }
+
+        let codec = self.construct(name, sink)?;
+        let codec: Arc<dyn Codec> = match &sink.fallback_codec {
+            Some(fallback) => Arc::new(FallbackCodec {
+                primary: codec,
+                fallback: self.construct(fallback, sink)?,
+            }),
+            None => codec,
+        };
+        let codec: Arc<dyn Codec> = if sink.zstd {
+            Arc::new(ZstdCodec {
+                inner: codec,
+                dictionaries: self.dictionaries.clone(),
+            })
+        } else {
+            codec
+        };
+        let codec: Arc<dyn Codec> = if sink.framed && !codec.framed() {
+            Arc::new(FramedCodec(codec))
+        } else {
+            codec
+        };
+        built.push(BuiltCodec {
+            name: name.to_string(),
+            fallback: sink.fallback_codec.clone(),
+            framed: sink.framed,
+            zstd: sink.zstd,
+            projection: sink.projection.clone(),
+            codec: codec.clone(),
+        });
+        Ok(codec)
+    }
+
+    fn construct(&self, name: &str, sink: &SinkConfig) -> GeyserResult<Arc<dyn Codec>> {
+        let codec = match self.codecs.get(name) {
+            Some(constructor) => constructor(sink),
+            None => {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "Unknown codec {:?}, expected one of {:?}",
+                        name,
+                        self.names().collect::<Vec<_>>()
+                    ),
+                })
+            }
+        };
+        if sink.projection != FieldProjection::default() && !codec.projects() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!("Codec {:?} does not support field projections", name),
+            });
+        }
+        Ok(codec)
+    }
+}
+
+/// An event on its way to the sinks, with its encoding in each codec
+/// computed on first use and shared by every sink using that codec.
+pub struct EncodedEvent<'a> {
+    pub event: &'a AccTx,
+    encodings: RefCell<Vec<(Arc<dyn Codec>, Option<Arc<[u8]>>)>>,
+}
+
+impl<'a> EncodedEvent<'a> {
+    pub fn new(event: &'a AccTx) -> Self {
+        EncodedEvent {
+            event,
+            encodings: RefCell::default(),
+        }
+    }
+
+    pub fn encode(&self, codec: &Arc<dyn Codec>) -> Option<Arc<[u8]>> {
+        let mut encodings = self.encodings.borrow_mut();
+        if let Some((_, bytes)) = encodings.iter().find(|(used, _)| Arc::ptr_eq(used, codec)) {
+            return bytes.clone();
+        }
+
+        let bytes: Option<Arc<[u8]>> = codec.encode(self.event).map(Arc::from);
+        encodings.push((codec.clone(), bytes.clone()));
+        bytes
+    }
+}
+
+/// Length-prefixes the records of another codec.
+#[derive(Debug)]
+struct FramedCodec(Arc<dyn Codec>);
+
+impl Codec for FramedCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        Some(frame(&self.0.encode(event)?))
+    }
+
+    fn framed(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes with a second codec what the first one cannot, so an event a
+/// decoding codec fails on still reaches the sink in a raw form.
+#[derive(Debug)]
+struct FallbackCodec {
+    primary: Arc<dyn Codec>,
+    fallback: Arc<dyn Codec>,
+}
+
+impl Codec for FallbackCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        self.primary
+            .encode(event)
+            .or_else(|| self.fallback.encode(event))
+    }
+
+    fn framed(&self) -> bool {
+        self.primary.framed() && self.fallback.framed()
+    }
+}
+
+/// Prefixes a record with its length as a big-endian `u32`.
+pub(crate) fn frame(record: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(4 + record.len());
+    frame.extend_from_slice(&(record.len() as u32).to_be_bytes());
+    frame.extend_from_slice(record);
+    frame
+}
+
+/// Rust `Debug` output of the projected event.
+#[derive(Debug)]
+pub struct DebugCodec {
+    projection: FieldProjection,
+}
+
+impl Codec for DebugCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        Some(event.into_projected_bytes(&self.projection))
+    }
+
+    fn projects(&self) -> bool {
+        true
+    }
+}
+
+/// [`SubscribeUpdate`] messages, for the events the schema covers, with the
+/// fields the projection leaves out cleared.
+#[derive(Debug)]
+pub struct ProtobufCodec {
+    projection: FieldProjection,
+}
+
+impl Codec for ProtobufCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        let mut update = SubscribeUpdate::from_event(event)?;
+        update.project(&self.projection);
+        Some(update.encode_to_vec())
+    }
+
+    fn projects(&self) -> bool {
+        true
+    }
+}
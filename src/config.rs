@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    AccountCacheConfig, AccountFilterConfig, AdminConfig, AzureBlobConfig, BackfillConfig,
+    BootstrapConfig, BufferCommitment, ChainedPluginConfig, DatadogConfig, DegradedModeConfig,
+    DeltaConfig, EpochEventsConfig, FieldProjection, ForkStatsConfig, GrpcConfig, HeartbeatConfig,
+    KafkaConfig, MintSupplyConfig, MulticastConfig, NatsConfig, OwnerLamportsConfig,
+    PostgresConfig, PrettyConfig, ProgramQuotasConfig, PrometheusConfig, QueueConfig,
+    RawTraceConfig, RedactionConfig, ShmRingConfig, SignatureBloomConfig, SinkErrorPolicy,
+    SinkLatencyConfig, SlotBufferConfig, SlotStatsConfig, StartupConfig, StreamConfig,
+    ThroughputConfig, TimestampsConfig, TransactionFilterConfig, UdsConfig, VerboseCaptureConfig,
+    WalConfig, WatermarkConfig, WebSocketConfig, ZstdDictionaryConfig,
+};
+
+/// Plugin settings parsed from the JSON file handed to `on_load`.
+///
+/// Unknown keys (such as the validator's own `libpath`) are ignored and
+/// every section falls back to its default when omitted.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    /// Directory the output files are created in, the validator's working
+    /// directory by default.
+    pub output_dir: PathBuf,
+    /// Written to `accs.txt` unless `path` is set.
+    pub accounts: SinkConfig,
+    /// Written to `txs.txt` unless `path` is set.
+    pub transactions: SinkConfig,
+    /// Notifications the validator sends the plugin.
+    pub notifications: NotificationsConfig,
+    /// Output for slot status updates, `slots.txt` by default.
+    pub slots: SinkConfig,
+    /// Output for block metadata, `blocks.txt` by default.
+    pub blocks: SinkConfig,
+    /// Output for derived events such as aggregates, `events.txt` by
+    /// default.
+    pub events: SinkConfig,
+    /// Output for periodic monitoring events such as throughput,
+    /// `metrics.txt` by default.
+    pub metrics: SinkConfig,
+    /// Serves events to gRPC subscribers when set.
+    pub grpc: Option<GrpcConfig>,
+    /// Serves events over raw TCP and Unix domain socket streams when set.
+    pub stream: Option<StreamConfig>,
+    /// Pushes JSON events to WebSocket clients, each with its own filters,
+    /// when set.
+    pub websocket: Option<WebSocketConfig>,
+    /// Produces events to Kafka when set.
+    pub kafka: Option<KafkaConfig>,
+    /// Archives events to Azure Blob Storage when set.
+    pub azure_blob: Option<AzureBlobConfig>,
+    /// Commits events to a Delta Lake table when set.
+    pub delta: Option<DeltaConfig>,
+    /// Publishes events to a UDP multicast group when set.
+    pub multicast: Option<MulticastConfig>,
+    /// Publishes every event to NATS, optionally through JetStream, when set.
+    pub nats: Option<NatsConfig>,
+    /// Writes accounts, transactions and slots to PostgreSQL when set.
+    pub postgres: Option<PostgresConfig>,
+    /// Writes events to a shared-memory ring buffer consumers poll when set.
+    pub shm_ring: Option<ShmRingConfig>,
+    /// Pushes framed events to a consumer's Unix domain socket when set.
+    pub uds: Option<UdsConfig>,
+    /// Human-readable summaries of every event when set.
+    pub pretty: Option<PrettyConfig>,
+    /// Heartbeats on idle file and Kafka sinks when set.
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Periodic completeness watermarks on file and Kafka sinks when set.
+    pub watermarks: Option<WatermarkConfig>,
+    pub datadog: Option<DatadogConfig>,
+    /// Serves the counters for Prometheus to scrape when set.
+    pub prometheus: Option<PrometheusConfig>,
+    /// Debugging aid recording every notification as delivered, before
+    /// filtering and without payloads, when set.
+    pub raw_trace: Option<RawTraceConfig>,
+    /// Per-owner dictionaries for sinks with `zstd` set, trained from their
+    /// records when a retrain interval is set.
+    pub zstd_dictionaries: Option<ZstdDictionaryConfig>,
+    /// Serves the admin HTTP API when set.
+    pub admin: Option<AdminConfig>,
+    /// Limits of the verbose capture the admin API switches on.
+    pub verbose_capture: VerboseCaptureConfig,
+    /// Lets the admin API replay gaps from archive files when set.
+    pub backfill: Option<BackfillConfig>,
+    /// Plugins loaded by this one, which receive the accounts passing
+    /// `account_filter` and every other notification.
+    pub chain: Vec<ChainedPluginConfig>,
+    pub account_filter: AccountFilterConfig,
+    /// Transactions passing it are emitted; chained plugins still receive
+    /// every transaction.
+    pub transaction_filter: TransactionFilterConfig,
+    /// Caps the account updates per second of programs when set.
+    pub program_quotas: Option<ProgramQuotasConfig>,
+    /// Capacity of the writer's queue and what happens when it is full.
+    pub queue: QueueConfig,
+    /// Draining of the writer when the plugin unloads.
+    pub shutdown: ShutdownConfig,
+    /// Bulk writing of the accounts replayed at startup.
+    pub startup: StartupConfig,
+    /// Emits the current state of accounts fetched over RPC at load.
+    pub bootstrap: Option<BootstrapConfig>,
+    /// Drop account data under queue pressure when set.
+    pub degraded_mode: Option<DegradedModeConfig>,
+    /// Delay every output until its slot is old enough, or reaches a
+    /// commitment, when set.
+    pub slot_buffer: Option<SlotBufferConfig>,
+    /// Log events from their callback until the writer is done with them,
+    /// recovering those a crash left behind at the next load, when set.
+    pub wal: Option<WalConfig>,
+    /// Time every output's writes, moving those that keep exceeding the
+    /// budget onto their own queue, when set.
+    pub sink_latency: Option<SinkLatencyConfig>,
+    pub redaction: RedactionConfig,
+    /// JSON file mapping pubkeys to labels attached to emitted events.
+    pub labels_file: Option<PathBuf>,
+    /// Latest state of every account, kept for lifecycle events and
+    /// bounded by these settings when set.
+    pub account_cache: Option<AccountCacheConfig>,
+    /// Replace the data of executable accounts, program binaries, with its
+    /// hash and length.
+    pub strip_executable_data: bool,
+    /// Emit created/reassigned/resized/closed events for accounts.
+    pub lifecycle_events: bool,
+    pub mint_supply: Option<MintSupplyConfig>,
+    pub owner_lamports: Option<OwnerLamportsConfig>,
+    pub slot_stats: Option<SlotStatsConfig>,
+    pub epoch_events: Option<EpochEventsConfig>,
+    pub fork_stats: Option<ForkStatsConfig>,
+    /// Emit a rollback for every slot a root leaves behind on a dead fork.
+    pub rollback_events: bool,
+    /// Report how long slots take to be confirmed and rooted after their
+    /// first event.
+    pub confirmation_latency: bool,
+    pub throughput: Option<ThroughputConfig>,
+    /// Emit normalized transfer events for single tokens of 0-decimal mints.
+    pub nft_transfers: bool,
+    /// Emit mint, transfer and burn events decoded from Bubblegum.
+    pub compressed_nfts: bool,
+    /// Fill the `memos` field of transactions with their SPL Memo contents.
+    pub extract_memos: bool,
+    /// Fill the `sol_transfers` field of transactions with their native
+    /// SOL transfers.
+    pub extract_sol_transfers: bool,
+    /// Fill the `error_class` field of failed transactions with a stable
+    /// code and category of their error.
+    pub classify_errors: bool,
+    /// Emit events when durable nonces are advanced.
+    pub nonce_events: bool,
+    /// Index token accounts by wallet owner for the admin API, and report
+    /// changes of their owner.
+    pub token_owner_index: bool,
+    /// Emit decoded address lookup tables alongside their account updates.
+    pub decode_lookup_tables: bool,
+    /// Emit events for program deployments, upgrades and authority changes.
+    pub program_upgrades: bool,
+    /// Emit transactions joined with the account updates they caused once
+    /// their slot is confirmed.
+    pub tx_effects: bool,
+    pub timestamps: TimestampsConfig,
+    /// Give accounts and transactions a `trace_id`, carried by every sink
+    /// and logged when they fail to be delivered.
+    pub trace_ids: bool,
+}
+
+/// Settings for one output of the background writer.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SinkConfig {
+    /// File of a file sink, relative to `output_dir` unless absolute.
+    pub path: Option<PathBuf>,
+    /// Name of a registered codec; each sink has its own default.
+    pub codec: Option<String>,
+    /// Codec for the events `codec` has no encoding for or fails to encode,
+    /// e.g. `json` behind a decoding codec, instead of leaving them out.
+    pub fallback_codec: Option<String>,
+    /// Prefix every record with its length as a big-endian `u32`, the
+    /// archive format `fusion-cat` reads.
+    pub framed: bool,
+    /// Compress each record into a zstd frame, using the dictionary of the
+    /// account's owner when `zstd_dictionaries` has one.
+    pub zstd: bool,
+    /// File sinks write the records of accounts owned by, and transactions
+    /// invoking, each of these programs to a file of its own, e.g.
+    /// `accs.<program>.txt`, instead of the shared file.
+    pub split_by_owner: Vec<String>,
+    /// Hold events until their slot reaches this commitment, `confirmed` or
+    /// `rooted` (`finalized`), for this sink alone.
+    pub commitment: Option<BufferCommitment>,
+    /// Write the events of each slot in one batch once the slot is
+    /// processed: accounts, then transactions, then the slot update.
+    pub batch_by_slot: bool,
+    /// What to do when a write to this sink fails, skip by default.
+    pub on_error: SinkErrorPolicy,
+    /// File sinks write a manifest next to each file, with a bloom filter
+    /// of its transaction signatures that `fusion-cat --key` uses to skip
+    /// the files without a signature.
+    pub signature_bloom: Option<SignatureBloomConfig>,
+    #[serde(flatten)]
+    pub projection: FieldProjection,
+}
+
+/// Disabled notifications are not requested from the validator, or are
+/// dropped on arrival for those it always sends, so chained plugins do not
+/// see them either.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub accounts: bool,
+    pub transactions: bool,
+    pub slots: bool,
+    pub blocks: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig {
+            accounts: true,
+            transactions: true,
+            slots: true,
+            blocks: true,
+        }
+    }
+}
+
+/// How long `on_unload` waits for the writer to drain its queue and close
+/// the outputs before giving up on what is left.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    pub timeout_secs: u64,
+    /// JSON file the events abandoned by each output are written to, see
+    /// [`crate::ExitCheckpoint`].
+    pub checkpoint_path: Option<PathBuf>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            timeout_secs: 30,
+            checkpoint_path: None,
+        }
+    }
+}
+
+impl PluginConfig {
+    pub fn load(config_file: &str) -> GeyserResult<Self> {
+        let contents = std::fs::read_to_string(config_file)?;
+
+        serde_json::from_str(&contents).map_err(|error| GeyserPluginError::ConfigFileReadError {
+            msg: error.to_string(),
+        })
+    }
+}
+
+pub(crate) fn parse_pubkey(value: &str) -> GeyserResult<Pubkey> {
+    value
+        .parse()
+        .map_err(|error| GeyserPluginError::ConfigFileReadError {
+            msg: format!("Invalid pubkey {:?}: {}", value, error),
+        })
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::PluginMetrics;
+
+/// Reports the plugin counters to a DogStatsD agent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatadogConfig {
+    pub address: SocketAddr,
+    /// Metric names are `<prefix>.<counter>`.
+    pub prefix: String,
+    pub tags: Vec<String>,
+    pub interval_secs: u64,
+    /// Also sends an error event when more events than this are dropped
+    /// within one interval.
+    pub drop_event_threshold: Option<u64>,
+}
+
+impl Default for DatadogConfig {
+    fn default() -> Self {
+        DatadogConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 8125)),
+            prefix: "fusion_engine".to_string(),
+            tags: Vec::new(),
+            interval_secs: 10,
+            drop_event_threshold: None,
+        }
+    }
+}
+
+/// Flushes counter deltas from a background thread until dropped.
+#[derive(Debug)]
+pub struct DatadogExporter {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DatadogExporter {
+    pub fn start(config: &DatadogConfig, metrics: Arc<PluginMetrics>) -> GeyserResult<Self> {
+        let socket_error = |error: std::io::Error| {
+            GeyserPluginError::Custom(
+                format!("Failed to reach DogStatsD at {}: {}", config.address, error).into(),
+            )
+        };
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(socket_error)?;
+        socket.connect(config.address).map_err(socket_error)?;
+
+        let (stop, stopped) = mpsc::channel();
+        let reporter = DatadogReporter {
+            socket,
+            config: config.clone(),
+            tags: if config.tags.is_empty() {
+                String::new()
+            } else {
+                format!("|#{}", config.tags.join(","))
+            },
+            metrics,
+            reported: HashMap::new(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("fusionDatadog".to_string())
+            .spawn(move || reporter.run(stopped))
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        info!("Reporting metrics to DogStatsD at {}", config.address);
+
+        Ok(DatadogExporter {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DatadogExporter {
+    fn drop(&mut self) {
+        // Disconnecting the channel makes the thread flush once more and exit.
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct DatadogReporter {
+    socket: UdpSocket,
+    config: DatadogConfig,
+    tags: String,
+    metrics: Arc<PluginMetrics>,
+    /// Counter values at the last flush, by metric name.
+    reported: HashMap<&'static str, u64>,
+}
+
+impl DatadogReporter {
+    fn run(mut self, stopped: Receiver<()>) {
+        let interval = Duration::from_secs(self.config.interval_secs.max(1));
+        loop {
+            let closed = matches!(
+                stopped.recv_timeout(interval),
+                Err(RecvTimeoutError::Disconnected)
+            );
+            self.flush();
+            if closed {
+                return;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let counters = [
+            ("account_updates", &self.metrics.account_updates),
+            ("transactions", &self.metrics.transactions),
+            ("dropped_events", &self.metrics.dropped_events),
+            ("quota_dropped_events", &self.metrics.quota_dropped_events),
+            (
+                "overflow_dropped_events",
+                &self.metrics.overflow_dropped_events,
+            ),
+        ];
+
+        let mut datagram = String::new();
+        let mut dropped = 0;
+        for (name, counter) in counters {
+            let value = PluginMetrics::get(counter);
+            let reported = self.reported.entry(name).or_default();
+            let delta = value.saturating_sub(*reported);
+            *reported = value;
+            if name == "dropped_events" {
+                dropped = delta;
+            }
+            datagram.push_str(&format!(
+                "{}.{}:{}|c{}\n",
+                self.config.prefix, name, delta, self.tags
+            ));
+        }
+        self.send(datagram.trim_end());
+
+        if let Some(threshold) = self.config.drop_event_threshold {
+            if dropped > threshold {
+                let title = format!("{} dropping events", self.config.prefix);
+                let text = format!(
+                    "Dropped {} events in the last {}s",
+                    dropped, self.config.interval_secs
+                );
+                self.send(&format!(
+                    "_e{{{},{}}}:{}|{}|t:error{}",
+                    title.len(),
+                    text.len(),
+                    title,
+                    text,
+                    self.tags
+                ));
+            }
+        }
+    }
+
+    fn send(&self, datagram: &str) {
+        if let Err(error) = self.socket.send(datagram.as_bytes()) {
+            warn!("Failed to send to DogStatsD: {}", error);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::AccTx;
+
+/// Captures accounts without their data, only its hash, while the writer
+/// falls behind, so consumers keep seeing which accounts change during
+/// spikes. Stages that decode account data skip such updates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DegradedModeConfig {
+    /// Events waiting for the writer at which degraded mode starts.
+    pub enter_queue_len: usize,
+    /// Events waiting for the writer at which full payloads resume.
+    pub exit_queue_len: usize,
+}
+
+impl Default for DegradedModeConfig {
+    fn default() -> Self {
+        DegradedModeConfig {
+            enter_queue_len: 50_000,
+            exit_queue_len: 10_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DegradedMode {
+    config: DegradedModeConfig,
+    active: bool,
+}
+
+impl DegradedMode {
+    pub fn new(config: &DegradedModeConfig) -> Self {
+        DegradedMode {
+            config: config.clone(),
+            active: false,
+        }
+    }
+
+    /// Strips the data of `event` while `queue_len` events are waiting
+    /// above the thresholds.
+    pub fn apply(&mut self, queue_len: usize, event: &mut AccTx) {
+        if !self.active && queue_len >= self.config.enter_queue_len {
+            self.active = true;
+            warn!(
+                "Writer queue at {} events, capturing account metadata only",
+                queue_len
+            );
+        } else if self.active && queue_len <= self.config.exit_queue_len {
+            self.active = false;
+            info!(
+                "Writer queue at {} events, capturing full accounts again",
+                queue_len
+            );
+        }
+
+        if self.active {
+            event.strip_data();
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! Delta Lake table of events, for lakehouse query engines to read
+//! transactionally. Rows are written to a Parquet data file, which a commit
+//! in the table's `_delta_log` adds once the file is complete, so readers
+//! see every row of a commit or none of it.
+//!
+//! The table has the columns `kind`, `slot` and `record`, the event in the
+//! sink's codec. Commits are published with a hard link, which fails when
+//! the version exists, so the table must be on a filesystem, or a mount of
+//! object storage, that supports them.
+
+use std::{
+    collections::hash_map::RandomState,
+    fs::{self, File},
+    hash::{BuildHasher, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info};
+use parquet::{
+    data_type::{ByteArray, ByteArrayType, DataType, Int64Type},
+    errors::ParquetError,
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::{
+    AccTx, Codec, CodecRegistry, EncodedEvent, FailedEvents, PluginMetrics, Sink, SinkConfig,
+    Undelivered,
+};
+
+const PARQUET_SCHEMA: &str = "message event {
+    REQUIRED BYTE_ARRAY kind (UTF8);
+    REQUIRED INT64 slot;
+    REQUIRED BYTE_ARRAY record (UTF8);
+}";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeltaConfig {
+    /// Directory of the table, created with its first commit when missing.
+    pub table_path: PathBuf,
+    /// Rows that trigger a commit.
+    #[serde(default = "default_rows_per_commit")]
+    pub rows_per_commit: usize,
+    /// Longest time rows wait for a commit.
+    #[serde(default = "default_commit_secs")]
+    pub commit_secs: u64,
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Codec of the `record` column, which must be a text one such as the
+    /// default `json`.
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+fn default_rows_per_commit() -> usize {
+    100_000
+}
+
+fn default_commit_secs() -> u64 {
+    60
+}
+
+fn default_queue_capacity() -> usize {
+    100_000
+}
+
+struct DeltaRow {
+    event: AccTx,
+    record: Arc<[u8]>,
+}
+
+/// Commits events to a Delta Lake table from a dedicated thread.
+#[derive(Debug)]
+pub struct DeltaSink {
+    codec: Arc<dyn Codec>,
+    sender: Option<SyncSender<DeltaRow>>,
+    thread: Option<JoinHandle<()>>,
+    undelivered: Undelivered,
+    /// Commits that failed, with the error, reported on the next heartbeat.
+    failed: Receiver<(Vec<AccTx>, String)>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl DeltaSink {
+    pub fn start(
+        config: &DeltaConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let codec = codecs.build(&config.sink, "json")?;
+        let table = DeltaTable::open(&config.table_path).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to open Delta table {:?}: {}",
+                    config.table_path, error
+                )
+                .into(),
+            )
+        })?;
+
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+        let (failed_sender, failed) = mpsc::channel();
+        let undelivered = Undelivered::default();
+        let worker = DeltaWorker {
+            table,
+            rows_per_commit: config.rows_per_commit.max(1),
+            commit_timeout: Duration::from_secs(config.commit_secs.max(1)),
+            failed: failed_sender,
+            metrics: metrics.clone(),
+            undelivered: undelivered.clone(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("fusionDelta".to_string())
+            .spawn(move || worker.run(receiver))
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        info!("Committing events to Delta table {:?}", config.table_path);
+
+        Ok(DeltaSink {
+            codec,
+            sender: Some(sender),
+            thread: Some(thread),
+            undelivered,
+            failed,
+            metrics,
+        })
+    }
+
+    fn stop(&mut self) {
+        // Closing the queue makes the thread commit what is left and exit.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        // Nothing retries them any more.
+        for (events, error) in self.failed.try_iter() {
+            self.undelivered.done(events.iter().map(AccTx::slot));
+            dropped(&self.metrics, &events, &error);
+        }
+    }
+}
+
+impl Sink for DeltaSink {
+    fn name(&self) -> &'static str {
+        "delta"
+    }
+
+    /// Waits while the queue is full.
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+        if event.event.is_control() {
+            return Ok(());
+        }
+        if let Some(record) = event.encode(&self.codec) {
+            let slot = event.event.slot();
+            self.undelivered.add(slot);
+            let row = DeltaRow {
+                event: event.event.clone(),
+                record,
+            };
+            if sender.send(row).is_err() {
+                self.undelivered.done([slot]);
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "Delta commit thread stopped",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the events of failed commits to the error policy.
+    fn heartbeat(&mut self, _slot: u64) -> io::Result<()> {
+        let mut events = Vec::new();
+        let mut last_error = None;
+        for (failed, error) in self.failed.try_iter() {
+            self.undelivered.done(failed.iter().map(AccTx::slot));
+            events.extend(failed);
+            last_error = Some(error);
+        }
+        match last_error {
+            Some(error) => Err(FailedEvents::wrap(events, error)),
+            None => Ok(()),
+        }
+    }
+
+    fn pending(&self) -> u64 {
+        self.undelivered.get()
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.undelivered.oldest()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl Drop for DeltaSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct DeltaWorker {
+    table: DeltaTable,
+    rows_per_commit: usize,
+    commit_timeout: Duration,
+    failed: Sender<(Vec<AccTx>, String)>,
+    metrics: Arc<PluginMetrics>,
+    undelivered: Undelivered,
+}
+
+impl DeltaWorker {
+    fn run(mut self, receiver: Receiver<DeltaRow>) {
+        let mut closed = false;
+        while !closed {
+            let mut rows = Vec::new();
+            let deadline = Instant::now() + self.commit_timeout;
+            while rows.len() < self.rows_per_commit {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(row) => rows.push(row),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+
+            if !rows.is_empty() {
+                self.commit(rows);
+            }
+        }
+    }
+
+    /// Takes the events of `rows` off once committed, and hands them back
+    /// to the sink, still undelivered, when the commit fails.
+    fn commit(&mut self, rows: Vec<DeltaRow>) {
+        let error = match self.table.append(&rows) {
+            Ok(()) => {
+                self.undelivered
+                    .done(rows.iter().map(|row| row.event.slot()));
+                return;
+            }
+            Err(error) => error.to_string(),
+        };
+        let events: Vec<AccTx> = rows.into_iter().map(|row| row.event).collect();
+        // Unless the sink is gone, with nothing left to retry them.
+        if let Err(mpsc::SendError((events, error))) = self.failed.send((events, error)) {
+            self.undelivered.done(events.iter().map(AccTx::slot));
+            dropped(&self.metrics, &events, &error);
+        }
+    }
+}
+
+fn dropped(metrics: &PluginMetrics, events: &[AccTx], error: &str) {
+    let count = events.len() as u64;
+    let error = format!(
+        "Dropping {} events after Delta commit error: {}",
+        count, error
+    );
+    error!("{}", error);
+    metrics.record_error(error);
+    let first_slot = events.iter().map(AccTx::slot).min().unwrap_or_default();
+    let last_slot = events.iter().map(AccTx::slot).max().unwrap_or_default();
+    metrics.gaps.record("delta", first_slot, last_slot, count);
+    for _ in 0..count {
+        PluginMetrics::increment(&metrics.dropped_events);
+    }
+}
+
+/// The table's directory and the version its next commit gets.
+struct DeltaTable {
+    path: PathBuf,
+    version: u64,
+}
+
+impl DeltaTable {
+    /// Creates the table with the protocol and schema as version 0 unless
+    /// it has commits already.
+    fn open(path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(path.join("_delta_log"))?;
+        let mut table = DeltaTable {
+            path: path.to_path_buf(),
+            version: 0,
+        };
+        match table.latest_version()? {
+            Some(version) => table.version = version + 1,
+            None => {
+                let now = unix_ms();
+                table.publish(&[
+                    json!({ "protocol": { "minReaderVersion": 1, "minWriterVersion": 2 } }),
+                    json!({ "metaData": {
+                        "id": random_uuid(),
+                        "format": { "provider": "parquet", "options": {} },
+                        "schemaString": delta_schema(),
+                        "partitionColumns": [],
+                        "configuration": {},
+                        "createdTime": now,
+                    } }),
+                    commit_info(now, "CREATE TABLE"),
+                ])?;
+            }
+        }
+        Ok(table)
+    }
+
+    fn latest_version(&self) -> io::Result<Option<u64>> {
+        let mut latest = None;
+        for entry in fs::read_dir(self.path.join("_delta_log"))? {
+            let name = entry?.file_name();
+            let version = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .filter(|version| version.len() == 20)
+                .and_then(|version| version.parse::<u64>().ok());
+            latest = latest.max(version);
+        }
+        Ok(latest)
+    }
+
+    /// Writes `rows` to a data file and commits it.
+    fn append(&mut self, rows: &[DeltaRow]) -> io::Result<()> {
+        let name = format!("part-{:020}-{:016x}.parquet", self.version, random_u64());
+        let path = self.path.join(&name);
+        let committed = write_parquet(&path, rows)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+            .and_then(|()| {
+                let now = unix_ms();
+                let add = json!({ "add": {
+                    "path": name,
+                    "partitionValues": {},
+                    "size": fs::metadata(&path)?.len(),
+                    "modificationTime": now,
+                    "dataChange": true,
+                } });
+                self.publish(&[add, commit_info(now, "WRITE")])
+            });
+        if committed.is_err() {
+            let _ = fs::remove_file(&path);
+        }
+        committed
+    }
+
+    /// Publishes `actions` as the next version, moving past versions
+    /// another writer published meanwhile.
+    fn publish(&mut self, actions: &[Value]) -> io::Result<()> {
+        let log = self.path.join("_delta_log");
+        let mut commit = String::new();
+        for action in actions {
+            commit.push_str(&action.to_string());
+            commit.push('\n');
+        }
+        let partial = log.join(format!(".{:020}.json.partial", self.version));
+        fs::write(&partial, commit)?;
+        let mut published = fs::hard_link(&partial, log.join(format!("{:020}.json", self.version)));
+        if matches!(&published, Err(error) if error.kind() == io::ErrorKind::AlreadyExists) {
+            self.version = self.latest_version()?.map_or(0, |version| version + 1);
+            published = fs::hard_link(&partial, log.join(format!("{:020}.json", self.version)));
+        }
+        let _ = fs::remove_file(&partial);
+        published?;
+        self.version += 1;
+        Ok(())
+    }
+}
+
+fn write_parquet(path: &Path, rows: &[DeltaRow]) -> Result<(), ParquetError> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    let kinds: Vec<ByteArray> = rows
+        .iter()
+        .map(|row| ByteArray::from(row.event.kind()))
+        .collect();
+    let slots: Vec<i64> = rows.iter().map(|row| row.event.slot() as i64).collect();
+    let records: Vec<ByteArray> = rows
+        .iter()
+        .map(|row| ByteArray::from(row.record.to_vec()))
+        .collect();
+    write_column::<ByteArrayType>(&mut row_group, &kinds)?;
+    write_column::<Int64Type>(&mut row_group, &slots)?;
+    write_column::<ByteArrayType>(&mut row_group, &records)?;
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, File>,
+    values: &[T::T],
+) -> Result<(), ParquetError> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| ParquetError::General("Missing Parquet column".to_string()))?;
+    column.typed::<T>().write_batch(values, None, None)?;
+    column.close()
+}
+
+/// The Parquet schema in Delta's notation.
+fn delta_schema() -> String {
+    let field = |name: &str, kind: &str| json!({ "name": name, "type": kind, "nullable": false, "metadata": {} });
+    json!({
+        "type": "struct",
+        "fields": [field("kind", "string"), field("slot", "long"), field("record", "string")],
+    })
+    .to_string()
+}
+
+fn commit_info(timestamp: u64, operation: &str) -> Value {
+    json!({ "commitInfo": {
+        "timestamp": timestamp,
+        "operation": operation,
+        "operationParameters": {},
+        "engineInfo": "fusion-engine-geyser",
+    } })
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+fn random_uuid() -> String {
+    let (high, low) = (random_u64(), random_u64());
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xfff,
+        ((low >> 48) & 0x3fff) | 0x8000,
+        low & 0xffff_ffff_ffff
+    )
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use solana_sdk::{clock::DEFAULT_SLOTS_PER_EPOCH, epoch_schedule::EpochSchedule};
+
+use crate::{AccTx, AccountUpdate, TransactionUpdate};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EpochEventsConfig {
+    pub slots_per_epoch: u64,
+    /// Whether the cluster started with shorter warmup epochs.
+    pub warmup: bool,
+}
+
+impl Default for EpochEventsConfig {
+    fn default() -> Self {
+        EpochEventsConfig {
+            slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
+            warmup: false,
+        }
+    }
+}
+
+/// Emits an [`AccTx::EpochStarted`] whenever events move into a new epoch.
+#[derive(Debug)]
+pub struct EpochTracker {
+    schedule: EpochSchedule,
+    epoch: Option<u64>,
+}
+
+impl EpochTracker {
+    pub fn new(config: &EpochEventsConfig) -> Self {
+        EpochTracker {
+            schedule: EpochSchedule::custom(
+                config.slots_per_epoch,
+                config.slots_per_epoch,
+                config.warmup,
+            ),
+            epoch: None,
+        }
+    }
+
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let slot = match event {
+            AccTx::Acc(AccountUpdate { slot, .. }) | AccTx::Tx(TransactionUpdate { slot, .. }) => {
+                *slot
+            }
+            _ => return Vec::new(),
+        };
+
+        let epoch = self.schedule.get_epoch(slot);
+        let previous_epoch = match self.epoch {
+            Some(previous_epoch) if previous_epoch < epoch => previous_epoch,
+            Some(_) => return Vec::new(),
+            None => {
+                self.epoch = Some(epoch);
+                return Vec::new();
+            }
+        };
+        self.epoch = Some(epoch);
+
+        // Skipped epochs are still reported so consumers see every boundary.
+        (previous_epoch + 1..=epoch)
+            .map(|epoch| AccTx::EpochStarted {
+                epoch,
+                first_slot: self.schedule.get_first_slot_in_epoch(epoch),
+                slot,
+            })
+            .collect()
+    }
+}
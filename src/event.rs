@@ -0,0 +1,219 @@
+//! The notifications of the validator as typed events, carried by the
+//! matching [`AccTx`] variants. Code that only cares about one kind can take
+//! its type instead of matching the whole enum.
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_sdk::{hash::Hash, signature::Signature, transaction::SanitizedTransaction};
+use solana_transaction_status::{Reward, TransactionStatusMeta};
+
+use crate::{AccTx, LabeledPubkey, SolTransfer, TraceId, TxErrorClass};
+
+/// What every typed event has.
+pub trait Event: Into<AccTx> {
+    /// Stable snake_case name, the same as [`AccTx::kind`].
+    const KIND: &'static str;
+
+    fn slot(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AccountUpdate {
+    pub pubkey: Vec<u8>,
+    pub lamports: u64,
+    pub owner: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: Vec<u8>,
+    pub write_version: u64,
+    pub txn_signature: Option<Signature>,
+    pub slot: u64,
+    pub is_startup: bool,
+    /// Set instead of `data` for accounts whose data was stripped, see
+    /// [`AccTx::strip_data`].
+    pub data_hash: Option<Hash>,
+    /// Length of the stripped data.
+    pub data_len: Option<u64>,
+    pub capture_ns: Option<u64>,
+    pub wall_clock_ms: Option<i64>,
+    pub unix_timestamp: Option<i64>,
+    pub trace_id: Option<TraceId>,
+    pub labels: Vec<LabeledPubkey>,
+}
+
+impl AccountUpdate {
+    /// An update of `pubkey` with no lamports or data, to fill in with the
+    /// `with_` methods.
+    pub fn new(pubkey: Vec<u8>, owner: Vec<u8>, slot: u64) -> Self {
+        AccountUpdate {
+            pubkey,
+            owner,
+            slot,
+            ..AccountUpdate::default()
+        }
+    }
+
+    pub fn with_lamports(mut self, lamports: u64) -> Self {
+        self.lamports = lamports;
+        self
+    }
+
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    pub fn with_write_version(mut self, write_version: u64) -> Self {
+        self.write_version = write_version;
+        self
+    }
+
+    pub fn with_txn_signature(mut self, txn_signature: Signature) -> Self {
+        self.txn_signature = Some(txn_signature);
+        self
+    }
+
+    pub fn startup(mut self) -> Self {
+        self.is_startup = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionUpdate {
+    pub slot: u64,
+    pub signature: Signature,
+    pub is_vote: bool,
+    pub transaction: SanitizedTransaction,
+    pub transaction_status_meta: TransactionStatusMeta,
+    pub index: Option<usize>,
+    pub capture_ns: Option<u64>,
+    pub wall_clock_ms: Option<i64>,
+    pub unix_timestamp: Option<i64>,
+    pub trace_id: Option<TraceId>,
+    pub labels: Vec<LabeledPubkey>,
+    pub memos: Vec<String>,
+    pub sol_transfers: Vec<SolTransfer>,
+    pub error_class: Option<TxErrorClass>,
+}
+
+impl TransactionUpdate {
+    /// Signature and vote flag are taken from `transaction`.
+    pub fn new(
+        slot: u64,
+        transaction: SanitizedTransaction,
+        transaction_status_meta: TransactionStatusMeta,
+    ) -> Self {
+        TransactionUpdate {
+            slot,
+            signature: *transaction.signature(),
+            is_vote: transaction.is_simple_vote_transaction(),
+            transaction,
+            transaction_status_meta,
+            index: None,
+            capture_ns: None,
+            wall_clock_ms: None,
+            unix_timestamp: None,
+            trace_id: None,
+            labels: Vec::new(),
+            memos: Vec::new(),
+            sol_transfers: Vec::new(),
+            error_class: None,
+        }
+    }
+
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotUpdate {
+    pub slot: u64,
+    pub parent: Option<u64>,
+    pub status: SlotStatus,
+}
+
+impl SlotUpdate {
+    pub fn new(slot: u64, parent: Option<u64>, status: SlotStatus) -> Self {
+        SlotUpdate {
+            slot,
+            parent,
+            status,
+        }
+    }
+}
+
+/// Metadata of a block, notified once it is complete.
+#[derive(Debug, Clone, Default)]
+pub struct BlockUpdate {
+    pub slot: u64,
+    pub blockhash: String,
+    pub parent_slot: Option<u64>,
+    pub parent_blockhash: Option<String>,
+    pub block_time: Option<i64>,
+    pub block_height: Option<u64>,
+    pub executed_transaction_count: Option<u64>,
+    pub rewards: Vec<Reward>,
+}
+
+impl BlockUpdate {
+    pub fn new(slot: u64, blockhash: String) -> Self {
+        BlockUpdate {
+            slot,
+            blockhash,
+            ..BlockUpdate::default()
+        }
+    }
+
+    pub fn with_parent(mut self, parent_slot: u64, parent_blockhash: String) -> Self {
+        self.parent_slot = Some(parent_slot);
+        self.parent_blockhash = Some(parent_blockhash);
+        self
+    }
+
+    pub fn with_block_time(mut self, block_time: i64) -> Self {
+        self.block_time = Some(block_time);
+        self
+    }
+
+    pub fn with_block_height(mut self, block_height: u64) -> Self {
+        self.block_height = Some(block_height);
+        self
+    }
+}
+
+macro_rules! typed_event {
+    ($type:ident, $variant:ident, $kind:literal) => {
+        impl Event for $type {
+            const KIND: &'static str = $kind;
+
+            fn slot(&self) -> u64 {
+                self.slot
+            }
+        }
+
+        impl From<$type> for AccTx {
+            fn from(event: $type) -> Self {
+                AccTx::$variant(event)
+            }
+        }
+
+        impl TryFrom<AccTx> for $type {
+            type Error = AccTx;
+
+            /// Gives the event back when it is of another kind.
+            fn try_from(event: AccTx) -> Result<Self, AccTx> {
+                match event {
+                    AccTx::$variant(event) => Ok(event),
+                    event => Err(event),
+                }
+            }
+        }
+    };
+}
+
+typed_event!(AccountUpdate, Acc, "account");
+typed_event!(TransactionUpdate, Tx, "transaction");
+typed_event!(SlotUpdate, Slot, "slot");
+typed_event!(BlockUpdate, Block, "block");
@@ -0,0 +1,146 @@
+//! What a restart loses: the events each output had not delivered when
+//! `on_unload` stopped waiting for the writer.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::Sink;
+
+/// Events a sink has queued for its delivery thread and not delivered yet,
+/// counted by slot and shared with that thread.
+#[derive(Debug, Clone, Default)]
+pub struct Undelivered(Arc<Mutex<BTreeMap<u64, u64>>>);
+
+impl Undelivered {
+    pub fn add(&self, slot: u64) {
+        *self.0.lock().unwrap().entry(slot).or_default() += 1;
+    }
+
+    /// Takes one event of each of `slots` off, whether it was delivered or
+    /// given up as a gap.
+    pub fn done(&self, slots: impl IntoIterator<Item = u64>) {
+        let mut undelivered = self.0.lock().unwrap();
+        for slot in slots {
+            if let Some(events) = undelivered.get_mut(&slot) {
+                *events -= 1;
+                if *events == 0 {
+                    undelivered.remove(&slot);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.lock().unwrap().values().sum()
+    }
+
+    /// Lowest slot with an event not delivered yet.
+    pub fn oldest(&self) -> Option<u64> {
+        self.0.lock().unwrap().keys().next().copied()
+    }
+}
+
+/// Kept up to date by the writer, which reports its outputs after every
+/// write and once more after closing them.
+#[derive(Debug, Default)]
+pub struct ExitBarrier {
+    inner: Mutex<Progress>,
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    /// Undelivered events by output; outputs sharing a name add up.
+    pending: BTreeMap<&'static str, u64>,
+    latest_slot: u64,
+    latest_root: u64,
+    /// Events taken from the queue since the outputs last reported.
+    taken: u64,
+}
+
+/// The final account of an unload, logged and written to the checkpoint
+/// file.
+#[derive(Debug, Serialize)]
+pub struct ExitCheckpoint {
+    pub unloaded_at: u64,
+    /// Whether the writer closed every output before the timeout.
+    pub drained: bool,
+    pub latest_slot: u64,
+    pub latest_root: u64,
+    /// Events left in the writer queue, lost to every output.
+    pub queued: u64,
+    /// Events the writer took from its queue and had not handed to the
+    /// outputs yet, lost to every output too.
+    pub taken: u64,
+    /// Events each output will not deliver, including `queued` and
+    /// `taken`.
+    pub abandoned: BTreeMap<&'static str, u64>,
+}
+
+impl ExitBarrier {
+    /// `held` events of the plugin's slot buffer are lost to every output.
+    pub fn report(&self, sinks: &[Box<dyn Sink>], held: u64, latest_slot: u64, latest_root: u64) {
+        let mut pending = BTreeMap::new();
+        for sink in sinks {
+            *pending.entry(sink.name()).or_insert(held) += sink.pending();
+        }
+        *self.inner.lock().unwrap() = Progress {
+            pending,
+            latest_slot,
+            latest_root,
+            taken: 0,
+        };
+    }
+
+    /// Counts `events` taken from the queue until the next report.
+    pub fn take(&self, events: u64) {
+        self.inner.lock().unwrap().taken += events;
+    }
+
+    /// Accounts for `queued` events the writer never took, on top of what
+    /// its outputs last reported.
+    pub fn checkpoint(&self, drained: bool, queued: u64) -> ExitCheckpoint {
+        let progress = self.inner.lock().unwrap();
+        ExitCheckpoint {
+            unloaded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default(),
+            drained,
+            latest_slot: progress.latest_slot,
+            latest_root: progress.latest_root,
+            queued,
+            taken: progress.taken,
+            abandoned: progress
+                .pending
+                .iter()
+                .map(|(sink, pending)| (*sink, pending + progress.taken + queued))
+                .collect(),
+        }
+    }
+}
+
+impl ExitCheckpoint {
+    pub fn total_abandoned(&self) -> u64 {
+        self.abandoned.values().sum()
+    }
+
+    /// e.g. `kafka 0, file 12`.
+    pub fn summary(&self) -> String {
+        self.abandoned
+            .iter()
+            .map(|(sink, abandoned)| format!("{} {}", sink, abandoned))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
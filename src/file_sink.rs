@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::{
+    config::parse_pubkey, AccTx, AccountUpdate, Codec, CodecRegistry, EncodedEvent,
+    HeartbeatConfig, IdleTimer, PartialWrite, PluginConfig, SegmentManifest, SignatureBloom, Sink,
+    SinkConfig, TransactionUpdate,
+};
+
+/// The output files of the background writer, by the events they hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileRoute {
+    Accounts,
+    Transactions,
+    Slots,
+    Blocks,
+    /// Derived events such as aggregates.
+    Events,
+    /// Periodic monitoring events such as throughput.
+    Metrics,
+}
+
+impl FileRoute {
+    pub const ALL: [FileRoute; 6] = [
+        FileRoute::Accounts,
+        FileRoute::Transactions,
+        FileRoute::Slots,
+        FileRoute::Blocks,
+        FileRoute::Events,
+        FileRoute::Metrics,
+    ];
+
+    /// File `event` is written to; sinks write their own control records.
+    pub fn of(event: &AccTx) -> Option<FileRoute> {
+        match event {
+            AccTx::Acc(_) | AccTx::Lifecycle { .. } | AccTx::AddressLookupTable { .. } => {
+                Some(FileRoute::Accounts)
+            }
+            AccTx::Tx(_) | AccTx::TxWithEffects { .. } => Some(FileRoute::Transactions),
+            AccTx::Slot(_) | AccTx::SlotRollback { .. } => Some(FileRoute::Slots),
+            AccTx::Block(_) => Some(FileRoute::Blocks),
+            AccTx::MintSupply { .. }
+            | AccTx::OwnerLamports { .. }
+            | AccTx::SlotStats { .. }
+            | AccTx::NftTransfer { .. }
+            | AccTx::CompressedNft { .. }
+            | AccTx::NonceAdvanced { .. }
+            | AccTx::NonceAccountChanged { .. }
+            | AccTx::TokenOwnerChanged { .. }
+            | AccTx::ProgramUpgraded { .. }
+            | AccTx::ProgramDataChanged { .. }
+            | AccTx::EpochStarted { .. } => Some(FileRoute::Events),
+            AccTx::Throughput { .. }
+            | AccTx::ForkStats { .. }
+            | AccTx::ConfirmationLatency { .. } => Some(FileRoute::Metrics),
+            AccTx::Heartbeat { .. } | AccTx::Watermark { .. } => None,
+        }
+    }
+
+    pub fn default_name(self) -> &'static str {
+        match self {
+            FileRoute::Accounts => "accs.txt",
+            FileRoute::Transactions => "txs.txt",
+            FileRoute::Slots => "slots.txt",
+            FileRoute::Blocks => "blocks.txt",
+            FileRoute::Events => "events.txt",
+            FileRoute::Metrics => "metrics.txt",
+        }
+    }
+
+    pub fn sink_config(self, config: &PluginConfig) -> &SinkConfig {
+        match self {
+            FileRoute::Accounts => &config.accounts,
+            FileRoute::Transactions => &config.transactions,
+            FileRoute::Slots => &config.slots,
+            FileRoute::Blocks => &config.blocks,
+            FileRoute::Events => &config.events,
+            FileRoute::Metrics => &config.metrics,
+        }
+    }
+}
+
+/// One of the background writer's output files.
+#[derive(Debug)]
+pub struct FileSink {
+    route: FileRoute,
+    path: PathBuf,
+    /// Flushed after every write but those of startup batches.
+    file: BufWriter<File>,
+    /// Files of the programs in `split_by_owner`.
+    splits: HashMap<Pubkey, BufWriter<File>>,
+    codec: Arc<dyn Codec>,
+    idle: Option<IdleTimer>,
+    /// Data records written so far.
+    sequence: u64,
+    /// Manifest of the shared file, with `signature_bloom`.
+    manifest: Option<SegmentManifest>,
+}
+
+impl FileSink {
+    /// Creates the file of `route` at its `path`, or default name, within
+    /// `output_dir`, along with missing parent directories.
+    pub fn create(
+        route: FileRoute,
+        config: &PluginConfig,
+        codecs: &CodecRegistry,
+        heartbeat: Option<&HeartbeatConfig>,
+    ) -> GeyserResult<Self> {
+        let sink = route.sink_config(config);
+        let path = config.output_dir.join(
+            sink.path
+                .as_deref()
+                .unwrap_or(Path::new(route.default_name())),
+        );
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| {
+                GeyserPluginError::Custom(
+                    format!("Failed to create output directory {:?}: {}", parent, error).into(),
+                )
+            })?;
+        }
+
+        let mut splits = HashMap::new();
+        for owner in &sink.split_by_owner {
+            let owner = parse_pubkey(owner)?;
+            let file = create_file(&split_path(&path, &owner))?;
+            splits.insert(owner, BufWriter::new(file));
+        }
+
+        let file = BufWriter::new(create_file(&path)?);
+        // That of the file just truncated.
+        SegmentManifest::remove(&path).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!("Failed to remove the manifest of {:?}: {}", path, error).into(),
+            )
+        })?;
+        Ok(FileSink {
+            route,
+            file,
+            path,
+            splits,
+            codec: codecs.build(sink, "debug")?,
+            idle: heartbeat.map(IdleTimer::new),
+            sequence: 0,
+            manifest: sink.signature_bloom.as_ref().map(|config| SegmentManifest {
+                bytes: 0,
+                records: 0,
+                slots: None,
+                signatures: SignatureBloom::new(config),
+            }),
+        })
+    }
+
+    fn write_record(&mut self, encoded: &EncodedEvent) -> io::Result<()> {
+        let event = encoded.event;
+        if let Some(bytes) = encoded.encode(&self.codec) {
+            if event.is_control() {
+                for file in self.splits.values_mut() {
+                    file.write_all(&bytes)?;
+                }
+                self.file.write_all(&bytes)?;
+            } else {
+                let mut split = false;
+                for owner in owners(event) {
+                    if let Some(file) = self.splits.get_mut(&owner) {
+                        file.write_all(&bytes)?;
+                        split = true;
+                    }
+                }
+                if !split {
+                    self.file.write_all(&bytes)?;
+                    if let Some(manifest) = &mut self.manifest {
+                        let slot = event.slot();
+                        manifest.records += 1;
+                        manifest.slots = Some(match manifest.slots {
+                            Some((first_slot, last_slot)) => {
+                                (first_slot.min(slot), last_slot.max(slot))
+                            }
+                            None => (slot, slot),
+                        });
+                        if let Some(signature) = signature(event) {
+                            manifest.signatures.insert(signature);
+                        }
+                    }
+                }
+            }
+            if !event.is_control() {
+                self.sequence += 1;
+            }
+            if let Some(idle) = &mut self.idle {
+                idle.reset();
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_buffers(&mut self) -> io::Result<()> {
+        for file in self.splits.values_mut() {
+            file.flush()?;
+        }
+        self.file.flush()
+    }
+
+    /// Writes the manifest of the shared file as flushed, at `segment`.
+    fn write_manifest(&mut self, segment: &Path) -> io::Result<()> {
+        if let Some(manifest) = &mut self.manifest {
+            manifest.bytes = self.file.get_ref().metadata()?.len();
+            manifest.write(segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        if FileRoute::of(event.event) != Some(self.route) {
+            return Ok(());
+        }
+        self.write_record(event)?;
+        self.flush_buffers()
+    }
+
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        for (written, event) in events.iter().enumerate() {
+            if FileRoute::of(event.event) == Some(self.route) {
+                self.write_record(event)
+                    .map_err(|error| PartialWrite::wrap(written, error))?;
+            }
+        }
+        self.flush_buffers()
+            .map_err(|error| PartialWrite::wrap(events.len(), error))
+    }
+
+    /// Writes a heartbeat when heartbeats are on and the sink went idle.
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        match &self.idle {
+            Some(idle) if idle.expired() => {
+                self.write_record(&EncodedEvent::new(&AccTx::Heartbeat {
+                    slot,
+                    healthy: true,
+                }))?;
+                self.flush_buffers()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Marks everything up to `slot` and the records written so far as
+    /// complete.
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        self.write_record(&EncodedEvent::new(&AccTx::Watermark {
+            slot,
+            max_sequence: self.sequence,
+        }))?;
+        self.flush_buffers()
+    }
+
+    /// Syncs the files to disk, and writes the manifest of the shared one.
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buffers()?;
+        for file in self.splits.values() {
+            file.get_ref().sync_data()?;
+        }
+        self.file.get_ref().sync_data()?;
+        let path = self.path.clone();
+        self.write_manifest(&path)
+    }
+
+    /// Renames the files to `<path>.<unix seconds>` and continues in new
+    /// ones, the manifest of the shared file going with it. Sequence numbers
+    /// carry on across files.
+    fn rotate(&mut self) -> io::Result<()> {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        self.flush_buffers()?;
+        for (owner, file) in &mut self.splits {
+            *file = rotate_file(&split_path(&self.path, owner), suffix)?;
+        }
+        self.write_manifest(&rotated_path(&self.path, suffix))?;
+        self.file = rotate_file(&self.path, suffix)?;
+        if let Some(manifest) = &mut self.manifest {
+            SegmentManifest::remove(&self.path)?;
+            manifest.records = 0;
+            manifest.slots = None;
+            manifest.signatures.clear();
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "path": self.path,
+            "splits": self.splits.len(),
+            "records": self.sequence,
+        })
+    }
+}
+
+fn create_file(path: &Path) -> GeyserResult<File> {
+    File::create(path).map_err(|error| {
+        GeyserPluginError::Custom(format!("Failed to create {:?}: {}", path, error).into())
+    })
+}
+
+fn rotated_path(path: &Path, suffix: u64) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", suffix));
+    PathBuf::from(rotated)
+}
+
+fn rotate_file(path: &Path, suffix: u64) -> io::Result<BufWriter<File>> {
+    std::fs::rename(path, rotated_path(path, suffix))?;
+    File::create(path).map(BufWriter::new)
+}
+
+/// `accs.txt` becomes `accs.<owner>.txt`.
+fn split_path(path: &Path, owner: &Pubkey) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, owner, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, owner),
+    };
+    path.with_file_name(file_name)
+}
+
+fn signature(event: &AccTx) -> Option<&Signature> {
+    match event {
+        AccTx::Tx(TransactionUpdate { signature, .. }) | AccTx::TxWithEffects { signature, .. } => {
+            Some(signature)
+        }
+        _ => None,
+    }
+}
+
+/// Programs an event belongs to for splitting.
+fn owners(event: &AccTx) -> Vec<Pubkey> {
+    match event {
+        AccTx::Acc(AccountUpdate { owner, .. }) | AccTx::Lifecycle { owner, .. } => {
+            Pubkey::try_from(owner.as_slice()).into_iter().collect()
+        }
+        AccTx::Tx(TransactionUpdate { transaction, .. }) => {
+            let mut programs: Vec<Pubkey> = transaction
+                .message()
+                .program_instructions_iter()
+                .map(|(program_id, _)| *program_id)
+                .collect();
+            programs.sort();
+            programs.dedup();
+            programs
+        }
+        AccTx::TxWithEffects { transaction, .. } => owners(transaction),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+use crate::{mint_supply::default_interval_slots, pipeline::SlotInterval, AccTx, SlotUpdate};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForkStatsConfig {
+    /// Statistics are emitted once every this many rooted slots.
+    #[serde(default = "default_interval_slots")]
+    pub interval_slots: u64,
+}
+
+/// Counts skipped slots between roots and switches of the processed tip to
+/// another fork, summarized per window as [`AccTx::ForkStats`].
+#[derive(Debug)]
+pub struct ForkStatsMonitor {
+    interval: SlotInterval,
+    tip: Option<u64>,
+    root: Option<u64>,
+    window_root: u64,
+    rooted_slots: u64,
+    skipped_slots: u64,
+    fork_switches: u64,
+}
+
+impl ForkStatsMonitor {
+    pub fn new(config: &ForkStatsConfig) -> Self {
+        ForkStatsMonitor {
+            interval: SlotInterval::new(config.interval_slots),
+            tip: None,
+            root: None,
+            window_root: 0,
+            rooted_slots: 0,
+            skipped_slots: 0,
+            fork_switches: 0,
+        }
+    }
+
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let (slot, parent, status) = match event {
+            AccTx::Slot(SlotUpdate {
+                slot,
+                parent,
+                status,
+            }) => (*slot, *parent, *status),
+            _ => return Vec::new(),
+        };
+
+        match status {
+            SlotStatus::Processed => {
+                // The tip normally advances onto its own child; any other
+                // parent means the node moved over to a different fork.
+                if let (Some(tip), Some(parent)) = (self.tip, parent) {
+                    if slot != tip && parent != tip {
+                        self.fork_switches += 1;
+                    }
+                }
+                self.tip = Some(slot);
+                Vec::new()
+            }
+            SlotStatus::Rooted => self.root(slot),
+            SlotStatus::Confirmed => Vec::new(),
+        }
+    }
+
+    fn root(&mut self, slot: u64) -> Vec<AccTx> {
+        match self.root {
+            Some(root) if slot > root => {
+                self.rooted_slots += 1;
+                self.skipped_slots += slot - root - 1;
+            }
+            Some(_) => return Vec::new(),
+            // Windows start at the first root seen.
+            None => {
+                self.window_root = slot;
+                self.interval.tick(slot);
+            }
+        }
+        self.root = Some(slot);
+
+        if !self.interval.tick(slot) {
+            return Vec::new();
+        }
+
+        vec![AccTx::ForkStats {
+            first_slot: std::mem::replace(&mut self.window_root, slot) + 1,
+            slot,
+            rooted_slots: std::mem::take(&mut self.rooted_slots),
+            skipped_slots: std::mem::take(&mut self.skipped_slots),
+            fork_switches: std::mem::take(&mut self.fork_switches),
+        }]
+    }
+}
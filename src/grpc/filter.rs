@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    grpc::proto::{AccountsFilter, SubscribeRequest, TransactionsFilter},
+    AccTx, AccountUpdate, TransactionUpdate,
+};
+
+#[derive(Debug, Default)]
+struct AccountsMatcher {
+    pubkeys: HashSet<Pubkey>,
+    owners: HashSet<Pubkey>,
+}
+
+#[derive(Debug, Default)]
+struct TransactionsMatcher {
+    include_votes: bool,
+    include_failed: bool,
+    account_include: HashSet<Pubkey>,
+}
+
+/// The parsed filters of one subscription. Matches nothing until the first
+/// request arrives.
+#[derive(Debug, Default)]
+pub struct SubscriptionFilter {
+    accounts: Option<AccountsMatcher>,
+    transactions: Option<TransactionsMatcher>,
+    slots: bool,
+    group: Option<String>,
+}
+
+impl SubscriptionFilter {
+    pub fn new(request: &SubscribeRequest) -> Result<Self, String> {
+        Ok(SubscriptionFilter {
+            accounts: request
+                .accounts
+                .as_ref()
+                .map(AccountsMatcher::new)
+                .transpose()?,
+            transactions: request
+                .transactions
+                .as_ref()
+                .map(TransactionsMatcher::new)
+                .transpose()?,
+            slots: request.slots,
+            group: Some(request.group.clone()).filter(|group| !group.is_empty()),
+        })
+    }
+
+    /// The consumer group sharing the stream with this subscription.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn matches(&self, event: &AccTx) -> bool {
+        match event {
+            AccTx::Acc(AccountUpdate { pubkey, owner, .. }) => match &self.accounts {
+                Some(accounts) => accounts.matches(pubkey, owner),
+                None => false,
+            },
+            AccTx::Tx(_) => match &self.transactions {
+                Some(transactions) => transactions.matches(event),
+                None => false,
+            },
+            AccTx::Slot(_) => self.slots,
+            _ => false,
+        }
+    }
+}
+
+impl AccountsMatcher {
+    fn new(filter: &AccountsFilter) -> Result<Self, String> {
+        Ok(AccountsMatcher {
+            pubkeys: parse_pubkeys(&filter.pubkeys)?,
+            owners: parse_pubkeys(&filter.owners)?,
+        })
+    }
+
+    fn matches(&self, pubkey: &[u8], owner: &[u8]) -> bool {
+        if self.pubkeys.is_empty() && self.owners.is_empty() {
+            return true;
+        }
+
+        contains(&self.pubkeys, pubkey) || contains(&self.owners, owner)
+    }
+}
+
+impl TransactionsMatcher {
+    fn new(filter: &TransactionsFilter) -> Result<Self, String> {
+        Ok(TransactionsMatcher {
+            include_votes: filter.include_votes,
+            include_failed: filter.include_failed,
+            account_include: parse_pubkeys(&filter.account_include)?,
+        })
+    }
+
+    fn matches(&self, event: &AccTx) -> bool {
+        let (is_vote, transaction, transaction_status_meta) = match event {
+            AccTx::Tx(TransactionUpdate {
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => (*is_vote, transaction, transaction_status_meta),
+            _ => return false,
+        };
+
+        if is_vote && !self.include_votes {
+            return false;
+        }
+        if transaction_status_meta.status.is_err() && !self.include_failed {
+            return false;
+        }
+
+        self.account_include.is_empty()
+            || transaction
+                .message()
+                .account_keys()
+                .iter()
+                .any(|key| self.account_include.contains(key))
+    }
+}
+
+fn contains(keys: &HashSet<Pubkey>, key: &[u8]) -> bool {
+    matches!(Pubkey::try_from(key), Ok(key) if keys.contains(&key))
+}
+
+fn parse_pubkeys(pubkeys: &[String]) -> Result<HashSet<Pubkey>, String> {
+    pubkeys
+        .iter()
+        .map(|pubkey| {
+            pubkey
+                .parse()
+                .map_err(|_| format!("Invalid pubkey {:?}", pubkey))
+        })
+        .collect()
+}
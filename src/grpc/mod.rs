@@ -0,0 +1,378 @@
+//! Streams events to gRPC subscribers, each with its own filters.
+
+mod filter;
+pub mod proto;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use tokio::{
+    runtime::Runtime,
+    sync::{mpsc, oneshot},
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+
+pub use filter::SubscriptionFilter;
+use proto::{
+    geyser_server::{Geyser, GeyserServer},
+    subscribe_update, AccountsFilter, FiltersApplied, SubscribeRequest, SubscribeUpdate,
+    TransactionsFilter,
+};
+
+use crate::{AccTx, AccountUpdate, TransactionUpdate};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrpcConfig {
+    pub address: SocketAddr,
+    /// Updates buffered per subscriber; subscribers falling this far behind
+    /// are disconnected.
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+}
+
+fn default_channel_capacity() -> usize {
+    100_000
+}
+
+/// What the servers receive for each subscription. Events are shared and
+/// only encoded by the server that delivers them.
+#[derive(Debug, Clone)]
+pub(crate) enum SubscriberUpdate {
+    Event(Arc<AccTx>),
+    FiltersApplied(u64),
+    Error(Box<Status>),
+}
+
+pub(crate) type UpdateSender = mpsc::Sender<SubscriberUpdate>;
+
+#[derive(Debug)]
+struct Subscriber {
+    filter: SubscriptionFilter,
+    sender: UpdateSender,
+}
+
+#[derive(Debug, Default)]
+struct SubscriberSet {
+    subscribers: HashMap<u64, Subscriber>,
+    /// Members of each consumer group, ordered by id.
+    groups: HashMap<String, Vec<u64>>,
+}
+
+impl SubscriberSet {
+    fn regroup(&mut self) {
+        self.groups.clear();
+        for (id, subscriber) in &self.subscribers {
+            if let Some(group) = subscriber.filter.group() {
+                self.groups.entry(group.to_string()).or_default().push(*id);
+            }
+        }
+        for members in self.groups.values_mut() {
+            members.sort_unstable();
+        }
+    }
+
+    /// Whether `id` receives `event`: subscribers in a group only get the
+    /// events whose partition key hashes to them.
+    fn is_assigned(&self, id: u64, subscriber: &Subscriber, event: &AccTx) -> bool {
+        let (group, key) = match (subscriber.filter.group(), partition_key(event)) {
+            (Some(group), Some(key)) => (group, key),
+            _ => return true,
+        };
+
+        match self.groups.get(group) {
+            Some(members) => members[(key % members.len() as u64) as usize] == id,
+            None => true,
+        }
+    }
+}
+
+/// Subscriptions shared between the streaming servers and the background
+/// writer, which publishes every event through them.
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    set: Mutex<SubscriberSet>,
+    next_id: AtomicU64,
+    latest_slot: AtomicU64,
+}
+
+impl Subscribers {
+    pub fn publish(&self, event: &AccTx) {
+        self.latest_slot.fetch_max(event.slot(), Ordering::Relaxed);
+
+        let mut set = self.set.lock().unwrap();
+        let mut shared = None;
+        let mut disconnected = Vec::new();
+        for (id, subscriber) in &set.subscribers {
+            if !subscriber.filter.matches(event) || !set.is_assigned(*id, subscriber, event) {
+                continue;
+            }
+            let event = shared
+                .get_or_insert_with(|| Arc::new(event.clone()))
+                .clone();
+
+            match subscriber.sender.try_send(SubscriberUpdate::Event(event)) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Disconnecting lagging subscriber {}", id);
+                    disconnected.push(*id);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => disconnected.push(*id),
+            }
+        }
+
+        if !disconnected.is_empty() {
+            for id in disconnected {
+                set.subscribers.remove(&id);
+            }
+            set.regroup();
+        }
+    }
+
+    pub(crate) fn add(&self, sender: UpdateSender) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.set.lock().unwrap().subscribers.insert(
+            id,
+            Subscriber {
+                filter: SubscriptionFilter::default(),
+                sender,
+            },
+        );
+        id
+    }
+
+    /// Swaps the filters of a subscriber, returning the first slot whose
+    /// events are all matched against them.
+    pub(crate) fn update(&self, id: u64, filter: SubscriptionFilter) -> Option<u64> {
+        let mut set = self.set.lock().unwrap();
+        set.subscribers.get_mut(&id)?.filter = filter;
+        set.regroup();
+        Some(self.latest_slot.load(Ordering::Relaxed) + 1)
+    }
+
+    /// Ids and filters of the current subscriptions.
+    pub fn describe(&self) -> Vec<(u64, String)> {
+        let set = self.set.lock().unwrap();
+        let mut subscriptions: Vec<_> = set
+            .subscribers
+            .iter()
+            .map(|(id, subscriber)| (*id, format!("{:?}", subscriber.filter)))
+            .collect();
+        subscriptions.sort_unstable();
+        subscriptions
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        let mut set = self.set.lock().unwrap();
+        if set.subscribers.remove(&id).is_some() {
+            set.regroup();
+        }
+    }
+}
+
+/// Spreads account updates by pubkey and transactions by signature; slot
+/// updates go to every member of a group.
+fn partition_key(event: &AccTx) -> Option<u64> {
+    let key: &[u8] = match event {
+        AccTx::Acc(AccountUpdate { pubkey, .. }) => pubkey,
+        AccTx::Tx(TransactionUpdate { signature, .. }) => signature.as_ref(),
+        _ => return None,
+    };
+
+    Some(u64::from_le_bytes(key.get(..8)?.try_into().ok()?))
+}
+
+type UpdateStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+struct GeyserService {
+    subscribers: Arc<Subscribers>,
+    channel_capacity: usize,
+}
+
+impl GeyserService {
+    /// Subscribes with filters that never change, as the single-kind RPCs
+    /// do, acknowledging them first like `Subscribe`.
+    fn subscribe_with(&self, request: SubscribeRequest) -> Result<UpdateStream, Status> {
+        let filter = SubscriptionFilter::new(&request).map_err(Status::invalid_argument)?;
+        let (sender, receiver) = mpsc::channel(self.channel_capacity);
+        let id = self.subscribers.add(sender.clone());
+        let slot = self
+            .subscribers
+            .update(id, filter)
+            .ok_or_else(|| Status::unavailable("Subscription ended"))?;
+        let _ = sender.try_send(SubscriberUpdate::FiltersApplied(slot));
+        Ok(updates(receiver))
+    }
+}
+
+/// The stream of a subscription, which ends when the client drops it and
+/// the writer finds its channel closed.
+fn updates(receiver: mpsc::Receiver<SubscriberUpdate>) -> UpdateStream {
+    let updates = ReceiverStream::new(receiver).filter_map(|update| match update {
+        SubscriberUpdate::Event(event) => SubscribeUpdate::from_event(&event).map(Ok),
+        SubscriberUpdate::FiltersApplied(slot) => Some(Ok(SubscribeUpdate {
+            update: Some(subscribe_update::Update::FiltersApplied(FiltersApplied {
+                slot,
+            })),
+        })),
+        SubscriberUpdate::Error(status) => Some(Err(*status)),
+    });
+    Box::pin(updates)
+}
+
+#[tonic::async_trait]
+impl Geyser for GeyserService {
+    type SubscribeStream = UpdateStream;
+    type SubscribeAccountsStream = UpdateStream;
+    type SubscribeTransactionsStream = UpdateStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let mut requests = request.into_inner();
+        let (sender, receiver) = mpsc::channel(self.channel_capacity);
+        let id = self.subscribers.add(sender.clone());
+        let subscribers = self.subscribers.clone();
+
+        // Filter updates arrive in-band for as long as the client keeps its
+        // side of the stream open.
+        tokio::spawn(async move {
+            while let Some(request) = requests.next().await {
+                let applied = match request {
+                    Ok(request) => {
+                        SubscriptionFilter::new(&request).map_err(Status::invalid_argument)
+                    }
+                    Err(status) => Err(status),
+                };
+                let slot = match applied {
+                    Ok(filter) => match subscribers.update(id, filter) {
+                        Some(slot) => slot,
+                        None => return,
+                    },
+                    Err(status) => {
+                        subscribers.remove(id);
+                        let _ = sender.send(SubscriberUpdate::Error(Box::new(status))).await;
+                        return;
+                    }
+                };
+
+                let ack = SubscriberUpdate::FiltersApplied(slot);
+                if sender.send(ack).await.is_err() {
+                    subscribers.remove(id);
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(updates(receiver)))
+    }
+
+    /// `Subscribe` with only account filters, fixed for the stream.
+    async fn subscribe_accounts(
+        &self,
+        request: Request<AccountsFilter>,
+    ) -> Result<Response<Self::SubscribeAccountsStream>, Status> {
+        let request = SubscribeRequest {
+            accounts: Some(request.into_inner()),
+            ..SubscribeRequest::default()
+        };
+        self.subscribe_with(request).map(Response::new)
+    }
+
+    /// `Subscribe` with only transaction filters, fixed for the stream.
+    async fn subscribe_transactions(
+        &self,
+        request: Request<TransactionsFilter>,
+    ) -> Result<Response<Self::SubscribeTransactionsStream>, Status> {
+        let request = SubscribeRequest {
+            transactions: Some(request.into_inner()),
+            ..SubscribeRequest::default()
+        };
+        self.subscribe_with(request).map(Response::new)
+    }
+}
+
+/// The gRPC server and the runtime it is served from, shut down on drop.
+#[derive(Debug)]
+pub struct GrpcServer {
+    shutdown: Option<oneshot::Sender<()>>,
+    runtime: Option<Runtime>,
+}
+
+impl GrpcServer {
+    pub fn start(config: &GrpcConfig, subscribers: Arc<Subscribers>) -> GeyserResult<Self> {
+        let grpc_error = |error: std::io::Error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to start gRPC server on {}: {}",
+                    config.address, error
+                )
+                .into(),
+            )
+        };
+        let listener = std::net::TcpListener::bind(config.address).map_err(grpc_error)?;
+        listener.set_nonblocking(true).map_err(grpc_error)?;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("fusionGrpc")
+            .enable_all()
+            .build()
+            .map_err(grpc_error)?;
+        let listener = {
+            let _guard = runtime.enter();
+            tokio::net::TcpListener::from_std(listener).map_err(grpc_error)?
+        };
+
+        let service = GeyserService {
+            subscribers,
+            channel_capacity: config.channel_capacity,
+        };
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        runtime.spawn(async move {
+            let served = Server::builder()
+                .add_service(GeyserServer::new(service))
+                .serve_with_incoming_shutdown(
+                    tokio_stream::wrappers::TcpListenerStream::new(listener),
+                    async {
+                        let _ = shutdown_signal.await;
+                    },
+                )
+                .await;
+            if let Err(error) = served {
+                warn!("gRPC server stopped: {}", error);
+            }
+        });
+        info!("gRPC server listening on {}", config.address);
+
+        Ok(GrpcServer {
+            shutdown: Some(shutdown),
+            runtime: Some(runtime),
+        })
+    }
+}
+
+impl Drop for GrpcServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        // Dropping a runtime from within async code panics, so shut it down
+        // without waiting for its tasks.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
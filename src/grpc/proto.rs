@@ -0,0 +1,347 @@
+//! Messages of the `fusion_engine.Geyser` service.
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus as GeyserSlotStatus;
+
+use crate::{AccTx, FieldProjection};
+
+include!(concat!(env!("OUT_DIR"), "/fusion_engine.Geyser.rs"));
+
+/// Replaces the filters of a subscription. Event kinds whose filter is unset
+/// are not streamed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeRequest {
+    #[prost(message, optional, tag = "1")]
+    pub accounts: Option<AccountsFilter>,
+    #[prost(message, optional, tag = "2")]
+    pub transactions: Option<TransactionsFilter>,
+    #[prost(bool, tag = "3")]
+    pub slots: bool,
+    /// Subscriptions sharing a non-empty group name each receive a share of
+    /// the account and transaction updates, partitioned by pubkey and
+    /// signature hash, instead of duplicates.
+    #[prost(string, tag = "4")]
+    pub group: String,
+}
+
+/// Matches accounts by pubkey or owner; empty lists match every account.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountsFilter {
+    #[prost(string, repeated, tag = "1")]
+    pub pubkeys: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub owners: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionsFilter {
+    #[prost(bool, tag = "1")]
+    pub include_votes: bool,
+    #[prost(bool, tag = "2")]
+    pub include_failed: bool,
+    /// Only transactions mentioning one of these accounts when not empty.
+    #[prost(string, repeated, tag = "3")]
+    pub account_include: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeUpdate {
+    #[prost(oneof = "subscribe_update::Update", tags = "1, 2, 3, 4")]
+    pub update: Option<subscribe_update::Update>,
+}
+
+pub mod subscribe_update {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Update {
+        #[prost(message, tag = "1")]
+        Account(super::AccountUpdate),
+        #[prost(message, tag = "2")]
+        Transaction(super::TransactionUpdate),
+        #[prost(message, tag = "3")]
+        Slot(super::SlotUpdate),
+        #[prost(message, tag = "4")]
+        FiltersApplied(super::FiltersApplied),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountUpdate {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pubkey: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub lamports: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub owner: Vec<u8>,
+    #[prost(bool, tag = "4")]
+    pub executable: bool,
+    #[prost(uint64, tag = "5")]
+    pub rent_epoch: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub data: Vec<u8>,
+    #[prost(uint64, tag = "7")]
+    pub write_version: u64,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub txn_signature: Option<Vec<u8>>,
+    #[prost(uint64, tag = "9")]
+    pub slot: u64,
+    #[prost(bool, tag = "10")]
+    pub is_startup: bool,
+    /// Set instead of `data` for accounts captured in degraded mode.
+    #[prost(bytes = "vec", optional, tag = "11")]
+    pub data_hash: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "12")]
+    pub trace_id: Option<String>,
+    /// Length of the data `data_hash` replaces.
+    #[prost(uint64, optional, tag = "13")]
+    pub data_len: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionUpdate {
+    #[prost(bytes = "vec", tag = "1")]
+    pub signature: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub slot: u64,
+    #[prost(bool, tag = "3")]
+    pub is_vote: bool,
+    #[prost(uint64, optional, tag = "4")]
+    pub index: Option<u64>,
+    /// Bincode-encoded `VersionedTransaction`.
+    #[prost(bytes = "vec", tag = "5")]
+    pub transaction: Vec<u8>,
+    #[prost(string, optional, tag = "6")]
+    pub err: Option<String>,
+    #[prost(uint64, tag = "7")]
+    pub fee: u64,
+    #[prost(string, repeated, tag = "8")]
+    pub log_messages: Vec<String>,
+    #[prost(uint64, optional, tag = "9")]
+    pub compute_units_consumed: Option<u64>,
+    #[prost(string, optional, tag = "10")]
+    pub trace_id: Option<String>,
+    /// Set when errors are classified, e.g. `InstructionError:Custom(6001)`.
+    #[prost(string, optional, tag = "11")]
+    pub error_code: Option<String>,
+    #[prost(string, optional, tag = "12")]
+    pub error_category: Option<String>,
+    #[prost(bytes = "vec", tag = "13")]
+    pub fee_payer: Vec<u8>,
+    /// Signers, the fee payer first.
+    #[prost(bytes = "vec", repeated, tag = "14")]
+    pub signers: Vec<Vec<u8>>,
+    /// Keys of the message, then the writable and readonly ones loaded
+    /// from lookup tables, in the order instructions index them.
+    #[prost(bytes = "vec", repeated, tag = "15")]
+    pub account_keys: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "16")]
+    pub recent_blockhash: Vec<u8>,
+    #[prost(message, repeated, tag = "17")]
+    pub instructions: Vec<CompiledInstruction>,
+}
+
+/// A top-level instruction, its program and accounts indexing
+/// `TransactionUpdate.account_keys`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CompiledInstruction {
+    #[prost(uint32, tag = "1")]
+    pub program_id_index: u32,
+    /// One byte per account index.
+    #[prost(bytes = "vec", tag = "2")]
+    pub accounts: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SlotStatus {
+    Processed = 0,
+    Confirmed = 1,
+    Rooted = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SlotUpdate {
+    #[prost(uint64, tag = "1")]
+    pub slot: u64,
+    #[prost(uint64, optional, tag = "2")]
+    pub parent: Option<u64>,
+    #[prost(enumeration = "SlotStatus", tag = "3")]
+    pub status: i32,
+}
+
+/// Acknowledges a [`SubscribeRequest`]: events of this slot and later are
+/// all matched against the new filters.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FiltersApplied {
+    #[prost(uint64, tag = "1")]
+    pub slot: u64,
+}
+
+impl SubscribeUpdate {
+    /// Converts the events that have a protobuf representation.
+    pub fn from_event(event: &AccTx) -> Option<Self> {
+        let update = match event {
+            AccTx::Acc(crate::AccountUpdate {
+                pubkey,
+                lamports,
+                owner,
+                executable,
+                rent_epoch,
+                data,
+                write_version,
+                txn_signature,
+                slot,
+                is_startup,
+                data_hash,
+                data_len,
+                trace_id,
+                ..
+            }) => subscribe_update::Update::Account(AccountUpdate {
+                pubkey: pubkey.clone(),
+                lamports: *lamports,
+                owner: owner.clone(),
+                executable: *executable,
+                rent_epoch: *rent_epoch,
+                data: data.clone(),
+                write_version: *write_version,
+                txn_signature: txn_signature.map(|signature| signature.as_ref().to_vec()),
+                slot: *slot,
+                is_startup: *is_startup,
+                data_hash: data_hash.map(|data_hash| data_hash.to_bytes().to_vec()),
+                trace_id: trace_id.map(|trace_id| trace_id.to_string()),
+                data_len: *data_len,
+            }),
+            AccTx::Tx(crate::TransactionUpdate {
+                slot,
+                signature,
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                index,
+                trace_id,
+                error_class,
+                ..
+            }) => subscribe_update::Update::Transaction(TransactionUpdate {
+                signature: signature.as_ref().to_vec(),
+                slot: *slot,
+                is_vote: *is_vote,
+                index: index.map(|index| index as u64),
+                transaction: bincode::serialize(&transaction.to_versioned_transaction()).ok()?,
+                err: transaction_status_meta
+                    .status
+                    .as_ref()
+                    .err()
+                    .map(|err| err.to_string()),
+                fee: transaction_status_meta.fee,
+                log_messages: transaction_status_meta
+                    .log_messages
+                    .clone()
+                    .unwrap_or_default(),
+                compute_units_consumed: transaction_status_meta.compute_units_consumed,
+                trace_id: trace_id.map(|trace_id| trace_id.to_string()),
+                error_code: error_class.as_ref().map(|class| class.code.clone()),
+                error_category: error_class.as_ref().map(|class| class.category.to_string()),
+                fee_payer: transaction.message().fee_payer().to_bytes().to_vec(),
+                signers: event
+                    .signers()
+                    .iter()
+                    .map(|signer| signer.to_bytes().to_vec())
+                    .collect(),
+                account_keys: transaction
+                    .message()
+                    .account_keys()
+                    .iter()
+                    .map(|key| key.to_bytes().to_vec())
+                    .collect(),
+                recent_blockhash: transaction.message().recent_blockhash().to_bytes().to_vec(),
+                instructions: transaction
+                    .message()
+                    .instructions()
+                    .iter()
+                    .map(|instruction| CompiledInstruction {
+                        program_id_index: instruction.program_id_index.into(),
+                        accounts: instruction.accounts.clone(),
+                        data: instruction.data.clone(),
+                    })
+                    .collect(),
+            }),
+            AccTx::Slot(crate::SlotUpdate {
+                slot,
+                parent,
+                status,
+            }) => subscribe_update::Update::Slot(SlotUpdate {
+                slot: *slot,
+                parent: *parent,
+                status: SlotStatus::from(*status) as i32,
+            }),
+            _ => return None,
+        };
+
+        Some(SubscribeUpdate {
+            update: Some(update),
+        })
+    }
+
+    /// Clears the fields `projection` leaves out, by their names in
+    /// [`AccTx`]; fields derived from one go with it.
+    pub fn project(&mut self, projection: &FieldProjection) {
+        if *projection == FieldProjection::default() {
+            return;
+        }
+        match &mut self.update {
+            Some(subscribe_update::Update::Account(account)) => {
+                projection.clear("pubkey", &mut account.pubkey);
+                projection.clear("lamports", &mut account.lamports);
+                projection.clear("owner", &mut account.owner);
+                projection.clear("executable", &mut account.executable);
+                projection.clear("rent_epoch", &mut account.rent_epoch);
+                projection.clear("data", &mut account.data);
+                projection.clear("write_version", &mut account.write_version);
+                projection.clear("txn_signature", &mut account.txn_signature);
+                projection.clear("slot", &mut account.slot);
+                projection.clear("is_startup", &mut account.is_startup);
+                projection.clear("data_hash", &mut account.data_hash);
+                projection.clear("trace_id", &mut account.trace_id);
+                projection.clear("data_len", &mut account.data_len);
+            }
+            Some(subscribe_update::Update::Transaction(transaction)) => {
+                projection.clear("signature", &mut transaction.signature);
+                projection.clear("slot", &mut transaction.slot);
+                projection.clear("is_vote", &mut transaction.is_vote);
+                projection.clear("index", &mut transaction.index);
+                projection.clear("transaction", &mut transaction.transaction);
+                projection.clear("transaction", &mut transaction.fee_payer);
+                projection.clear("transaction", &mut transaction.signers);
+                projection.clear("transaction", &mut transaction.account_keys);
+                projection.clear("transaction", &mut transaction.recent_blockhash);
+                projection.clear("transaction", &mut transaction.instructions);
+                projection.clear("transaction_status_meta", &mut transaction.err);
+                projection.clear("transaction_status_meta", &mut transaction.fee);
+                projection.clear("transaction_status_meta", &mut transaction.log_messages);
+                projection.clear(
+                    "transaction_status_meta",
+                    &mut transaction.compute_units_consumed,
+                );
+                projection.clear("trace_id", &mut transaction.trace_id);
+                projection.clear("error_class", &mut transaction.error_code);
+                projection.clear("error_class", &mut transaction.error_category);
+            }
+            Some(subscribe_update::Update::Slot(slot)) => {
+                projection.clear("slot", &mut slot.slot);
+                projection.clear("parent", &mut slot.parent);
+                projection.clear("status", &mut slot.status);
+            }
+            Some(subscribe_update::Update::FiltersApplied(_)) | None => {}
+        }
+    }
+}
+
+impl From<GeyserSlotStatus> for SlotStatus {
+    fn from(status: GeyserSlotStatus) -> Self {
+        match status {
+            GeyserSlotStatus::Processed => SlotStatus::Processed,
+            GeyserSlotStatus::Confirmed => SlotStatus::Confirmed,
+            GeyserSlotStatus::Rooted => SlotStatus::Rooted,
+        }
+    }
+}
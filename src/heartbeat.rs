@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Emits [`AccTx::Heartbeat`](crate::AccTx::Heartbeat) records to sinks that
+/// have written nothing for a while, so quiet filters are distinguishable
+/// from a stalled plugin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub idle_secs: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig { idle_secs: 30 }
+    }
+}
+
+/// Time since a sink last wrote anything.
+#[derive(Debug)]
+pub(crate) struct IdleTimer {
+    idle: Duration,
+    last_output: Instant,
+}
+
+impl IdleTimer {
+    pub(crate) fn new(config: &HeartbeatConfig) -> Self {
+        IdleTimer {
+            idle: Duration::from_secs(config.idle_secs.max(1)),
+            last_output: Instant::now(),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.last_output = Instant::now();
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.last_output.elapsed() >= self.idle
+    }
+}
@@ -0,0 +1,67 @@
+use solana_sdk::{pubkey::Pubkey, transaction::SanitizedTransaction};
+use solana_transaction_status::TransactionStatusMeta;
+
+/// An instruction of a transaction with its account indexes resolved.
+#[derive(Debug)]
+pub struct ResolvedInstruction<'a> {
+    pub program_id: &'a Pubkey,
+    pub accounts: Vec<&'a Pubkey>,
+    pub data: &'a [u8],
+    /// Whether the instruction was invoked through CPI.
+    pub is_inner: bool,
+}
+
+impl ResolvedInstruction<'_> {
+    pub fn account(&self, index: usize) -> Option<&Pubkey> {
+        self.accounts.get(index).copied()
+    }
+}
+
+/// Every instruction of `transaction` in execution order, each top-level
+/// instruction followed by the inner instructions recorded in `meta`.
+pub fn resolve_instructions<'a>(
+    transaction: &'a SanitizedTransaction,
+    meta: &'a TransactionStatusMeta,
+) -> Vec<ResolvedInstruction<'a>> {
+    let message = transaction.message();
+    let account_keys = message.account_keys();
+    let resolve = |program_id_index: u8, accounts: &'a [u8], data: &'a [u8], is_inner: bool| {
+        Some(ResolvedInstruction {
+            program_id: account_keys.get(program_id_index as usize)?,
+            accounts: accounts
+                .iter()
+                .filter_map(|index| account_keys.get(*index as usize))
+                .collect(),
+            data,
+            is_inner,
+        })
+    };
+
+    let mut instructions = Vec::new();
+    for (index, instruction) in message.instructions().iter().enumerate() {
+        instructions.extend(resolve(
+            instruction.program_id_index,
+            &instruction.accounts,
+            &instruction.data,
+            false,
+        ));
+
+        let inner_instructions = meta
+            .inner_instructions
+            .iter()
+            .flatten()
+            .filter(|inner_instructions| inner_instructions.index as usize == index)
+            .flat_map(|inner_instructions| &inner_instructions.instructions);
+        for inner_instruction in inner_instructions {
+            let instruction = &inner_instruction.instruction;
+            instructions.extend(resolve(
+                instruction.program_id_index,
+                &instruction.accounts,
+                &instruction.data,
+                true,
+            ));
+        }
+    }
+
+    instructions
+}
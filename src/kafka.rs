@@ -0,0 +1,1064 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use prost::Message as _;
+use rdkafka::{
+    error::{KafkaError, KafkaResult},
+    message::{DeliveryResult, Header, Headers, Message, OwnedHeaders},
+    producer::{BaseProducer, BaseRecord, Producer, ProducerContext},
+    types::RDKafkaErrorCode,
+    ClientConfig, ClientContext,
+};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    grpc::proto::{subscribe_update::Update, SubscribeUpdate},
+    AccTx, AccountUpdate, Codec, CodecRegistry, EncodedEvent, FieldProjection, HeartbeatConfig,
+    IdleTimer, PluginMetrics, Sink, SinkConfig, TokenAccount, TraceId, TransactionUpdate,
+    Undelivered, SCHEMA_VERSION,
+};
+
+/// Kind of the checkpoint records of transactional producers.
+const CHECKPOINT: &str = "checkpoint";
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often an unhealthy producer asks its brokers for metadata to find
+/// out whether they are back.
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct KafkaConfig {
+    pub brokers: String,
+    pub topic: String,
+    /// Topics of event kinds, as in the `event_type` header, that are not
+    /// produced to `topic`, e.g. `{"account": "accounts"}`.
+    #[serde(default)]
+    pub topics: HashMap<String, String>,
+    /// Message keys of event kinds that are not keyed the default way, e.g.
+    /// `{"account": "mint"}`. Events lacking the field get the default key.
+    #[serde(default)]
+    pub keys: HashMap<String, MessageKey>,
+    /// Broker acknowledgements a message needs: `0`, `1` or `all`. The
+    /// per-key delivery mode always uses `all`.
+    #[serde(default)]
+    pub acks: Option<String>,
+    /// Codec and projection of the payloads, protobuf by default.
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+    /// Extra librdkafka producer properties.
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+    #[serde(default)]
+    pub transactions: Option<KafkaTransactionsConfig>,
+    /// Authenticates to the Kafka endpoint of an Azure Event Hubs
+    /// namespace, with `brokers` set to `<namespace>.servicebus.windows.net:9093`
+    /// and `topic` to the event hub.
+    #[serde(default)]
+    pub event_hubs: Option<EventHubsAuth>,
+    /// Events queued for the producer thread before the writer waits.
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// A second cluster events are produced to instead while this one
+    /// fails to deliver, until it recovers. Its own `standby` is ignored.
+    #[serde(default)]
+    pub standby: Option<Box<KafkaConfig>>,
+    /// After switching clusters in either direction, keep producing to both
+    /// for this long so consumers can move over without a gap.
+    #[serde(default)]
+    pub dual_write_secs: u64,
+}
+
+/// Field of an event that becomes its message key, so compacted topics
+/// keep the latest event per value of that field.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKey {
+    /// Account pubkey, transaction signature or slot.
+    #[default]
+    Default,
+    /// Program owning an account.
+    Owner,
+    /// Mint of a token account or of mint supply and NFT transfer events.
+    Mint,
+    /// Program owning an account, or first program a transaction invokes.
+    Program,
+    /// Fee payer of a transaction.
+    FeePayer,
+    Slot,
+}
+
+/// Compression of the messages a broker sink publishes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Snappy,
+}
+
+impl Compression {
+    fn as_str(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Snappy => "snappy",
+        }
+    }
+}
+
+/// Ordering guarantees of a broker sink.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    /// Retries may reorder messages of the same key.
+    #[default]
+    Unordered,
+    /// Messages sharing a partition key (account pubkey, transaction
+    /// signature, slot) are published in the order the plugin emitted them,
+    /// retries included. Nothing is promised across keys, and a message
+    /// that fails for good leaves a gap rather than blocking its key.
+    PerKey,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "auth", rename_all = "snake_case")]
+pub enum EventHubsAuth {
+    /// Shared access signature from a namespace or event hub connection
+    /// string.
+    Sas { connection_string: String },
+    /// Azure AD service principal, through the client credentials flow.
+    Aad {
+        tenant_id: String,
+        client_id: String,
+        client_secret: String,
+    },
+}
+
+impl EventHubsAuth {
+    fn configure(&self, brokers: &str, client_config: &mut ClientConfig) {
+        client_config.set("security.protocol", "SASL_SSL");
+        match self {
+            EventHubsAuth::Sas { connection_string } => {
+                client_config
+                    .set("sasl.mechanism", "PLAIN")
+                    .set("sasl.username", "$ConnectionString")
+                    .set("sasl.password", connection_string);
+            }
+            EventHubsAuth::Aad {
+                tenant_id,
+                client_id,
+                client_secret,
+            } => {
+                let namespace = brokers.split(':').next().unwrap_or(brokers);
+                client_config
+                    .set("sasl.mechanism", "OAUTHBEARER")
+                    .set("sasl.oauthbearer.method", "oidc")
+                    .set("sasl.oauthbearer.client.id", client_id)
+                    .set("sasl.oauthbearer.client.secret", client_secret)
+                    .set(
+                        "sasl.oauthbearer.token.endpoint.url",
+                        format!(
+                            "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+                            tenant_id
+                        ),
+                    )
+                    .set(
+                        "sasl.oauthbearer.scope",
+                        format!("https://{}/.default", namespace),
+                    );
+            }
+        }
+    }
+}
+
+/// Publishes events in Kafka transactions, each committed together with a
+/// checkpoint record describing the batch, so `read_committed` consumers
+/// never see partial batches, including across plugin restarts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KafkaTransactionsConfig {
+    /// Must stay the same across restarts so a restarted plugin fences off
+    /// and aborts whatever its previous instance left open.
+    pub transactional_id: String,
+    pub checkpoint_topic: String,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_batch_timeout_ms")]
+    pub batch_timeout_ms: u64,
+    /// Attempts at a batch failing with retriable errors before it is
+    /// dropped as a gap and the producer marked unhealthy.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled on every further one.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_max_retry_backoff_ms")]
+    pub max_retry_backoff_ms: u64,
+}
+
+impl KafkaTransactionsConfig {
+    /// Wait after the failed `attempt`, counted from 1.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let backoff_ms = self
+            .retry_backoff_ms
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_retry_backoff_ms);
+        Duration::from_millis(backoff_ms)
+    }
+}
+
+fn default_queue_capacity() -> usize {
+    100_000
+}
+
+fn default_batch_size() -> usize {
+    1_000
+}
+
+fn default_batch_timeout_ms() -> u64 {
+    100
+}
+
+fn default_max_attempts() -> u32 {
+    10
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_retry_backoff_ms() -> u64 {
+    5_000
+}
+
+struct KafkaRecord {
+    /// Event kind, or [`CHECKPOINT`] for transaction checkpoints.
+    kind: &'static str,
+    key: Vec<u8>,
+    payload: Arc<[u8]>,
+    headers: OwnedHeaders,
+    slot: u64,
+    trace_id: Option<TraceId>,
+}
+
+/// Produces encoded events from a dedicated thread.
+#[derive(Debug)]
+pub struct KafkaSink {
+    codec: Arc<dyn Codec>,
+    idle: Option<IdleTimer>,
+    /// Data records queued so far.
+    sequence: u64,
+    healthy: Arc<AtomicBool>,
+    standby: Option<Standby>,
+    /// Whether payloads are plain protobuf, so archived updates can be
+    /// replayed as they are.
+    replayable: bool,
+    keys: HashMap<String, MessageKey>,
+    sender: Option<SyncSender<KafkaRecord>>,
+    thread: Option<JoinHandle<()>>,
+    undelivered: Undelivered,
+}
+
+#[derive(Debug)]
+struct Standby {
+    sink: Box<KafkaSink>,
+    dual_write: Duration,
+    active: bool,
+    dual_write_until: Option<Instant>,
+}
+
+impl KafkaSink {
+    pub fn start(
+        config: &KafkaConfig,
+        codecs: &CodecRegistry,
+        heartbeat: Option<&HeartbeatConfig>,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let codec = codecs.build(&config.sink, "protobuf")?;
+        let kafka_error = |error: KafkaError| {
+            GeyserPluginError::Custom(format!("Failed to start Kafka producer: {}", error).into())
+        };
+
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", &config.brokers)
+            .set("compression.type", config.compression.as_str());
+        if config.delivery == DeliveryMode::PerKey {
+            // The idempotent producer keeps up to five requests in flight
+            // per partition without reordering them on retry.
+            client_config
+                .set("enable.idempotence", "true")
+                .set("acks", "all")
+                .set("max.in.flight.requests.per.connection", "5")
+                .set("partitioner", "consistent_random");
+        }
+        if let (Some(acks), DeliveryMode::Unordered) = (&config.acks, config.delivery) {
+            client_config.set("acks", acks);
+        }
+        if let Some(event_hubs) = &config.event_hubs {
+            event_hubs.configure(&config.brokers, &mut client_config);
+        }
+        for (key, value) in &config.properties {
+            client_config.set(key, value);
+        }
+        if let Some(transactions) = &config.transactions {
+            client_config.set("transactional.id", &transactions.transactional_id);
+        }
+        let healthy = Arc::new(AtomicBool::new(true));
+        let undelivered = Undelivered::default();
+        let context = HealthContext {
+            healthy: healthy.clone(),
+            // Transactions record the gaps of whole aborted batches, and
+            // take their records off once committed.
+            gaps: config.transactions.is_none().then(|| metrics.clone()),
+            undelivered: config.transactions.is_none().then(|| undelivered.clone()),
+        };
+        let producer: BaseProducer<HealthContext> = client_config
+            .create_with_context(context)
+            .map_err(kafka_error)?;
+        if config.transactions.is_some() {
+            producer
+                .init_transactions(TRANSACTION_TIMEOUT)
+                .map_err(kafka_error)?;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+        let worker = KafkaWorker {
+            producer,
+            undelivered: undelivered.clone(),
+            topic: config.topic.clone(),
+            topics: config.topics.clone(),
+            transactions: config.transactions.clone(),
+            metrics: metrics.clone(),
+            batch: 0,
+            last_probe: Instant::now(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("fusionKafka".to_string())
+            .spawn(move || worker.run(receiver))
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        info!("Producing events to Kafka topic {}", config.topic);
+
+        let standby = match &config.standby {
+            Some(standby) => {
+                let standby_config = KafkaConfig {
+                    standby: None,
+                    ..standby.as_ref().clone()
+                };
+                Some(Standby {
+                    sink: Box::new(KafkaSink::start(
+                        &standby_config,
+                        codecs,
+                        heartbeat,
+                        metrics,
+                    )?),
+                    dual_write: Duration::from_secs(config.dual_write_secs),
+                    active: false,
+                    dual_write_until: None,
+                })
+            }
+            None => None,
+        };
+
+        Ok(KafkaSink {
+            codec,
+            idle: heartbeat.map(IdleTimer::new),
+            sequence: 0,
+            healthy,
+            standby,
+            replayable: config.sink.codec.as_deref().unwrap_or("protobuf") == "protobuf"
+                && !config.sink.framed
+                && config.sink.projection == FieldProjection::default(),
+            keys: config.keys.clone(),
+            sender: Some(sender),
+            thread: Some(thread),
+            undelivered,
+        })
+    }
+
+    /// Queues `event` for the active cluster, waiting while the queue is
+    /// full.
+    pub fn send(&mut self, event: &EncodedEvent) {
+        let (primary, standby) = self.targets();
+        if primary {
+            self.produce(event);
+        }
+        if let (true, Some(standby)) = (standby, &mut self.standby) {
+            standby.sink.send(event);
+        }
+    }
+
+    /// Which of the primary and standby clusters events go to, switching
+    /// to the standby while the primary is unhealthy and back once it
+    /// recovers.
+    fn targets(&mut self) -> (bool, bool) {
+        let healthy = self.healthy.load(Ordering::Relaxed);
+        let standby = match &mut self.standby {
+            Some(standby) => standby,
+            None => return (true, false),
+        };
+
+        if standby.active == healthy {
+            standby.active = !healthy;
+            standby.dual_write_until = Some(Instant::now() + standby.dual_write);
+            if standby.active {
+                warn!("Kafka primary is failing, producing to the standby");
+            } else {
+                info!("Kafka primary recovered, producing to it again");
+            }
+        }
+        let dual_write = standby
+            .dual_write_until
+            .is_some_and(|until| Instant::now() < until);
+
+        (!standby.active || dual_write, standby.active || dual_write)
+    }
+
+    fn produce(&mut self, encoded: &EncodedEvent) {
+        let (sender, payload) = match (&self.sender, encoded.encode(&self.codec)) {
+            (Some(sender), Some(payload)) => (sender, payload),
+            _ => return,
+        };
+        let event = encoded.event;
+
+        let key = self.keys.get(event.kind()).copied().unwrap_or_default();
+        self.undelivered.add(event.slot());
+        let _ = sender.send(KafkaRecord {
+            kind: event.kind(),
+            key: message_key(event, key),
+            payload,
+            headers: headers(event),
+            slot: event.slot(),
+            trace_id: event.trace_id(),
+        });
+        if !event.is_control() {
+            self.sequence += 1;
+        }
+        if let Some(idle) = &mut self.idle {
+            idle.reset();
+        }
+    }
+
+    /// Produces an archived update again, marked with a `backfill` header,
+    /// if this sink's payloads have the archive's format.
+    pub fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        if !self.replayable {
+            return false;
+        }
+
+        let (primary, standby) = self.targets();
+        let mut replayed = false;
+        if primary {
+            replayed = self.produce_update(update);
+        }
+        if let (true, Some(standby)) = (standby, &mut self.standby) {
+            replayed |= standby.sink.replay(update);
+        }
+        replayed
+    }
+
+    fn produce_update(&mut self, update: &SubscribeUpdate) -> bool {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return false,
+        };
+
+        let (kind, slot, key, owner, trace_id, signers) = match &update.update {
+            Some(Update::Account(account)) => (
+                "account",
+                account.slot,
+                account.pubkey.clone(),
+                Pubkey::try_from(account.owner.as_slice()).ok(),
+                account.trace_id.as_deref(),
+                Vec::new(),
+            ),
+            Some(Update::Transaction(transaction)) => (
+                "transaction",
+                transaction.slot,
+                transaction.signature.clone(),
+                None,
+                transaction.trace_id.as_deref(),
+                transaction
+                    .signers
+                    .iter()
+                    .filter_map(|signer| Pubkey::try_from(signer.as_slice()).ok())
+                    .collect(),
+            ),
+            Some(Update::Slot(slot)) => (
+                "slot",
+                slot.slot,
+                slot.slot.to_be_bytes().to_vec(),
+                None,
+                None,
+                Vec::new(),
+            ),
+            _ => return false,
+        };
+        let trace_id = trace_id
+            .and_then(|trace_id| u128::from_str_radix(trace_id, 16).ok())
+            .map(TraceId);
+        self.undelivered.add(slot);
+        let _ = sender.send(KafkaRecord {
+            kind,
+            key,
+            payload: update.encode_to_vec().into(),
+            headers: envelope(kind, slot, owner, trace_id, &signers).insert(Header {
+                key: "backfill",
+                value: Some("true"),
+            }),
+            slot,
+            trace_id,
+        });
+        self.sequence += 1;
+        if let Some(idle) = &mut self.idle {
+            idle.reset();
+        }
+        true
+    }
+
+    /// Lets the thread publish what is queued and waits for it to exit.
+    fn stop(&mut self) {
+        // Closing the queue makes the thread publish what is left and exit.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Sends a heartbeat when heartbeats are on and nothing was sent lately.
+    pub fn heartbeat(&mut self, slot: u64) {
+        let (primary, standby) = self.targets();
+        if primary && self.idle.as_ref().is_some_and(IdleTimer::expired) {
+            self.produce(&EncodedEvent::new(&AccTx::Heartbeat {
+                slot,
+                healthy: true,
+            }));
+        }
+        if let (true, Some(standby)) = (standby, &mut self.standby) {
+            standby.sink.heartbeat(slot);
+        }
+    }
+
+    /// Marks everything up to `slot` and the records sent so far as
+    /// complete. Each cluster counts its own records.
+    pub fn watermark(&mut self, slot: u64) {
+        let (primary, standby) = self.targets();
+        if primary {
+            self.produce(&EncodedEvent::new(&AccTx::Watermark {
+                slot,
+                max_sequence: self.sequence,
+            }));
+        }
+        if let (true, Some(standby)) = (standby, &mut self.standby) {
+            standby.sink.watermark(slot);
+        }
+    }
+
+    pub fn status(&self) -> serde_json::Value {
+        serde_json::json!({
+            "healthy": self.healthy.load(Ordering::Relaxed),
+            "records": self.sequence,
+            "standby": self.standby.as_ref().map(|standby| serde_json::json!({
+                "active": standby.active,
+                "sink": standby.sink.status(),
+            })),
+        })
+    }
+}
+
+impl Sink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        self.send(event);
+        Ok(())
+    }
+
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        KafkaSink::heartbeat(self, slot);
+        Ok(())
+    }
+
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        KafkaSink::watermark(self, slot);
+        Ok(())
+    }
+
+    fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        KafkaSink::replay(self, update)
+    }
+
+    fn status(&self) -> serde_json::Value {
+        KafkaSink::status(self)
+    }
+
+    fn pending(&self) -> u64 {
+        let standby = self
+            .standby
+            .as_ref()
+            .map(|standby| standby.sink.pending())
+            .unwrap_or_default();
+        self.undelivered.get() + standby
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        let standby = self
+            .standby
+            .as_ref()
+            .and_then(|standby| standby.sink.undelivered_from());
+        self.undelivered.oldest().into_iter().chain(standby).min()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stop();
+        if let Some(standby) = &mut self.standby {
+            standby.sink.stop();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for KafkaSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Marks the producer unhealthy when messages fail to be delivered or all
+/// brokers are down, and healthy again once a message is delivered.
+struct HealthContext {
+    healthy: Arc<AtomicBool>,
+    /// Records undelivered messages as gaps when set.
+    gaps: Option<Arc<PluginMetrics>>,
+    /// Takes messages off once delivered or failed when set.
+    undelivered: Option<Undelivered>,
+}
+
+impl ClientContext for HealthContext {
+    fn error(&self, error: KafkaError, reason: &str) {
+        if error.rdkafka_error_code() == Some(RDKafkaErrorCode::AllBrokersDown) {
+            self.healthy.store(false, Ordering::Relaxed);
+        }
+        error!("librdkafka: {}: {}", error, reason);
+    }
+}
+
+impl ProducerContext for HealthContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        self.healthy
+            .store(delivery_result.is_ok(), Ordering::Relaxed);
+        let (message, error) = match delivery_result {
+            Ok(message) => (message, None),
+            Err((error, message)) => (message, Some(error)),
+        };
+        let header = |key: &str| {
+            message.headers().and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|header| header.key == key)
+                    .and_then(|header| header.value)
+                    .map(String::from_utf8_lossy)
+            })
+        };
+        let slot: Option<u64> = header("slot").and_then(|slot| slot.parse().ok());
+        if let Some(error) = error {
+            if let Some(trace_id) = header("trace_id") {
+                warn!("Failed to deliver trace {} to Kafka: {}", trace_id, error);
+            }
+            if let (Some(metrics), Some(slot)) = (&self.gaps, slot) {
+                metrics.gaps.record("kafka", slot, slot, 1);
+            }
+        }
+
+        // Messages purged when the producer is dropped before delivering
+        // them stay undelivered.
+        let purged = matches!(
+            error,
+            Some(KafkaError::MessageProduction(
+                RDKafkaErrorCode::PurgeQueue | RDKafkaErrorCode::PurgeInflight
+            ))
+        );
+        if let (Some(undelivered), Some(slot), false) = (&self.undelivered, slot, purged) {
+            undelivered.done([slot]);
+        }
+    }
+}
+
+struct KafkaWorker {
+    producer: BaseProducer<HealthContext>,
+    topic: String,
+    topics: HashMap<String, String>,
+    transactions: Option<KafkaTransactionsConfig>,
+    metrics: Arc<PluginMetrics>,
+    undelivered: Undelivered,
+    batch: u64,
+    last_probe: Instant,
+}
+
+impl KafkaWorker {
+    fn run(mut self, receiver: Receiver<KafkaRecord>) {
+        let transactions = match self.transactions.clone() {
+            Some(transactions) => transactions,
+            None => {
+                loop {
+                    match receiver.recv_timeout(PROBE_INTERVAL) {
+                        // Delivery reports take enqueued records off.
+                        Ok(record) => {
+                            let topic = self.topic_of(&record);
+                            if let Err(error) = self.produce(topic, &record) {
+                                self.dropped(topic, &record, error);
+                                self.undelivered.done([record.slot]);
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                    self.producer.poll(Duration::ZERO);
+                    self.probe();
+                }
+                if let Err(error) = self.producer.flush(TRANSACTION_TIMEOUT) {
+                    error!("Failed to flush Kafka producer: {}", error);
+                }
+                return;
+            }
+        };
+
+        let batch_timeout = Duration::from_millis(transactions.batch_timeout_ms);
+        let mut records = Vec::with_capacity(transactions.batch_size);
+        let mut closed = false;
+        while !closed {
+            let deadline = Instant::now() + batch_timeout;
+            while records.len() < transactions.batch_size {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(record) => records.push(record),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+
+            if !records.is_empty() {
+                self.commit(&transactions, &records);
+                self.undelivered
+                    .done(records.iter().map(|record| record.slot));
+                records.clear();
+            }
+            self.probe();
+        }
+    }
+
+    /// While unhealthy, and so likely failed over with nothing to deliver,
+    /// checks now and then whether the brokers answer again.
+    fn probe(&mut self) {
+        let healthy = &self.producer.context().healthy;
+        if healthy.load(Ordering::Relaxed) || self.last_probe.elapsed() < PROBE_INTERVAL {
+            return;
+        }
+
+        self.last_probe = Instant::now();
+        if self
+            .producer
+            .client()
+            .fetch_metadata(Some(&self.topic), PROBE_INTERVAL)
+            .is_ok()
+        {
+            healthy.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Publishes a batch and its checkpoint in one transaction, retrying
+    /// aborted transactions with a growing backoff until they commit, fail
+    /// fatally or run out of attempts.
+    fn commit(&mut self, transactions: &KafkaTransactionsConfig, records: &[KafkaRecord]) {
+        self.batch += 1;
+        let checkpoint = KafkaRecord {
+            kind: CHECKPOINT,
+            key: transactions.transactional_id.as_bytes().to_vec(),
+            payload: serde_json::json!({
+                "transactional_id": transactions.transactional_id,
+                "batch": self.batch,
+                "events": records.len(),
+                "first_slot": records.iter().map(|record| record.slot).min(),
+                "last_slot": records.iter().map(|record| record.slot).max(),
+            })
+            .to_string()
+            .into_bytes()
+            .into(),
+            headers: OwnedHeaders::new(),
+            slot: 0,
+            trace_id: None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.transact(transactions, records, &checkpoint) {
+                Ok(()) => return,
+                Err(error) => error,
+            };
+            match &error {
+                KafkaError::Transaction(transaction_error)
+                    if (transaction_error.txn_requires_abort()
+                        || transaction_error.is_retriable())
+                        && attempt < transactions.max_attempts =>
+                {
+                    let backoff = transactions.backoff(attempt);
+                    warn!(
+                        "Retrying Kafka transaction in {:?} after attempt {}: {}",
+                        backoff, attempt, error
+                    );
+                    std::thread::sleep(backoff);
+                }
+                _ => {
+                    self.producer
+                        .context()
+                        .healthy
+                        .store(false, Ordering::Relaxed);
+                    let error = format!(
+                        "Dropping batch {} of {} events after {} attempts, Kafka error: {}",
+                        self.batch,
+                        records.len(),
+                        attempt,
+                        error
+                    );
+                    error!("{}", error);
+                    self.metrics.record_error(error);
+                    self.metrics.gaps.record(
+                        "kafka",
+                        records
+                            .iter()
+                            .map(|record| record.slot)
+                            .min()
+                            .unwrap_or_default(),
+                        records
+                            .iter()
+                            .map(|record| record.slot)
+                            .max()
+                            .unwrap_or_default(),
+                        records.len() as u64,
+                    );
+                    for _ in records {
+                        PluginMetrics::increment(&self.metrics.dropped_events);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// One attempt at the transaction of a batch. A transaction that fails
+    /// once begun, including a record failing to be enqueued, is aborted
+    /// whole, so that the next attempt can begin anew.
+    fn transact(
+        &self,
+        transactions: &KafkaTransactionsConfig,
+        records: &[KafkaRecord],
+        checkpoint: &KafkaRecord,
+    ) -> KafkaResult<()> {
+        self.producer.begin_transaction()?;
+        let committed = (|| {
+            for record in records {
+                self.produce(self.topic_of(record), record)?;
+            }
+            self.produce(&transactions.checkpoint_topic, checkpoint)?;
+            self.commit_transaction(transactions)
+        })();
+
+        if committed.is_err() {
+            if let Err(error) = self.producer.abort_transaction(TRANSACTION_TIMEOUT) {
+                error!("Failed to abort Kafka transaction: {}", error);
+            }
+        }
+        committed
+    }
+
+    /// Commits the open transaction, trying again while the error is
+    /// retriable, as the transaction stays open in between.
+    fn commit_transaction(&self, transactions: &KafkaTransactionsConfig) -> KafkaResult<()> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.producer.commit_transaction(TRANSACTION_TIMEOUT) {
+                Err(KafkaError::Transaction(error))
+                    if error.is_retriable() && attempt < transactions.max_attempts =>
+                {
+                    error
+                }
+                committed => return committed,
+            };
+            let backoff = transactions.backoff(attempt);
+            warn!("Retrying Kafka commit in {:?}: {}", backoff, error);
+            std::thread::sleep(backoff);
+        }
+    }
+
+    fn topic_of(&self, record: &KafkaRecord) -> &str {
+        self.topics.get(record.kind).unwrap_or(&self.topic)
+    }
+
+    /// Enqueues a record, waiting for delivery reports while librdkafka's
+    /// queue is full. Later records are never enqueued ahead of it.
+    fn produce(&self, topic: &str, kafka_record: &KafkaRecord) -> KafkaResult<()> {
+        let mut record = BaseRecord::to(topic)
+            .key(&kafka_record.key)
+            .payload(&*kafka_record.payload)
+            .headers(kafka_record.headers.clone());
+        loop {
+            match self.producer.send(record) {
+                Ok(()) => return Ok(()),
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    self.producer.poll(Duration::from_millis(10));
+                    record = returned;
+                }
+                Err((error, _)) => return Err(error),
+            }
+        }
+    }
+
+    /// Records a record produced outside of transactions that could not be
+    /// enqueued as a gap.
+    fn dropped(&self, topic: &str, kafka_record: &KafkaRecord, error: KafkaError) {
+        let error = match kafka_record.trace_id {
+            Some(trace_id) => format!(
+                "Failed to produce trace {} to Kafka topic {}: {}",
+                trace_id, topic, error
+            ),
+            None => format!("Failed to produce to Kafka topic {}: {}", topic, error),
+        };
+        warn!("{}", error);
+        self.metrics.record_error(error);
+        let slot = kafka_record.slot;
+        self.metrics.gaps.record("kafka", slot, slot, 1);
+        PluginMetrics::increment(&self.metrics.dropped_events);
+    }
+}
+
+fn message_key(event: &AccTx, key: MessageKey) -> Vec<u8> {
+    let field = match (key, event) {
+        (key, AccTx::TxWithEffects { transaction, .. }) => return message_key(transaction, key),
+        (
+            MessageKey::Owner | MessageKey::Program,
+            AccTx::Acc(AccountUpdate { owner, .. }) | AccTx::Lifecycle { owner, .. },
+        ) => Some(owner.clone()),
+        (MessageKey::Program, AccTx::Tx(TransactionUpdate { transaction, .. })) => transaction
+            .message()
+            .program_instructions_iter()
+            .next()
+            .map(|(program_id, _)| program_id.to_bytes().to_vec()),
+        (MessageKey::FeePayer, AccTx::Tx(TransactionUpdate { transaction, .. })) => {
+            Some(transaction.message().fee_payer().to_bytes().to_vec())
+        }
+        (MessageKey::Mint, AccTx::Acc(AccountUpdate { owner, data, .. })) => {
+            TokenAccount::unpack(owner, data).map(|account| account.mint.to_bytes().to_vec())
+        }
+        (MessageKey::Mint, AccTx::MintSupply { mint, .. } | AccTx::NftTransfer { mint, .. }) => {
+            Some(mint.to_bytes().to_vec())
+        }
+        (MessageKey::Slot, event) => Some(event.slot().to_be_bytes().to_vec()),
+        _ => None,
+    };
+
+    field.unwrap_or_else(|| match event {
+        AccTx::Acc(AccountUpdate { pubkey, .. }) => pubkey.clone(),
+        AccTx::Tx(TransactionUpdate { signature, .. }) => signature.as_ref().to_vec(),
+        _ => event.slot().to_be_bytes().to_vec(),
+    })
+}
+
+fn headers(event: &AccTx) -> OwnedHeaders {
+    let owner = match event {
+        AccTx::Acc(AccountUpdate { owner, .. }) | AccTx::Lifecycle { owner, .. } => {
+            Pubkey::try_from(owner.as_slice()).ok()
+        }
+        _ => None,
+    };
+    envelope(
+        event.kind(),
+        event.slot(),
+        owner,
+        event.trace_id(),
+        &event.signers(),
+    )
+}
+
+/// Lets brokers filter and route messages without decoding payloads.
+fn envelope(
+    kind: &str,
+    slot: u64,
+    owner: Option<Pubkey>,
+    trace_id: Option<TraceId>,
+    signers: &[Pubkey],
+) -> OwnedHeaders {
+    let slot = slot.to_string();
+    let schema_version = SCHEMA_VERSION.to_string();
+    let headers = OwnedHeaders::new()
+        .insert(Header {
+            key: "event_type",
+            value: Some(kind),
+        })
+        .insert(Header {
+            key: "slot",
+            value: Some(&slot),
+        })
+        .insert(Header {
+            key: "schema_version",
+            value: Some(&schema_version),
+        });
+
+    let headers = match trace_id {
+        Some(trace_id) => headers.insert(Header {
+            key: "trace_id",
+            value: Some(&trace_id.to_string()),
+        }),
+        None => headers,
+    };
+
+    let headers = match signers.first() {
+        Some(fee_payer) => headers
+            .insert(Header {
+                key: "fee_payer",
+                value: Some(&fee_payer.to_string()),
+            })
+            .insert(Header {
+                key: "signers",
+                value: Some(
+                    &signers
+                        .iter()
+                        .map(Pubkey::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+            }),
+        None => headers,
+    };
+
+    match owner {
+        Some(owner) => headers.insert(Header {
+            key: "owner",
+            value: Some(&owner.to_string()),
+        }),
+        None => headers,
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{config::parse_pubkey, AccTx, AccountUpdate, TransactionUpdate};
+
+/// Human-readable name and tags for a pubkey, e.g. "Raydium AMM".
+#[derive(Debug, Clone, Deserialize)]
+pub struct Label {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabeledPubkey {
+    pub pubkey: Pubkey,
+    pub label: Arc<Label>,
+}
+
+/// Pubkey to label mapping loaded from a JSON object of the form
+/// `{ "<pubkey>": { "name": "...", "tags": ["..."] } }`.
+#[derive(Debug, Default)]
+pub struct Labels {
+    labels: HashMap<Pubkey, Arc<Label>>,
+}
+
+impl Labels {
+    pub fn load(path: &Path) -> GeyserResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let entries: HashMap<String, Label> = serde_json::from_str(&contents).map_err(|error| {
+            GeyserPluginError::ConfigFileReadError {
+                msg: format!("Invalid labels file {:?}: {}", path, error),
+            }
+        })?;
+
+        let labels = entries
+            .into_iter()
+            .map(|(pubkey, label)| Ok((parse_pubkey(&pubkey)?, Arc::new(label))))
+            .collect::<GeyserResult<_>>()?;
+
+        Ok(Labels { labels })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn attach(&self, event: &mut AccTx) {
+        if self.is_empty() {
+            return;
+        }
+
+        match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                labels,
+                ..
+            }) => {
+                for key in [pubkey, owner] {
+                    if let Ok(key) = Pubkey::try_from(key.as_slice()) {
+                        self.push(labels, &key);
+                    }
+                }
+            }
+            AccTx::Tx(TransactionUpdate {
+                transaction,
+                labels,
+                ..
+            }) => {
+                for key in transaction.message().account_keys().iter() {
+                    self.push(labels, key);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&self, labels: &mut Vec<LabeledPubkey>, pubkey: &Pubkey) {
+        if let Some(label) = self.labels.get(pubkey) {
+            labels.push(LabeledPubkey {
+                pubkey: *pubkey,
+                label: label.clone(),
+            });
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+use crate::{AccTx, AccountUpdate, SlotUpdate};
+
+/// Measures how long after the first event for a slot it is reported
+/// confirmed and rooted, as [`AccTx::ConfirmationLatency`] events.
+#[derive(Debug, Default)]
+pub struct ConfirmationLatencyMonitor {
+    first_seen: BTreeMap<u64, Instant>,
+    root: u64,
+}
+
+impl ConfirmationLatencyMonitor {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let status = match event {
+            AccTx::Acc(AccountUpdate {
+                is_startup: false, ..
+            })
+            | AccTx::Tx(_) => None,
+            AccTx::Slot(SlotUpdate { status, .. }) => Some(*status),
+            _ => return Vec::new(),
+        };
+
+        let slot = event.slot();
+        if slot <= self.root {
+            return Vec::new();
+        }
+
+        let status = match status {
+            Some(status @ (SlotStatus::Confirmed | SlotStatus::Rooted)) => status,
+            _ => {
+                self.first_seen.entry(slot).or_insert_with(Instant::now);
+                return Vec::new();
+            }
+        };
+        // Slots only known from the notification itself have nothing to
+        // measure against.
+        let first_seen = match status {
+            SlotStatus::Rooted => {
+                self.root = slot;
+                let first_seen = self.first_seen.remove(&slot);
+                self.first_seen = self.first_seen.split_off(&slot);
+                first_seen
+            }
+            _ => self.first_seen.get(&slot).copied(),
+        };
+
+        match first_seen {
+            Some(first_seen) => vec![AccTx::ConfirmationLatency {
+                slot,
+                status,
+                latency_ms: first_seen.elapsed().as_millis() as u64,
+            }],
+            None => Vec::new(),
+        }
+    }
+}
@@ -1,69 +1,475 @@
-use lazy_static::lazy_static;
-use log::info;
-use smol::channel::{unbounded, Sender};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, StreamExt};
+use log::{info, warn};
+use smol::{Task, Timer};
 use solana_geyser_plugin_interface::geyser_plugin_interface::{
-    GeyserPlugin, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
+    GeyserPlugin, GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
     ReplicaTransactionInfoVersions, Result as GeyserResult, SlotStatus,
 };
-use solana_sdk::{signature::Signature, transaction::SanitizedTransaction};
-use solana_transaction_status::TransactionStatusMeta;
+use solana_sdk::{
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+    signature::Signature,
+};
 
+mod account_cache;
+pub use account_cache::*;
 mod account_filter;
 pub use account_filter::*;
+mod admin;
+pub use admin::*;
+mod azure_blob;
+pub use azure_blob::*;
+mod backfill;
+pub use backfill::*;
+mod bootstrap;
+pub use bootstrap::*;
+mod bubblegum;
+pub use bubblegum::*;
+mod capture;
+pub use capture::*;
+mod chain;
+pub use chain::*;
+mod clock;
+pub use clock::*;
+mod codec;
+pub use codec::*;
+mod config;
+pub use config::*;
+mod datadog;
+pub use datadog::*;
+mod degraded;
+pub use degraded::*;
+mod delta;
+pub use delta::*;
+mod epoch;
+pub use epoch::*;
+mod exit;
+pub use exit::*;
+mod event;
+pub use event::*;
+mod file_sink;
+pub use file_sink::*;
+mod fork_stats;
+pub use fork_stats::*;
+mod grpc;
+pub use grpc::*;
+mod heartbeat;
+pub use heartbeat::*;
+mod instructions;
+pub use instructions::*;
+mod kafka;
+pub use kafka::*;
+mod labels;
+pub use labels::*;
+mod latency;
+pub use latency::*;
+mod lifecycle;
+pub use lifecycle::*;
+mod lookup_table;
+pub use lookup_table::*;
+mod memo;
+pub use memo::*;
+mod metrics;
+pub use metrics::*;
+mod mint_supply;
+pub use mint_supply::*;
+mod multicast;
+pub use multicast::*;
+mod nats_sink;
+pub use nats_sink::*;
+mod nft;
+pub use nft::*;
+mod owner_lamports;
+pub use owner_lamports::*;
+mod pipeline;
+pub use pipeline::*;
+mod postgres_sink;
+pub use postgres_sink::*;
+mod projection;
+pub use projection::*;
+mod pretty;
+pub use pretty::*;
+mod program_upgrades;
+pub use program_upgrades::*;
+mod prometheus;
+pub use prometheus::*;
+mod quota;
+pub use quota::*;
+mod queue;
+pub use queue::*;
+mod raw_trace;
+pub use raw_trace::*;
+mod record;
+pub use record::*;
+mod redaction;
+pub use redaction::*;
+mod rollback;
+pub use rollback::*;
+mod schema;
+pub use schema::*;
+mod shm_ring;
+pub use shm_ring::*;
+mod shm_sink;
+pub use shm_sink::*;
+mod signature_bloom;
+pub use signature_bloom::*;
+mod sink;
+pub use sink::*;
+mod sink_errors;
+pub use sink_errors::*;
+mod sink_latency;
+pub use sink_latency::*;
+mod slot_batch;
+pub use slot_batch::*;
+mod slot_buffer;
+pub use slot_buffer::*;
+mod slot_stats;
+pub use slot_stats::*;
+mod startup;
+pub use startup::*;
+mod stream;
+pub use stream::*;
+mod system;
+pub use system::*;
+mod throughput;
+pub use throughput::*;
+mod token;
+pub use token::*;
+mod token_index;
+pub use token_index::*;
+mod trace;
+pub use trace::*;
+mod transaction_filter;
+pub use transaction_filter::*;
+mod tx_effects;
+pub use tx_effects::*;
+mod tx_error;
+pub use tx_error::*;
+mod uds_sink;
+pub use uds_sink::*;
+mod wal;
+pub use wal::*;
+mod watermark;
+pub use watermark::*;
+mod websocket;
+pub use websocket::*;
+mod zstd_dictionary;
+pub use zstd_dictionary::*;
 
-#[derive(Debug)]
+/// Everything the background writer handles. The validator's notifications
+/// carry their typed [`Event`]; the other variants are derived from them.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum AccTx {
-    Acc {
+    Acc(AccountUpdate),
+    Tx(TransactionUpdate),
+    Lifecycle {
         pubkey: Vec<u8>,
-        lamports: u64,
         owner: Vec<u8>,
-        executable: bool,
-        rent_epoch: u64,
-        data: Vec<u8>,
+        slot: u64,
         write_version: u64,
+        change: AccountChange,
+    },
+    MintSupply {
+        mint: Pubkey,
+        slot: u64,
+        supply: u64,
+        holders: u64,
+        token_accounts: u64,
+    },
+    OwnerLamports {
+        owner: Pubkey,
+        slot: u64,
+        lamports: u64,
+        accounts: u64,
+    },
+    SlotStats {
+        slot: u64,
+        transactions: u64,
+        vote_transactions: u64,
+        failed_transactions: u64,
+        fees: u64,
+        fee_payers: u64,
+    },
+    Throughput {
+        slot: u64,
+        window_secs: u64,
+        transactions_per_sec: f64,
+        account_updates_per_sec: f64,
+        dropped_events_per_sec: f64,
+    },
+    NftTransfer {
+        mint: Pubkey,
+        from: Option<Pubkey>,
+        to: Option<Pubkey>,
+        slot: u64,
+        signature: Signature,
+    },
+    CompressedNft {
+        action: CompressedNftAction,
+        tree: Pubkey,
+        asset_id: Option<Pubkey>,
+        leaf_index: Option<u32>,
+        seq: Option<u64>,
+        owner: Option<Pubkey>,
+        new_owner: Option<Pubkey>,
+        slot: u64,
+        signature: Signature,
+    },
+    NonceAdvanced {
+        nonce_account: Pubkey,
+        authority: Option<Pubkey>,
+        /// Durable nonces advance even when the rest of the transaction fails.
+        failed: bool,
+        slot: u64,
+        signature: Signature,
+    },
+    NonceAccountChanged {
+        nonce_account: Pubkey,
+        authority: Pubkey,
+        blockhash: Hash,
+        slot: u64,
         txn_signature: Option<Signature>,
+    },
+    /// A token account got a new owner, was created or was closed, see
+    /// [`TokenOwnerIndex`].
+    TokenOwnerChanged {
+        account: Pubkey,
+        mint: Pubkey,
+        previous_owner: Option<Pubkey>,
+        owner: Option<Pubkey>,
         slot: u64,
-        is_startup: bool,
     },
-    Tx {
+    AddressLookupTable {
+        address: Pubkey,
+        authority: Option<Pubkey>,
+        /// Unset while the table is active.
+        deactivation_slot: Option<u64>,
+        last_extended_slot: u64,
+        addresses: Vec<Pubkey>,
+        slot: u64,
+        write_version: u64,
+    },
+    ProgramUpgraded {
+        action: ProgramAction,
+        program: Option<Pubkey>,
+        programdata: Option<Pubkey>,
+        authority: Option<Pubkey>,
+        new_authority: Option<Pubkey>,
         slot: u64,
         signature: Signature,
-        is_vote: bool,
-        transaction: SanitizedTransaction,
-        transaction_status_meta: TransactionStatusMeta,
-        index: Option<usize>,
+    },
+    ProgramDataChanged {
+        programdata: Pubkey,
+        program: Option<Pubkey>,
+        deployed_slot: u64,
+        upgrade_authority: Option<Pubkey>,
+        previous_deployed_slot: Option<u64>,
+        previous_upgrade_authority: Option<Pubkey>,
+        slot: u64,
+        txn_signature: Option<Signature>,
+    },
+    EpochStarted {
+        epoch: u64,
+        first_slot: u64,
+        slot: u64,
+    },
+    Slot(SlotUpdate),
+    /// A slot on a fork that died, see [`RollbackTracker`].
+    SlotRollback {
+        slot: u64,
+    },
+    Block(BlockUpdate),
+    ForkStats {
+        first_slot: u64,
+        slot: u64,
+        rooted_slots: u64,
+        skipped_slots: u64,
+        fork_switches: u64,
+    },
+    ConfirmationLatency {
+        slot: u64,
+        status: SlotStatus,
+        latency_ms: u64,
+    },
+    /// A transaction and the account updates carrying its signature, see
+    /// [`TxEffectsJoiner`].
+    TxWithEffects {
+        slot: u64,
+        signature: Signature,
+        transaction: Box<AccTx>,
+        accounts: Vec<AccTx>,
+    },
+    /// Written to sinks that have been idle, see [`HeartbeatConfig`].
+    Heartbeat {
+        slot: u64,
+        healthy: bool,
+    },
+    /// Everything a sink wrote for slots up to the rooted `slot`, and its
+    /// first `max_sequence` records overall, is complete.
+    Watermark {
+        slot: u64,
+        max_sequence: u64,
     },
 }
 
 impl Default for AccTx {
     fn default() -> Self {
-        AccTx::Acc {
-            pubkey: Vec::default(),
-            lamports: u64::default(),
-            owner: Vec::default(),
-            executable: bool::default(),
-            rent_epoch: u64::default(),
-            data: Vec::default(),
-            write_version: u64::default(),
-            txn_signature: Option::default(),
-            slot: u64::default(),
-            is_startup: bool::default(),
-        }
+        AccTx::Acc(AccountUpdate::default())
+    }
+}
+
+impl fmt::Display for AccTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
     }
 }
 
 impl AccTx {
-    pub fn to_string(&self) -> String {
-        format!("{:?}", self)
+    pub fn slot(&self) -> u64 {
+        match self {
+            AccTx::Acc(AccountUpdate { slot, .. })
+            | AccTx::Tx(TransactionUpdate { slot, .. })
+            | AccTx::Lifecycle { slot, .. }
+            | AccTx::MintSupply { slot, .. }
+            | AccTx::OwnerLamports { slot, .. }
+            | AccTx::SlotStats { slot, .. }
+            | AccTx::Throughput { slot, .. }
+            | AccTx::NftTransfer { slot, .. }
+            | AccTx::CompressedNft { slot, .. }
+            | AccTx::NonceAdvanced { slot, .. }
+            | AccTx::NonceAccountChanged { slot, .. }
+            | AccTx::TokenOwnerChanged { slot, .. }
+            | AccTx::AddressLookupTable { slot, .. }
+            | AccTx::ProgramUpgraded { slot, .. }
+            | AccTx::ProgramDataChanged { slot, .. }
+            | AccTx::EpochStarted { slot, .. }
+            | AccTx::Slot(SlotUpdate { slot, .. })
+            | AccTx::SlotRollback { slot }
+            | AccTx::Block(BlockUpdate { slot, .. })
+            | AccTx::ForkStats { slot, .. }
+            | AccTx::ConfirmationLatency { slot, .. }
+            | AccTx::TxWithEffects { slot, .. }
+            | AccTx::Heartbeat { slot, .. }
+            | AccTx::Watermark { slot, .. } => *slot,
+        }
+    }
+
+    /// Stable snake_case name of the variant, for routing metadata.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AccTx::Acc(_) => AccountUpdate::KIND,
+            AccTx::Tx(_) => TransactionUpdate::KIND,
+            AccTx::Lifecycle { .. } => "lifecycle",
+            AccTx::MintSupply { .. } => "mint_supply",
+            AccTx::OwnerLamports { .. } => "owner_lamports",
+            AccTx::SlotStats { .. } => "slot_stats",
+            AccTx::Throughput { .. } => "throughput",
+            AccTx::NftTransfer { .. } => "nft_transfer",
+            AccTx::CompressedNft { .. } => "compressed_nft",
+            AccTx::NonceAdvanced { .. } => "nonce_advanced",
+            AccTx::NonceAccountChanged { .. } => "nonce_account_changed",
+            AccTx::TokenOwnerChanged { .. } => "token_owner_changed",
+            AccTx::AddressLookupTable { .. } => "address_lookup_table",
+            AccTx::ProgramUpgraded { .. } => "program_upgraded",
+            AccTx::ProgramDataChanged { .. } => "program_data_changed",
+            AccTx::EpochStarted { .. } => "epoch_started",
+            AccTx::Slot(_) => SlotUpdate::KIND,
+            AccTx::SlotRollback { .. } => "slot_rollback",
+            AccTx::Block(_) => BlockUpdate::KIND,
+            AccTx::ForkStats { .. } => "fork_stats",
+            AccTx::ConfirmationLatency { .. } => "confirmation_latency",
+            AccTx::TxWithEffects { .. } => "tx_with_effects",
+            AccTx::Heartbeat { .. } => "heartbeat",
+            AccTx::Watermark { .. } => "watermark",
+        }
+    }
+
+    /// Set on accounts and transactions when trace ids are on.
+    pub fn trace_id(&self) -> Option<TraceId> {
+        match self {
+            AccTx::Acc(AccountUpdate { trace_id, .. })
+            | AccTx::Tx(TransactionUpdate { trace_id, .. }) => *trace_id,
+            _ => None,
+        }
+    }
+
+    /// Signers of transactions, the fee payer first.
+    pub fn signers(&self) -> Vec<Pubkey> {
+        match self {
+            AccTx::Tx(TransactionUpdate { transaction, .. }) => {
+                let message = transaction.message();
+                message
+                    .account_keys()
+                    .iter()
+                    .take(message.header().num_required_signatures as usize)
+                    .copied()
+                    .collect()
+            }
+            AccTx::TxWithEffects { transaction, .. } => transaction.signers(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Replaces the data of an account with its hash and length.
+    pub fn strip_data(&mut self) {
+        if let AccTx::Acc(AccountUpdate {
+            data,
+            data_hash,
+            data_len,
+            ..
+        }) = self
+        {
+            if data_hash.is_none() {
+                *data_hash = Some(hash(data));
+                *data_len = Some(data.len() as u64);
+                *data = Vec::new();
+            }
+        }
+    }
+
+    /// Accounts whose data was replaced by its hash, in degraded mode or
+    /// for executables when `strip_executable_data` is set.
+    pub fn is_metadata_only(&self) -> bool {
+        matches!(
+            self,
+            AccTx::Acc(AccountUpdate {
+                data_hash: Some(_),
+                ..
+            })
+        )
+    }
+
+    /// Heartbeats and watermarks, which sinks write about themselves.
+    pub fn is_control(&self) -> bool {
+        matches!(self, AccTx::Heartbeat { .. } | AccTx::Watermark { .. })
     }
 
     pub fn into_bytes(&self) -> Vec<u8> {
         self.to_string().into_bytes()
     }
 
+    pub fn project<'a>(&'a self, projection: &'a FieldProjection) -> Projected<'a> {
+        Projected {
+            event: self,
+            projection,
+        }
+    }
+
+    pub fn into_projected_bytes(&self, projection: &FieldProjection) -> Vec<u8> {
+        format!("{:?}", self.project(projection)).into_bytes()
+    }
+
     pub fn into_acc(slot: u64, is_startup: bool, value: &ReplicaAccountInfoVersions) -> Self {
         match value {
-            ReplicaAccountInfoVersions::V0_0_1(inner_account) => Self::Acc {
+            ReplicaAccountInfoVersions::V0_0_1(inner_account) => Self::Acc(AccountUpdate {
                 pubkey: inner_account.pubkey.to_owned(),
                 lamports: inner_account.lamports,
                 owner: inner_account.owner.to_owned(),
@@ -74,8 +480,15 @@ impl AccTx {
                 txn_signature: Option::default(),
                 slot,
                 is_startup,
-            },
-            ReplicaAccountInfoVersions::V0_0_2(inner_account) => Self::Acc {
+                data_hash: Option::default(),
+                data_len: Option::default(),
+                capture_ns: Option::default(),
+                wall_clock_ms: Option::default(),
+                unix_timestamp: Option::default(),
+                trace_id: Option::default(),
+                labels: Vec::default(),
+            }),
+            ReplicaAccountInfoVersions::V0_0_2(inner_account) => Self::Acc(AccountUpdate {
                 pubkey: inner_account.pubkey.to_owned(),
                 lamports: inner_account.lamports,
                 owner: inner_account.owner.to_owned(),
@@ -86,60 +499,474 @@ impl AccTx {
                 txn_signature: inner_account.txn_signature.cloned(),
                 slot,
                 is_startup,
-            },
+                data_hash: Option::default(),
+                data_len: Option::default(),
+                capture_ns: Option::default(),
+                wall_clock_ms: Option::default(),
+                unix_timestamp: Option::default(),
+                trace_id: Option::default(),
+                labels: Vec::default(),
+            }),
+        }
+    }
+
+    pub fn into_block(value: &ReplicaBlockInfoVersions) -> Self {
+        match value {
+            ReplicaBlockInfoVersions::V0_0_1(blockinfo) => Self::Block(BlockUpdate {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                parent_slot: Option::default(),
+                parent_blockhash: Option::default(),
+                block_time: blockinfo.block_time,
+                block_height: blockinfo.block_height,
+                executed_transaction_count: Option::default(),
+                rewards: blockinfo.rewards.to_vec(),
+            }),
+
+            ReplicaBlockInfoVersions::V0_0_2(blockinfo) => Self::Block(BlockUpdate {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                parent_slot: Some(blockinfo.parent_slot),
+                parent_blockhash: Some(blockinfo.parent_blockhash.to_string()),
+                block_time: blockinfo.block_time,
+                block_height: blockinfo.block_height,
+                executed_transaction_count: Some(blockinfo.executed_transaction_count),
+                rewards: blockinfo.rewards.to_vec(),
+            }),
         }
     }
 
     pub fn into_tx(slot: u64, value: &ReplicaTransactionInfoVersions) -> Self {
         match value {
-            ReplicaTransactionInfoVersions::V0_0_1(inner_tx) => Self::Tx {
+            ReplicaTransactionInfoVersions::V0_0_1(inner_tx) => Self::Tx(TransactionUpdate {
                 slot,
                 signature: inner_tx.signature.to_owned(),
                 is_vote: inner_tx.is_vote,
                 transaction: inner_tx.transaction.to_owned(),
                 transaction_status_meta: inner_tx.transaction_status_meta.to_owned(),
                 index: Option::default(),
-            },
+                capture_ns: Option::default(),
+                wall_clock_ms: Option::default(),
+                unix_timestamp: Option::default(),
+                trace_id: Option::default(),
+                labels: Vec::default(),
+                memos: Vec::default(),
+                sol_transfers: Vec::default(),
+                error_class: Option::default(),
+            }),
 
-            ReplicaTransactionInfoVersions::V0_0_2(inner_tx) => Self::Tx {
+            ReplicaTransactionInfoVersions::V0_0_2(inner_tx) => Self::Tx(TransactionUpdate {
                 slot,
                 signature: inner_tx.signature.to_owned(),
                 is_vote: inner_tx.is_vote,
                 transaction: inner_tx.transaction.to_owned(),
                 transaction_status_meta: inner_tx.transaction_status_meta.to_owned(),
                 index: Some(inner_tx.index),
-            },
+                capture_ns: Option::default(),
+                wall_clock_ms: Option::default(),
+                unix_timestamp: Option::default(),
+                trace_id: Option::default(),
+                labels: Vec::default(),
+                memos: Vec::default(),
+                sol_transfers: Vec::default(),
+                error_class: Option::default(),
+            }),
         }
     }
 }
 
-lazy_static! {
-    static ref SENDER: Sender<AccTx> = {
-        let (sender, receiver) = unbounded::<AccTx>();
+#[allow(clippy::large_enum_variant)]
+enum WriterInput {
+    Event(AccTx),
+    Tick,
+    Admin(AdminCommand),
+    Closed,
+}
+
+/// `sink` with the error policy and commitment of its `config`.
+fn output(sink: Box<dyn Sink>, config: &SinkConfig, metrics: &Arc<PluginMetrics>) -> Box<dyn Sink> {
+    let sink = GuardedSink::wrap(sink, config, metrics.clone());
+    CommittedSink::wrap(SlotBatchSink::wrap(sink, config), config)
+}
+
+/// State the writer shares with the plugin and the admin API.
+struct WriterShared {
+    slot_clock: Option<Arc<SlotClock>>,
+    subscribers: Option<Arc<Subscribers>>,
+    admin: Option<Arc<AdminState>>,
+    wal: Option<Arc<WriteAheadLog>>,
+    token_index: Option<Arc<TokenOwnerIndex>>,
+    exit: Arc<ExitBarrier>,
+}
+
+fn spawn_writer(
+    config: &PluginConfig,
+    metrics: Arc<PluginMetrics>,
+    codecs: &CodecRegistry,
+    shared: WriterShared,
+) -> GeyserResult<(EventQueue, Task<()>)> {
+    let WriterShared {
+        slot_clock,
+        subscribers,
+        admin,
+        wal,
+        token_index,
+        exit,
+    } = shared;
+    let (queue, receiver) = EventQueue::new(&config.queue, metrics.clone(), wal.clone());
+
+    let heartbeat = config.heartbeat.as_ref();
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if let Some(subscribers) = subscribers {
+        sinks.push(output(
+            Box::new(subscribers),
+            &SinkConfig::default(),
+            &metrics,
+        ));
+    }
+    if let Some(kafka) = &config.kafka {
+        sinks.push(output(
+            Box::new(KafkaSink::start(kafka, codecs, heartbeat, metrics.clone())?),
+            &kafka.sink,
+            &metrics,
+        ));
+    }
+    if let Some(azure_blob) = &config.azure_blob {
+        sinks.push(output(
+            Box::new(AzureBlobSink::start(azure_blob, codecs, metrics.clone())?),
+            &azure_blob.sink,
+            &metrics,
+        ));
+    }
+    if let Some(delta) = &config.delta {
+        sinks.push(output(
+            Box::new(DeltaSink::start(delta, codecs, metrics.clone())?),
+            &delta.sink,
+            &metrics,
+        ));
+    }
+    if let Some(multicast) = &config.multicast {
+        sinks.push(output(
+            Box::new(MulticastSink::start(multicast, codecs, metrics.clone())?),
+            &multicast.sink,
+            &metrics,
+        ));
+    }
+    if let Some(nats) = &config.nats {
+        sinks.push(output(
+            Box::new(NatsSink::start(nats, codecs, metrics.clone())?),
+            &nats.sink,
+            &metrics,
+        ));
+    }
+    if let Some(postgres) = &config.postgres {
+        sinks.push(output(
+            Box::new(PostgresSink::start(postgres, metrics.clone())?),
+            &postgres.sink,
+            &metrics,
+        ));
+    }
+    if let Some(shm_ring) = &config.shm_ring {
+        sinks.push(output(
+            Box::new(ShmRingSink::create(shm_ring, codecs, metrics.clone())?),
+            &shm_ring.sink,
+            &metrics,
+        ));
+    }
+    if let Some(uds) = &config.uds {
+        sinks.push(output(
+            Box::new(UdsSink::start(uds, codecs, metrics.clone())?),
+            &uds.sink,
+            &metrics,
+        ));
+    }
+    if let Some(pretty) = &config.pretty {
+        sinks.push(output(
+            Box::new(PrettySink::open(pretty)?),
+            &SinkConfig::default(),
+            &metrics,
+        ));
+    }
+    for route in FileRoute::ALL {
+        sinks.push(output(
+            Box::new(FileSink::create(route, config, codecs, heartbeat)?),
+            route.sink_config(config),
+            &metrics,
+        ));
+    }
+    if let Some(sink_latency) = &config.sink_latency {
+        sinks = sinks
+            .into_iter()
+            .map(|sink| {
+                Box::new(BudgetedSink::new(sink, sink_latency, metrics.clone())) as Box<dyn Sink>
+            })
+            .collect();
+    }
+    let mut watermarks = config.watermarks.as_ref().map(WatermarkTimer::new);
+    let mut slot_buffer = config.slot_buffer.as_ref().map(SlotBuffer::new);
+    let backfill_config = config.backfill.clone();
+    let mut pipeline = Pipeline::new(config, metrics.clone(), slot_clock, token_index)?;
+    let startup_batch_size = config.startup.batch_size.max(1);
+    let mut startup = StartupProgress::new(&config.startup, metrics.clone());
+    // Keys of logged events handed to the sinks, removed from the log once
+    // every sink delivered their slot.
+    let mut handed = BTreeSet::new();
+
+    // Taken ahead of the queue, which may not have room for all of them
+    // before the writer runs.
+    let mut recovered = VecDeque::new();
+    if let Some(wal) = &wal {
+        for update in wal.recover() {
+            match recovered_event(&update) {
+                Some(event) => recovered.push_back(event),
+                None => {
+                    for sink in &mut sinks {
+                        sink.replay(&update);
+                    }
+                    handed.extend(WriteAheadLog::update_key(&update));
+                }
+            }
+        }
+    }
 
-        use std::{fs::File, io::prelude::*};
+    let writer = smol::spawn(async move {
+        let mut ticker = Timer::interval(Duration::from_secs(1));
 
-        let mut accs_file = File::create("./accs.txt").unwrap();
-        let mut txs_file = File::create("./txs.txt").unwrap();
+        loop {
+            let input = match recovered.pop_front() {
+                Some(value) => WriterInput::Event(value),
+                None => {
+                    future::or(
+                        async {
+                            match receiver.recv().await {
+                                Ok(value) => WriterInput::Event(value),
+                                Err(_) => WriterInput::Closed,
+                            }
+                        },
+                        future::or(
+                            async {
+                                ticker.next().await;
+                                WriterInput::Tick
+                            },
+                            async {
+                                match &admin {
+                                    Some(admin) => match admin.next_command().await {
+                                        Some(command) => WriterInput::Admin(command),
+                                        None => future::pending().await,
+                                    },
+                                    None => future::pending().await,
+                                }
+                            },
+                        ),
+                    )
+                    .await
+                }
+            };
 
-        smol::spawn(async move {
-            while let Ok(value) = receiver.recv().await {
-                match value {
-                    AccTx::Acc { .. } => {
-                        accs_file.write_all(&value.into_bytes()).unwrap();
+            let is_tick = matches!(input, WriterInput::Tick);
+            let mut failure = None;
+            let mut bulk = false;
+            let mut logged = Vec::new();
+            if let (Some(_), WriterInput::Event(value)) = (&wal, &input) {
+                logged.extend(WriteAheadLog::key(value));
+            }
+            let values = match input {
+                WriterInput::Event(value) if StartupProgress::is_startup(&value) => {
+                    // Startup accounts arrive faster than anything else, so
+                    // take all that are queued and write them at once.
+                    let mut batch = vec![value];
+                    while batch.len() < startup_batch_size {
+                        match receiver.try_recv() {
+                            Ok(value) => {
+                                if wal.is_some() {
+                                    logged.extend(WriteAheadLog::key(&value));
+                                }
+                                batch.push(value)
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    exit.take(batch.len() as u64);
+                    startup.observe(
+                        batch
+                            .iter()
+                            .filter(|value| StartupProgress::is_startup(value))
+                            .count() as u64,
+                    );
+                    bulk = true;
+                    batch
+                        .into_iter()
+                        .flat_map(|value| pipeline.process(value))
+                        .collect()
+                }
+                WriterInput::Event(value) => {
+                    exit.take(1);
+                    pipeline.process(value)
+                }
+                WriterInput::Tick => {
+                    startup.tick();
+                    pipeline.tick()
+                }
+                WriterInput::Admin(AdminCommand::Backfill(id)) => {
+                    backfill(&metrics.gaps, id, backfill_config.as_ref(), &mut sinks);
+                    continue;
+                }
+                WriterInput::Admin(command) => {
+                    for sink in &mut sinks {
+                        let done = if let AdminCommand::RotateFiles = command {
+                            sink.rotate()
+                        } else {
+                            sink.flush()
+                        };
+                        if let Err(error) = done {
+                            let error = format!(
+                                "Failed to {:?} {} output: {}",
+                                command,
+                                sink.name(),
+                                error
+                            );
+                            warn!("{}", error);
+                            metrics.record_error(error);
+                        }
                     }
-                    AccTx::Tx { .. } => {
-                        txs_file.write_all(&value.into_bytes()).unwrap();
+                    continue;
+                }
+                WriterInput::Closed => break,
+            };
+            let values = match &mut slot_buffer {
+                Some(slot_buffer) => slot_buffer.push(values),
+                None => values,
+            };
+
+            if bulk {
+                let values: Vec<EncodedEvent> = values.iter().map(EncodedEvent::new).collect();
+                for sink in &mut sinks {
+                    let started = Instant::now();
+                    escalate(&mut failure, sink.name(), sink.write_batch(&values));
+                    metrics.record_sink_latency(sink.name(), started.elapsed());
+                }
+                for value in &values {
+                    metrics.record_written(value.event.kind(), 1);
+                }
+            } else {
+                for value in values {
+                    let value = EncodedEvent::new(&value);
+                    for sink in &mut sinks {
+                        let started = Instant::now();
+                        escalate(&mut failure, sink.name(), sink.write(&value));
+                        metrics.record_sink_latency(sink.name(), started.elapsed());
                     }
+                    metrics.record_written(value.event.kind(), 1);
                 }
             }
-        })
-        .detach();
+            // Events no sink could take stay logged for the next run.
+            if failure.is_none() {
+                handed.extend(logged);
+            }
+            let slot = pipeline.latest_slot();
+            exit.report(
+                &sinks,
+                slot_buffer
+                    .as_ref()
+                    .map(SlotBuffer::held)
+                    .unwrap_or_default(),
+                slot,
+                pipeline.latest_root(),
+            );
+
+            if is_tick {
+                if let Some(wal) = &wal {
+                    wal.remove_delivered(&mut handed, undelivered_from(&sinks, &slot_buffer));
+                }
+                for sink in &mut sinks {
+                    escalate(&mut failure, sink.name(), sink.heartbeat(slot));
+                }
+                if let Some(admin) = &admin {
+                    let outputs: Vec<serde_json::Value> = sinks
+                        .iter()
+                        .map(|sink| {
+                            serde_json::json!({
+                                "sink": sink.name(),
+                                "status": sink.status(),
+                            })
+                        })
+                        .collect();
+                    admin.report(serde_json::json!({
+                        "latest_slot": slot,
+                        "latest_root": pipeline.latest_root(),
+                        "outputs": outputs,
+                        "slot_buffer": slot_buffer.as_ref().map(SlotBuffer::released_through),
+                    }));
+                }
+            }
+
+            if is_tick && watermarks.as_mut().is_some_and(WatermarkTimer::due) {
+                // Buffered slots are not complete yet.
+                let root = match &slot_buffer {
+                    Some(slot_buffer) => pipeline.latest_root().min(slot_buffer.released_through()),
+                    None => pipeline.latest_root(),
+                };
+                for sink in &mut sinks {
+                    escalate(&mut failure, sink.name(), sink.watermark(root));
+                }
+            }
+
+            if let Some(error) = failure {
+                let error = format!("Stopping the writer, {}", error);
+                warn!("{}", error);
+                metrics.record_error(error);
+                receiver.close();
+                break;
+            }
+        }
+
+        for sink in &mut sinks {
+            if let Err(error) = sink.close() {
+                warn!("Failed to close {} output: {}", sink.name(), error);
+            }
+        }
+        if let Some(wal) = &wal {
+            wal.remove_delivered(&mut handed, undelivered_from(&sinks, &slot_buffer));
+        }
+        exit.report(
+            &sinks,
+            slot_buffer
+                .as_ref()
+                .map(SlotBuffer::held)
+                .unwrap_or_default(),
+            pipeline.latest_slot(),
+            pipeline.latest_root(),
+        );
+    });
+
+    Ok((queue, writer))
+}
 
-        sender
-    };
+/// Lowest slot with events the sinks, or the slot buffer holding them back
+/// from the sinks, have not delivered yet.
+fn undelivered_from(sinks: &[Box<dyn Sink>], slot_buffer: &Option<SlotBuffer>) -> Option<u64> {
+    sinks
+        .iter()
+        .filter_map(|sink| sink.undelivered_from())
+        .chain(slot_buffer.as_ref().and_then(SlotBuffer::oldest_held))
+        .min()
 }
 
+/// Keeps the first error a sink escalated.
+fn escalate(failure: &mut Option<String>, sink: &str, done: std::io::Result<()>) {
+    if let Err(error) = done {
+        failure.get_or_insert_with(|| format!("the {} output failed: {}", sink, error));
+    }
+}
+
+fn writer_stopped() -> GeyserPluginError {
+    GeyserPluginError::Custom("The background writer has stopped".into())
+}
+
+/// # Safety
+///
+/// Called by the validator's plugin manager, which takes ownership of the
+/// returned pointer.
 #[no_mangle]
 #[allow(improper_ctypes_definitions)]
 pub unsafe extern "C" fn _create_plugin() -> *mut dyn GeyserPlugin {
@@ -148,12 +975,42 @@ pub unsafe extern "C" fn _create_plugin() -> *mut dyn GeyserPlugin {
     Box::into_raw(plugin)
 }
 
-#[derive(Debug)]
-pub struct FusionEnginePlugin {}
+#[derive(Debug, Default)]
+pub struct FusionEnginePlugin {
+    config: PluginConfig,
+    codecs: CodecRegistry,
+    account_filter: AccountFilter,
+    transaction_filter: TransactionFilter,
+    program_quotas: Option<ProgramQuotas>,
+    startup_versions: Option<StartupVersions>,
+    metrics: Arc<PluginMetrics>,
+    slot_clock: Option<Arc<SlotClock>>,
+    capture_clock: CaptureClock,
+    /// Applied before events are logged or queued, so that redacted fields
+    /// reach neither the write-ahead log nor any stage in the clear.
+    redactor: Redactor,
+    subscribers: Option<Arc<Subscribers>>,
+    grpc: Option<GrpcServer>,
+    stream: Option<StreamServer>,
+    websocket: Option<WebSocketServer>,
+    datadog: Option<DatadogExporter>,
+    prometheus: Option<PrometheusExporter>,
+    raw_trace: Option<RawTrace>,
+    dictionary_trainer: Option<DictionaryTrainer>,
+    chain: Vec<ChainedPlugin>,
+    degraded: Option<DegradedMode>,
+    trace_ids: Option<TraceIds>,
+    admin: Option<AdminServer>,
+    wal: Option<Arc<WriteAheadLog>>,
+    queue: Option<EventQueue>,
+    writer: Option<Task<()>>,
+    exit: Arc<ExitBarrier>,
+    capture: Arc<VerboseCapture>,
+}
 
 impl FusionEnginePlugin {
     pub fn new() -> Self {
-        FusionEnginePlugin {}
+        FusionEnginePlugin::default()
     }
 }
 
@@ -170,10 +1027,180 @@ impl GeyserPlugin for FusionEnginePlugin {
             config_file
         );
 
+        self.config = PluginConfig::load(config_file)?;
+        self.account_filter = AccountFilter::new(&self.config.account_filter)?;
+        self.transaction_filter = TransactionFilter::new(&self.config.transaction_filter)?;
+        self.redactor = Redactor::new(&self.config.redaction)?;
+        self.program_quotas = match &self.config.program_quotas {
+            Some(program_quotas) => Some(ProgramQuotas::new(program_quotas, self.metrics.clone())?),
+            None => None,
+        };
+        self.startup_versions = self
+            .config
+            .startup
+            .latest_versions_only
+            .then(StartupVersions::default);
+        self.slot_clock = self
+            .config
+            .timestamps
+            .block_time
+            .then(|| Arc::new(SlotClock::default()));
+        self.capture_clock = CaptureClock::new(&self.config.timestamps);
+        self.degraded = self.config.degraded_mode.as_ref().map(DegradedMode::new);
+        if let Some(raw_trace) = &self.config.raw_trace {
+            self.raw_trace = Some(RawTrace::start(raw_trace)?);
+        }
+        if let Some(zstd_dictionaries) = &self.config.zstd_dictionaries {
+            let dictionaries = Arc::new(ZstdDictionaries::open(zstd_dictionaries)?);
+            if let Some(interval) = zstd_dictionaries.retrain_interval_secs {
+                self.dictionary_trainer = Some(DictionaryTrainer::start(
+                    dictionaries.clone(),
+                    Duration::from_secs(interval),
+                )?);
+            }
+            self.codecs.use_dictionaries(dictionaries);
+        }
+        self.trace_ids = self.config.trace_ids.then(TraceIds::default);
+        if self.config.grpc.is_some()
+            || self.config.stream.is_some()
+            || self.config.websocket.is_some()
+        {
+            let subscribers = Arc::new(Subscribers::default());
+            if let Some(grpc) = &self.config.grpc {
+                self.grpc = Some(GrpcServer::start(grpc, subscribers.clone())?);
+            }
+            if let Some(stream) = &self.config.stream {
+                self.stream = Some(StreamServer::start(
+                    stream,
+                    subscribers.clone(),
+                    self.codecs.clone(),
+                )?);
+            }
+            if let Some(websocket) = &self.config.websocket {
+                self.websocket = Some(WebSocketServer::start(
+                    websocket,
+                    subscribers.clone(),
+                    &self.codecs,
+                )?);
+            }
+            self.subscribers = Some(subscribers);
+        }
+        for chained in &self.config.chain {
+            self.chain.push(ChainedPlugin::load(chained)?);
+        }
+        if let Some(datadog) = &self.config.datadog {
+            self.datadog = Some(DatadogExporter::start(datadog, self.metrics.clone())?);
+        }
+        let admin_state = self
+            .config
+            .admin
+            .as_ref()
+            .map(|_| Arc::new(AdminState::default()));
+        self.wal = match &self.config.wal {
+            Some(wal) => Some(Arc::new(WriteAheadLog::open(wal)?)),
+            None => None,
+        };
+        let token_index = self
+            .config
+            .token_owner_index
+            .then(|| Arc::new(TokenOwnerIndex::default()));
+        self.capture = Arc::new(VerboseCapture::new(&self.config.verbose_capture));
+        let (queue, writer) = spawn_writer(
+            &self.config,
+            self.metrics.clone(),
+            &self.codecs,
+            WriterShared {
+                slot_clock: self.slot_clock.clone(),
+                subscribers: self.subscribers.clone(),
+                admin: admin_state.clone(),
+                wal: self.wal.clone(),
+                token_index: token_index.clone(),
+                exit: self.exit.clone(),
+            },
+        )?;
+        if let (Some(admin), Some(state)) = (&self.config.admin, admin_state) {
+            self.admin = Some(AdminServer::start(
+                admin,
+                AdminSources {
+                    config_file: config_file.to_string(),
+                    metrics: self.metrics.clone(),
+                    subscribers: self.subscribers.clone(),
+                    queue: queue.sender().clone(),
+                    token_index,
+                    capture: self.capture.clone(),
+                    state,
+                },
+            )?);
+        }
+        if let Some(prometheus) = &self.config.prometheus {
+            self.prometheus = Some(PrometheusExporter::start(
+                prometheus,
+                self.metrics.clone(),
+                queue.sender().clone(),
+            )?);
+        }
+        if let Some(bootstrap) = &self.config.bootstrap {
+            spawn_bootstrap(bootstrap, queue.sender().clone())?;
+        }
+        self.queue = Some(queue);
+        self.writer = Some(writer);
+
         Ok(())
     }
 
-    fn on_unload(&mut self) {}
+    /// Lets the writer drain its queue and close the sinks, which syncs
+    /// the output files, for up to `shutdown.timeout_secs`, then accounts
+    /// for what each output did not deliver.
+    fn on_unload(&mut self) {
+        let queue = self.queue.take();
+        if let Some(queue) = &queue {
+            queue.close();
+        }
+        let mut drained = true;
+        if let Some(mut writer) = self.writer.take() {
+            let timeout = Duration::from_secs(self.config.shutdown.timeout_secs);
+            drained = smol::block_on(future::or(
+                async {
+                    (&mut writer).await;
+                    true
+                },
+                async {
+                    Timer::after(timeout).await;
+                    false
+                },
+            ));
+            if drained {
+                info!("Writer drained its queue and closed the outputs");
+            } else {
+                warn!("Writer did not drain its queue within {:?}", timeout);
+                writer.detach();
+            }
+        }
+        let queued = queue.map(|queue| queue.sender().len() as u64);
+        let checkpoint = self.exit.checkpoint(drained, queued.unwrap_or_default());
+        info!(
+            "Unloaded with {} events abandoned up to slot {}: {}",
+            checkpoint.total_abandoned(),
+            checkpoint.latest_slot,
+            checkpoint.summary()
+        );
+        if let Some(path) = &self.config.shutdown.checkpoint_path {
+            if let Err(error) = checkpoint.write(path) {
+                warn!("Failed to write exit checkpoint {:?}: {}", path, error);
+            }
+        }
+        self.wal = None;
+        self.grpc = None;
+        self.stream = None;
+        self.websocket = None;
+        self.subscribers = None;
+        self.datadog = None;
+        self.prometheus = None;
+        self.raw_trace = None;
+        self.dictionary_trainer = None;
+        self.admin = None;
+        self.chain.clear();
+    }
 
     fn update_account(
         &mut self,
@@ -181,13 +1208,82 @@ impl GeyserPlugin for FusionEnginePlugin {
         slot: u64,
         is_startup: bool,
     ) -> GeyserResult<()> {
-        let outcome = AccTx::into_acc(slot, is_startup, &account);
-
-        smol::block_on(async move {
-            smol::spawn(async move { SENDER.send(outcome).await })
-                .await
-                .unwrap();
+        if let Some(raw_trace) = &mut self.raw_trace {
+            raw_trace.record(RawNotification::account(slot, is_startup, &account));
+        }
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return Ok(()),
+        };
+        PluginMetrics::increment(&self.metrics.account_updates);
+        if let Some(slot_clock) = &self.slot_clock {
+            slot_clock.observe(&account);
+        }
+        let captured = self.capture.captures(match &account {
+            ReplicaAccountInfoVersions::V0_0_1(inner_account) => inner_account.pubkey,
+            ReplicaAccountInfoVersions::V0_0_2(inner_account) => inner_account.pubkey,
         });
+        if !captured && !self.account_filter.matches(slot, &account) {
+            return Ok(());
+        }
+        for chained in &mut self.chain {
+            chained.update_account(&account, slot, is_startup);
+        }
+        if let (true, Some(startup_versions)) = (is_startup, &mut self.startup_versions) {
+            let (pubkey, write_version) = match &account {
+                ReplicaAccountInfoVersions::V0_0_1(inner_account) => {
+                    (inner_account.pubkey, inner_account.write_version)
+                }
+                ReplicaAccountInfoVersions::V0_0_2(inner_account) => {
+                    (inner_account.pubkey, inner_account.write_version)
+                }
+            };
+            if !startup_versions.is_latest(pubkey, write_version) {
+                return Ok(());
+            }
+        }
+        if let (false, Some(program_quotas)) = (captured, &mut self.program_quotas) {
+            let owner = match &account {
+                ReplicaAccountInfoVersions::V0_0_1(inner_account) => inner_account.owner,
+                ReplicaAccountInfoVersions::V0_0_2(inner_account) => inner_account.owner,
+            };
+            if !program_quotas.admit(owner) {
+                return Ok(());
+            }
+        }
+        let mut outcome = AccTx::into_acc(slot, is_startup, &account);
+        if self.config.strip_executable_data
+            && !captured
+            && matches!(
+                outcome,
+                AccTx::Acc(AccountUpdate {
+                    executable: true,
+                    ..
+                })
+            )
+        {
+            outcome.strip_data();
+        }
+        self.redactor.redact(&mut outcome);
+        self.capture_clock.stamp(&mut outcome);
+        if let Some(trace_ids) = &mut self.trace_ids {
+            trace_ids.stamp(&mut outcome);
+        }
+        if let (false, Some(degraded)) = (captured, &mut self.degraded) {
+            degraded.apply(queue.sender().len(), &mut outcome);
+        }
+        if captured {
+            info!(target: "fusion_engine::capture", "{:?}", outcome);
+        }
+        if let Some(wal) = &self.wal {
+            wal.append(&outcome);
+        }
+
+        if !queue.push(outcome) {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics.gaps.record("writer", slot, slot, 1);
+            return Err(writer_stopped());
+        }
 
         Ok(())
     }
@@ -197,37 +1293,131 @@ impl GeyserPlugin for FusionEnginePlugin {
         transaction: ReplicaTransactionInfoVersions,
         slot: u64,
     ) -> GeyserResult<()> {
-        let outcome = AccTx::into_tx(slot, &transaction);
+        if let Some(raw_trace) = &mut self.raw_trace {
+            raw_trace.record(RawNotification::transaction(slot, &transaction));
+        }
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return Ok(()),
+        };
+        PluginMetrics::increment(&self.metrics.transactions);
+        self.account_filter.observe_transaction(slot, &transaction);
+        let captured = self.capture.captures_transaction(&transaction);
+        if !captured && !self.transaction_filter.matches(&transaction) {
+            return Ok(());
+        }
+        for chained in &mut self.chain {
+            chained.notify_transaction(&transaction, slot);
+        }
+        let mut outcome = AccTx::into_tx(slot, &transaction);
+        self.redactor.redact(&mut outcome);
+        self.capture_clock.stamp(&mut outcome);
+        if let Some(trace_ids) = &mut self.trace_ids {
+            trace_ids.stamp(&mut outcome);
+        }
+        if captured {
+            info!(target: "fusion_engine::capture", "{:?}", outcome);
+        }
+        if let Some(wal) = &self.wal {
+            wal.append(&outcome);
+        }
 
-        smol::block_on(async move {
-            smol::spawn(async move { SENDER.send(outcome).await }).detach();
-        });
+        if !queue.push(outcome) {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics.gaps.record("writer", slot, slot, 1);
+            return Err(writer_stopped());
+        }
 
         Ok(())
     }
 
-    fn notify_block_metadata(&mut self, _blockinfo: ReplicaBlockInfoVersions) -> GeyserResult<()> {
+    fn notify_block_metadata(&mut self, blockinfo: ReplicaBlockInfoVersions) -> GeyserResult<()> {
+        if let Some(raw_trace) = &mut self.raw_trace {
+            raw_trace.record(RawNotification::block(&blockinfo));
+        }
+        if !self.config.notifications.blocks {
+            return Ok(());
+        }
+        for chained in &mut self.chain {
+            chained.notify_block_metadata(&blockinfo);
+        }
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return Ok(()),
+        };
+        PluginMetrics::increment(&self.metrics.blocks);
+        let outcome = AccTx::into_block(&blockinfo);
+        let slot = outcome.slot();
+
+        if !queue.push(outcome) {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics.gaps.record("writer", slot, slot, 1);
+            return Err(writer_stopped());
+        }
+
         Ok(())
     }
 
     fn update_slot_status(
         &mut self,
-        _slot: u64,
-        _parent: Option<u64>,
-        _status: SlotStatus,
+        slot: u64,
+        parent: Option<u64>,
+        status: SlotStatus,
     ) -> GeyserResult<()> {
+        if let Some(raw_trace) = &mut self.raw_trace {
+            raw_trace.record(RawNotification::slot(slot, parent, status));
+        }
+        if !self.config.notifications.slots {
+            return Ok(());
+        }
+        for chained in &mut self.chain {
+            chained.update_slot_status(slot, parent, status);
+        }
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return Ok(()),
+        };
+        PluginMetrics::increment(&self.metrics.slot_updates);
+        let outcome = AccTx::Slot(SlotUpdate {
+            slot,
+            parent,
+            status,
+        });
+        if let Some(wal) = &self.wal {
+            wal.append(&outcome);
+        }
+
+        if !queue.push(outcome) {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics.gaps.record("writer", slot, slot, 1);
+            return Err(writer_stopped());
+        }
+
         Ok(())
     }
 
     fn notify_end_of_startup(&mut self) -> GeyserResult<()> {
+        if let Some(raw_trace) = &mut self.raw_trace {
+            raw_trace.record(RawNotification::EndOfStartup);
+        }
+        for chained in &mut self.chain {
+            chained.notify_end_of_startup();
+        }
+        if let Some(startup_versions) = self.startup_versions.take() {
+            info!(
+                "Skipped {} startup accounts superseded by a later write version",
+                startup_versions.stale()
+            );
+        }
+
         Ok(())
     }
 
     fn account_data_notifications_enabled(&self) -> bool {
-        true
+        self.config.notifications.accounts
     }
 
     fn transaction_notifications_enabled(&self) -> bool {
-        true
+        self.config.notifications.transactions
     }
 }
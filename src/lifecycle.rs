@@ -0,0 +1,83 @@
+use crate::{AccTx, AccountUpdate, CacheUpdate};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountChange {
+    /// First update seen for the account outside of startup replay.
+    Created,
+    Reassigned {
+        previous_owner: Vec<u8>,
+    },
+    Resized {
+        previous_len: usize,
+        len: usize,
+    },
+    Closed,
+}
+
+/// Derives [`AccTx::Lifecycle`] events by comparing each account update
+/// with the previous state of the same pubkey in the [`AccountCache`].
+///
+/// Accounts evicted from the cache are reported created again.
+#[derive(Debug, Default)]
+pub struct LifecycleTracker;
+
+impl LifecycleTracker {
+    pub fn observe(&mut self, event: &AccTx, update: &CacheUpdate) -> Vec<AccTx> {
+        let (pubkey, owner, lamports, data, write_version, slot, is_startup) = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                data,
+                write_version,
+                slot,
+                is_startup,
+                ..
+            }) => (
+                pubkey,
+                owner,
+                *lamports,
+                data,
+                *write_version,
+                *slot,
+                *is_startup,
+            ),
+            _ => return Vec::new(),
+        };
+        let previous = match update {
+            CacheUpdate::Applied(previous) => previous,
+            CacheUpdate::Stale => return Vec::new(),
+        };
+
+        let mut changes = Vec::new();
+        match previous {
+            Some(_) if lamports == 0 => changes.push(AccountChange::Closed),
+            Some(previous) => {
+                if previous.owner != *owner {
+                    changes.push(AccountChange::Reassigned {
+                        previous_owner: previous.owner.clone(),
+                    });
+                }
+                if previous.data.len() != data.len() {
+                    changes.push(AccountChange::Resized {
+                        previous_len: previous.data.len(),
+                        len: data.len(),
+                    });
+                }
+            }
+            None if lamports == 0 || is_startup => {}
+            None => changes.push(AccountChange::Created),
+        }
+
+        changes
+            .into_iter()
+            .map(|change| AccTx::Lifecycle {
+                pubkey: pubkey.clone(),
+                owner: owner.clone(),
+                slot,
+                write_version,
+                change,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,56 @@
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+use crate::{AccTx, AccountUpdate};
+
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: Pubkey =
+    pubkey!("AddressLookupTab1e1111111111111111111111111");
+
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+const LOOKUP_TABLE_STATE: u32 = 1;
+
+/// Decodes address lookup table account updates into
+/// [`AccTx::AddressLookupTable`] events.
+pub fn decode_lookup_table(event: &AccTx) -> Option<AccTx> {
+    let (pubkey, owner, lamports, data, write_version, slot) = match event {
+        AccTx::Acc(AccountUpdate {
+            pubkey,
+            owner,
+            lamports,
+            data,
+            write_version,
+            slot,
+            ..
+        }) => (pubkey, owner, *lamports, data, *write_version, *slot),
+        _ => return None,
+    };
+    if lamports == 0 || owner.as_slice() != ADDRESS_LOOKUP_TABLE_PROGRAM_ID.as_ref() {
+        return None;
+    }
+
+    // Bincode layout of `ProgramState::LookupTable(LookupTableMeta)`,
+    // padded to a fixed size ahead of the address list.
+    let state = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    if state != LOOKUP_TABLE_STATE || data.len() < LOOKUP_TABLE_META_SIZE {
+        return None;
+    }
+    let deactivation_slot = u64::from_le_bytes(data[4..12].try_into().ok()?);
+    let last_extended_slot = u64::from_le_bytes(data[12..20].try_into().ok()?);
+    let authority = match data[21] {
+        0 => None,
+        _ => Some(Pubkey::try_from(&data[22..54]).ok()?),
+    };
+    let addresses = data[LOOKUP_TABLE_META_SIZE..]
+        .chunks_exact(32)
+        .filter_map(|address| Pubkey::try_from(address).ok())
+        .collect();
+
+    Some(AccTx::AddressLookupTable {
+        address: Pubkey::try_from(pubkey.as_slice()).ok()?,
+        authority,
+        deactivation_slot: (deactivation_slot != u64::MAX).then_some(deactivation_slot),
+        last_extended_slot,
+        addresses,
+        slot,
+        write_version,
+    })
+}
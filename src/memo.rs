@@ -0,0 +1,24 @@
+use solana_sdk::{pubkey, pubkey::Pubkey, transaction::SanitizedTransaction};
+use solana_transaction_status::TransactionStatusMeta;
+
+use crate::instructions::resolve_instructions;
+
+pub const MEMO_V1_PROGRAM_ID: Pubkey = pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// Contents of every SPL Memo instruction of the transaction, including
+/// memos issued through CPI. Memos that are not valid UTF-8 are skipped,
+/// as the Memo program itself rejects them.
+pub fn extract_memos(
+    transaction: &SanitizedTransaction,
+    meta: &TransactionStatusMeta,
+) -> Vec<String> {
+    resolve_instructions(transaction, meta)
+        .into_iter()
+        .filter(|instruction| {
+            *instruction.program_id == MEMO_PROGRAM_ID
+                || *instruction.program_id == MEMO_V1_PROGRAM_ID
+        })
+        .filter_map(|instruction| String::from_utf8(instruction.data.to_vec()).ok())
+        .collect()
+}
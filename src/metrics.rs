@@ -0,0 +1,113 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::GapLog;
+
+/// Errors kept for the admin API.
+const RECENT_ERRORS: usize = 100;
+/// Upper bounds of the sink latency buckets, in seconds.
+pub const LATENCY_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Counters shared between the Geyser callbacks and the background writer.
+#[derive(Debug, Default)]
+pub struct PluginMetrics {
+    pub account_updates: AtomicU64,
+    pub transactions: AtomicU64,
+    pub slot_updates: AtomicU64,
+    pub blocks: AtomicU64,
+    /// Events that could not be handed to the background writer.
+    pub dropped_events: AtomicU64,
+    /// Account updates turned away by program quotas.
+    pub quota_dropped_events: AtomicU64,
+    /// Events dropped by the overflow policy of a full writer queue.
+    pub overflow_dropped_events: AtomicU64,
+    pub account_cache_hits: AtomicU64,
+    pub account_cache_misses: AtomicU64,
+    pub account_cache_entries: AtomicU64,
+    /// Estimated memory held by the account cache.
+    pub account_cache_bytes: AtomicU64,
+    /// Accounts replayed at startup written so far.
+    pub startup_accounts: AtomicU64,
+    pub startup_accounts_per_sec: AtomicU64,
+    recent_errors: Mutex<VecDeque<String>>,
+    /// Events handed to the sinks, by kind.
+    written: Mutex<BTreeMap<&'static str, u64>>,
+    sink_latency: Mutex<BTreeMap<&'static str, LatencyHistogram>>,
+    pub gaps: GapLog,
+}
+
+/// Cumulative counts of write latencies, Prometheus style.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    /// Writes that took at most the matching [`LATENCY_BUCKETS`] bound.
+    pub buckets: [u64; LATENCY_BUCKETS.len()],
+    pub count: u64,
+    /// In seconds.
+    pub sum: f64,
+}
+
+impl LatencyHistogram {
+    fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+impl PluginMetrics {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    /// Remembers one of the latest errors of a sink.
+    pub fn record_error(&self, error: String) {
+        let mut recent_errors = self.recent_errors.lock().unwrap();
+        if recent_errors.len() == RECENT_ERRORS {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back(error);
+    }
+
+    pub fn record_written(&self, kind: &'static str, events: u64) {
+        *self.written.lock().unwrap().entry(kind).or_default() += events;
+    }
+
+    pub fn written(&self) -> BTreeMap<&'static str, u64> {
+        self.written.lock().unwrap().clone()
+    }
+
+    /// Times one write, of one or many events, to `sink`.
+    pub fn record_sink_latency(&self, sink: &'static str, latency: Duration) {
+        self.sink_latency
+            .lock()
+            .unwrap()
+            .entry(sink)
+            .or_default()
+            .observe(latency);
+    }
+
+    pub fn sink_latency(&self) -> BTreeMap<&'static str, LatencyHistogram> {
+        self.sink_latency.lock().unwrap().clone()
+    }
+
+    /// Oldest first.
+    pub fn recent_errors(&self) -> Vec<String> {
+        self.recent_errors.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::Result as GeyserResult;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    config::parse_pubkey, pipeline::SlotInterval, AccTx, AccountUpdate, TokenAccount,
+    TransactionUpdate,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MintSupplyConfig {
+    pub mints: Vec<String>,
+    /// Aggregates are emitted once every this many slots.
+    #[serde(default = "default_interval_slots")]
+    pub interval_slots: u64,
+}
+
+pub(crate) fn default_interval_slots() -> u64 {
+    150
+}
+
+#[derive(Debug, Default)]
+struct MintSupply {
+    balances: HashMap<Pubkey, u64>,
+}
+
+/// Keeps the token balances of configured mints from token account updates
+/// and periodically summarizes them as [`AccTx::MintSupply`] events.
+#[derive(Debug)]
+pub struct MintSupplyAggregator {
+    mints: HashMap<Pubkey, MintSupply>,
+    interval: SlotInterval,
+}
+
+impl MintSupplyAggregator {
+    pub fn new(config: &MintSupplyConfig) -> GeyserResult<Self> {
+        let mints = config
+            .mints
+            .iter()
+            .map(|mint| Ok((parse_pubkey(mint)?, MintSupply::default())))
+            .collect::<GeyserResult<_>>()?;
+
+        Ok(MintSupplyAggregator {
+            mints,
+            interval: SlotInterval::new(config.interval_slots),
+        })
+    }
+
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let slot = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                data,
+                slot,
+                ..
+            }) => {
+                self.update(pubkey, owner, *lamports, data);
+                *slot
+            }
+            AccTx::Tx(TransactionUpdate { slot, .. }) => *slot,
+            _ => return Vec::new(),
+        };
+
+        if !self.interval.tick(slot) {
+            return Vec::new();
+        }
+
+        self.mints
+            .iter()
+            .map(|(mint, supply)| AccTx::MintSupply {
+                mint: *mint,
+                slot,
+                supply: supply
+                    .balances
+                    .values()
+                    .fold(0, |a, b| a.saturating_add(*b)),
+                holders: supply
+                    .balances
+                    .values()
+                    .filter(|amount| **amount > 0)
+                    .count() as u64,
+                token_accounts: supply.balances.len() as u64,
+            })
+            .collect()
+    }
+
+    fn update(&mut self, pubkey: &[u8], owner: &[u8], lamports: u64, data: &[u8]) {
+        let pubkey = match Pubkey::try_from(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return,
+        };
+
+        match TokenAccount::unpack(owner, data) {
+            Some(token_account) if lamports > 0 => {
+                if let Some(supply) = self.mints.get_mut(&token_account.mint) {
+                    supply.balances.insert(pubkey, token_account.amount);
+                }
+            }
+            // Closed or reinitialized accounts no longer count towards any
+            // mint they were previously seen with.
+            _ => {
+                for supply in self.mints.values_mut() {
+                    supply.balances.remove(&pubkey);
+                }
+            }
+        }
+    }
+}
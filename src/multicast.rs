@@ -0,0 +1,208 @@
+//! Fan-out of encoded events to a UDP multicast group.
+//!
+//! Every datagram starts with a 13 byte big-endian header: its sequence
+//! number (`u64`), its kind (`u8`, 0 for data and 1 for parity), and for
+//! data the index of the fragment and the number of fragments of the record
+//! it carries (two `u16`). Records larger than a datagram are split into
+//! fragments with consecutive sequence numbers.
+//!
+//! With `fec_group` set, a parity datagram follows every group of that many
+//! data datagrams. Its fragment fields hold the number of datagrams in the
+//! group, its sequence number that of the group's first datagram, and its
+//! payload the XOR of the group's datagrams, each as its payload length
+//! (`u16`) followed by the payload, so that one lost datagram per group can
+//! be rebuilt.
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::Arc,
+};
+
+use log::info;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::{Codec, CodecRegistry, EncodedEvent, PluginMetrics, Sink, SinkConfig};
+
+const HEADER_LEN: usize = 13;
+const DATA: u8 = 0;
+const PARITY: u8 = 1;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MulticastConfig {
+    /// Group address and port, e.g. `239.1.1.1:5000`.
+    pub group: SocketAddr,
+    /// Local address to send from, any when unset.
+    #[serde(default)]
+    pub bind_address: Option<Ipv4Addr>,
+    /// Router hops datagrams may cross; 1 keeps them on the LAN.
+    #[serde(default = "default_ttl")]
+    pub ttl: u32,
+    /// Largest datagram sent, header included.
+    #[serde(default = "default_max_datagram")]
+    pub max_datagram: usize,
+    /// Data datagrams covered by each parity datagram, no parity when unset.
+    #[serde(default)]
+    pub fec_group: Option<usize>,
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+fn default_ttl() -> u32 {
+    1
+}
+
+fn default_max_datagram() -> usize {
+    1_400
+}
+
+/// Sends every event it can encode to the multicast group. Send errors
+/// count as dropped events, as there is nobody to retry for.
+#[derive(Debug)]
+pub struct MulticastSink {
+    socket: UdpSocket,
+    group: SocketAddr,
+    codec: Arc<dyn Codec>,
+    max_payload: usize,
+    fec_group: usize,
+    sequence: u64,
+    parity: Parity,
+    metrics: Arc<PluginMetrics>,
+}
+
+#[derive(Debug, Default)]
+struct Parity {
+    first_sequence: u64,
+    datagrams: u16,
+    bytes: Vec<u8>,
+}
+
+impl MulticastSink {
+    pub fn start(
+        config: &MulticastConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let socket_error = |error: io::Error| {
+            GeyserPluginError::Custom(
+                format!("Failed to set up multicast to {}: {}", config.group, error).into(),
+            )
+        };
+        if !config.group.ip().is_multicast() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!("{} is not a multicast address", config.group),
+            });
+        }
+        if config.max_datagram <= HEADER_LEN + 2 {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!("Multicast max_datagram must exceed {}", HEADER_LEN + 2),
+            });
+        }
+
+        let bind_address = config.bind_address.unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let socket = UdpSocket::bind((bind_address, 0)).map_err(socket_error)?;
+        socket
+            .set_multicast_ttl_v4(config.ttl)
+            .map_err(socket_error)?;
+        info!("Publishing events to multicast group {}", config.group);
+
+        Ok(MulticastSink {
+            socket,
+            group: config.group,
+            codec: codecs.build(&config.sink, "protobuf")?,
+            // Parity payloads carry a length before each payload.
+            max_payload: config.max_datagram - HEADER_LEN - 2,
+            fec_group: config.fec_group.unwrap_or(0).min(u16::MAX as usize),
+            sequence: 0,
+            parity: Parity::default(),
+            metrics,
+        })
+    }
+
+    fn send(&mut self, kind: u8, sequence: u64, fragment: u16, fragments: u16, payload: &[u8]) {
+        let mut datagram = Vec::with_capacity(HEADER_LEN + payload.len());
+        datagram.extend_from_slice(&sequence.to_be_bytes());
+        datagram.push(kind);
+        datagram.extend_from_slice(&fragment.to_be_bytes());
+        datagram.extend_from_slice(&fragments.to_be_bytes());
+        datagram.extend_from_slice(payload);
+        if let Err(error) = self.socket.send_to(&datagram, self.group) {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics
+                .record_error(format!("Failed to send multicast datagram: {}", error));
+        }
+    }
+
+    fn send_data(&mut self, fragment: u16, fragments: u16, payload: &[u8]) {
+        let sequence = self.sequence;
+        self.sequence += 1;
+        self.send(DATA, sequence, fragment, fragments, payload);
+        if self.fec_group == 0 {
+            return;
+        }
+
+        let parity = &mut self.parity;
+        if parity.datagrams == 0 {
+            parity.first_sequence = sequence;
+        }
+        parity.datagrams += 1;
+        let protected = (payload.len() as u16)
+            .to_be_bytes()
+            .into_iter()
+            .chain(payload.iter().copied());
+        if parity.bytes.len() < 2 + payload.len() {
+            parity.bytes.resize(2 + payload.len(), 0);
+        }
+        for (parity, byte) in parity.bytes.iter_mut().zip(protected) {
+            *parity ^= byte;
+        }
+
+        if parity.datagrams as usize >= self.fec_group {
+            let parity = std::mem::take(&mut self.parity);
+            self.send(
+                PARITY,
+                parity.first_sequence,
+                parity.datagrams,
+                parity.datagrams,
+                &parity.bytes,
+            );
+        }
+    }
+}
+
+impl Sink for MulticastSink {
+    fn name(&self) -> &'static str {
+        "multicast"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let record = match event.encode(&self.codec) {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        let fragments = record.len().div_ceil(self.max_payload).max(1);
+        if fragments > u16::MAX as usize {
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            return Ok(());
+        }
+
+        if record.is_empty() {
+            self.send_data(0, 1, &[]);
+        }
+        for (fragment, payload) in record.chunks(self.max_payload).enumerate() {
+            self.send_data(fragment as u16, fragments as u16, payload);
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "group": self.group.to_string(),
+            "sequence": self.sequence,
+        })
+    }
+}
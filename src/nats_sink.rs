@@ -0,0 +1,318 @@
+//! Encoded events published to NATS subjects, either fire and forget or
+//! acknowledged by a JetStream stream.
+
+use std::{error::Error, io, path::PathBuf, sync::Arc, time::Duration};
+
+use async_nats::{jetstream, Client, ConnectOptions};
+use log::{error, info, warn};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+use tokio::{
+    runtime::Runtime,
+    sync::mpsc::{self, Receiver, Sender},
+    task::JoinHandle,
+};
+
+use crate::{
+    AccTx, AccountUpdate, Codec, CodecRegistry, EncodedEvent, PluginMetrics, Sink, SinkConfig,
+    Undelivered,
+};
+
+const MAX_ATTEMPTS: u32 = 3;
+/// How often core publishes are flushed to the server while idle.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NatsConfig {
+    /// e.g. `nats://localhost:4222`.
+    pub url: String,
+    /// Subject of each event, where `{kind}`, `{slot}`, `{owner}` and
+    /// `{pubkey}` are replaced, e.g. `geyser.accounts.{owner}`. Events
+    /// without an owner or pubkey get `_` instead.
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    /// Credentials file, for servers that require one.
+    #[serde(default)]
+    pub credentials: Option<PathBuf>,
+    /// Publishes through JetStream, waiting for each event to be stored,
+    /// when set.
+    #[serde(default)]
+    pub jetstream: Option<JetStreamConfig>,
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JetStreamConfig {
+    pub stream: String,
+    /// Subjects the stream is created with when it does not exist yet.
+    #[serde(default = "default_stream_subjects")]
+    pub subjects: Vec<String>,
+}
+
+fn default_subject() -> String {
+    "geyser.{kind}".to_string()
+}
+
+fn default_stream_subjects() -> Vec<String> {
+    vec!["geyser.>".to_string()]
+}
+
+fn default_queue_capacity() -> usize {
+    100_000
+}
+
+struct NatsRecord {
+    subject: String,
+    payload: Arc<[u8]>,
+    slot: u64,
+}
+
+/// Publishes from a task on a runtime of its own, as the NATS client needs
+/// tokio.
+#[derive(Debug)]
+pub struct NatsSink {
+    codec: Arc<dyn Codec>,
+    subject: String,
+    sender: Option<Sender<NatsRecord>>,
+    task: Option<JoinHandle<()>>,
+    runtime: Runtime,
+    undelivered: Undelivered,
+}
+
+impl NatsSink {
+    pub fn start(
+        config: &NatsConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let nats_error = |error: Box<dyn Error>| {
+            GeyserPluginError::Custom(
+                format!("Failed to set up NATS at {}: {}", config.url, error).into(),
+            )
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("fusionNats")
+            .enable_all()
+            .build()
+            .map_err(|error| nats_error(error.into()))?;
+        let (client, jetstream) = runtime.block_on(connect(config)).map_err(nats_error)?;
+
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let undelivered = Undelivered::default();
+        let worker = NatsWorker {
+            client,
+            jetstream,
+            metrics,
+            undelivered: undelivered.clone(),
+        };
+        let task = runtime.spawn(worker.run(receiver));
+        info!(
+            "Publishing events to NATS at {} as {}",
+            config.url, config.subject
+        );
+
+        Ok(NatsSink {
+            codec: codecs.build(&config.sink, "protobuf")?,
+            subject: config.subject.clone(),
+            sender: Some(sender),
+            task: Some(task),
+            runtime,
+            undelivered,
+        })
+    }
+
+    /// The subject template filled in for `event`.
+    fn subject(&self, event: &AccTx) -> String {
+        let mut subject = self.subject.replace("{kind}", event.kind());
+        if subject.contains("{slot}") {
+            subject = subject.replace("{slot}", &event.slot().to_string());
+        }
+        if subject.contains("{owner}") {
+            let owner = match event {
+                AccTx::Acc(AccountUpdate { owner, .. }) | AccTx::Lifecycle { owner, .. } => {
+                    base58(owner)
+                }
+                _ => None,
+            };
+            subject = subject.replace("{owner}", owner.as_deref().unwrap_or("_"));
+        }
+        if subject.contains("{pubkey}") {
+            let pubkey = match event {
+                AccTx::Acc(AccountUpdate { pubkey, .. }) | AccTx::Lifecycle { pubkey, .. } => {
+                    base58(pubkey)
+                }
+                _ => None,
+            };
+            subject = subject.replace("{pubkey}", pubkey.as_deref().unwrap_or("_"));
+        }
+        subject
+    }
+
+    fn stop(&mut self) {
+        // Closing the queue makes the task publish what is left and exit.
+        self.sender = None;
+        if let Some(task) = self.task.take() {
+            let _ = self.runtime.block_on(task);
+        }
+    }
+}
+
+/// Connects to the server and creates the JetStream stream when missing.
+async fn connect(
+    config: &NatsConfig,
+) -> Result<(Client, Option<jetstream::Context>), Box<dyn Error>> {
+    let mut options = ConnectOptions::new().name("fusion-engine-geyser");
+    if let Some(credentials) = &config.credentials {
+        options = options.credentials_file(credentials).await?;
+    }
+    let client = options.connect(&config.url).await?;
+    let jetstream = match &config.jetstream {
+        Some(jetstream_config) => {
+            let jetstream = jetstream::new(client.clone());
+            if jetstream
+                .get_stream(&jetstream_config.stream)
+                .await
+                .is_err()
+            {
+                jetstream
+                    .create_stream(jetstream::stream::Config {
+                        name: jetstream_config.stream.clone(),
+                        subjects: jetstream_config.subjects.clone(),
+                        ..Default::default()
+                    })
+                    .await?;
+                info!("Created JetStream stream {}", jetstream_config.stream);
+            }
+            Some(jetstream)
+        }
+        None => None,
+    };
+    Ok((client, jetstream))
+}
+
+fn base58(bytes: &[u8]) -> Option<String> {
+    Pubkey::try_from(bytes)
+        .ok()
+        .map(|pubkey| pubkey.to_string())
+}
+
+impl Sink for NatsSink {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    /// Waits while the queue is full.
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let (sender, payload) = match (&self.sender, event.encode(&self.codec)) {
+            (Some(sender), Some(payload)) => (sender, payload),
+            _ => return Ok(()),
+        };
+        self.undelivered.add(event.event.slot());
+        let _ = sender.blocking_send(NatsRecord {
+            subject: self.subject(event.event),
+            payload,
+            slot: event.event.slot(),
+        });
+        Ok(())
+    }
+
+    fn pending(&self) -> u64 {
+        self.undelivered.get()
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.undelivered.oldest()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl Drop for NatsSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct NatsWorker {
+    client: Client,
+    jetstream: Option<jetstream::Context>,
+    metrics: Arc<PluginMetrics>,
+    undelivered: Undelivered,
+}
+
+impl NatsWorker {
+    async fn run(self, mut receiver: Receiver<NatsRecord>) {
+        loop {
+            match tokio::time::timeout(FLUSH_INTERVAL, receiver.recv()).await {
+                Ok(Some(record)) => {
+                    self.publish(&record).await;
+                    self.undelivered.done([record.slot]);
+                }
+                Ok(None) => break,
+                Err(_) => self.flush().await,
+            }
+        }
+        self.flush().await;
+    }
+
+    /// Retries stores JetStream did not acknowledge; core publishes only
+    /// fail once the client is closed.
+    async fn publish(&self, record: &NatsRecord) {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let payload = record.payload.to_vec();
+            let published: Result<(), Box<dyn Error + Send + Sync>> = match &self.jetstream {
+                Some(jetstream) => match jetstream
+                    .publish(record.subject.clone(), payload.into())
+                    .await
+                {
+                    Ok(ack) => ack.await.map(|_| ()).map_err(Into::into),
+                    Err(error) => Err(error.into()),
+                },
+                None => self
+                    .client
+                    .publish(record.subject.clone(), payload.into())
+                    .await
+                    .map_err(Into::into),
+            };
+            let error = match published {
+                Ok(()) => return,
+                Err(error) => error,
+            };
+            if attempt >= MAX_ATTEMPTS || self.jetstream.is_none() {
+                let error = format!(
+                    "Dropping event of slot {} after NATS error on {}: {}",
+                    record.slot, record.subject, error
+                );
+                error!("{}", error);
+                self.metrics.record_error(error);
+                PluginMetrics::increment(&self.metrics.dropped_events);
+                self.metrics
+                    .gaps
+                    .record("nats", record.slot, record.slot, 1);
+                return;
+            }
+            warn!("NATS publish failed on attempt {}: {}", attempt, error);
+            tokio::time::sleep(Duration::from_millis(100 << attempt)).await;
+        }
+    }
+
+    async fn flush(&self) {
+        if self.jetstream.is_none() {
+            if let Err(error) = self.client.flush().await {
+                warn!("Failed to flush NATS connection: {}", error);
+            }
+        }
+    }
+}
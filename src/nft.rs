@@ -0,0 +1,96 @@
+use solana_sdk::{message::AccountKeys, pubkey::Pubkey};
+use solana_transaction_status::TransactionTokenBalance;
+
+use crate::{
+    instructions::resolve_instructions, token::is_token_program, AccTx, TransactionUpdate,
+};
+
+const TRANSFER: u8 = 3;
+const TRANSFER_CHECKED: u8 = 12;
+
+/// Emits an [`AccTx::NftTransfer`] for every successful SPL Token transfer
+/// of a single token of a zero-decimals mint.
+#[derive(Debug, Default)]
+pub struct NftTransferDetector;
+
+impl NftTransferDetector {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let (slot, signature, transaction, meta) = match event {
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => (*slot, signature, transaction, transaction_status_meta),
+            _ => return Vec::new(),
+        };
+        if meta.status.is_err() {
+            return Vec::new();
+        }
+
+        let account_keys = transaction.message().account_keys();
+
+        let mut events = Vec::new();
+        for instruction in resolve_instructions(transaction, meta) {
+            if !is_token_program(instruction.program_id.as_ref()) {
+                continue;
+            }
+
+            let (source, destination, decimals) = match instruction.data.first() {
+                Some(&TRANSFER) => (instruction.account(0), instruction.account(1), None),
+                Some(&TRANSFER_CHECKED) => (
+                    instruction.account(0),
+                    instruction.account(2),
+                    instruction.data.get(9).copied(),
+                ),
+                _ => continue,
+            };
+            let amount = instruction
+                .data
+                .get(1..9)
+                .and_then(|amount| amount.try_into().ok())
+                .map(u64::from_le_bytes);
+            let (source, destination) = match (source, destination, amount) {
+                (Some(source), Some(destination), Some(1)) => (source, destination),
+                _ => continue,
+            };
+
+            let source_balance = token_balance(&meta.pre_token_balances, &account_keys, source);
+            let destination_balance =
+                token_balance(&meta.post_token_balances, &account_keys, destination);
+            let balance = match source_balance.or(destination_balance) {
+                Some(balance) => balance,
+                None => continue,
+            };
+            if decimals.unwrap_or(balance.ui_token_amount.decimals) != 0 {
+                continue;
+            }
+            let mint = match balance.mint.parse() {
+                Ok(mint) => mint,
+                Err(_) => continue,
+            };
+
+            events.push(AccTx::NftTransfer {
+                mint,
+                from: source_balance.and_then(|balance| balance.owner.parse().ok()),
+                to: destination_balance.and_then(|balance| balance.owner.parse().ok()),
+                slot,
+                signature: *signature,
+            });
+        }
+
+        events
+    }
+}
+
+fn token_balance<'a>(
+    balances: &'a Option<Vec<TransactionTokenBalance>>,
+    account_keys: &AccountKeys,
+    account: &Pubkey,
+) -> Option<&'a TransactionTokenBalance> {
+    balances
+        .iter()
+        .flatten()
+        .find(|balance| account_keys.get(balance.account_index as usize) == Some(account))
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::Result as GeyserResult;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    config::parse_pubkey, mint_supply::default_interval_slots, pipeline::SlotInterval, AccTx,
+    AccountUpdate, TransactionUpdate,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnerLamportsConfig {
+    pub owners: Vec<String>,
+    /// Aggregates are emitted once every this many slots.
+    #[serde(default = "default_interval_slots")]
+    pub interval_slots: u64,
+}
+
+/// Keeps the lamports of every account owned by a configured program and
+/// periodically summarizes them per owner as [`AccTx::OwnerLamports`].
+#[derive(Debug)]
+pub struct OwnerLamportsAggregator {
+    owners: HashMap<Pubkey, HashMap<Pubkey, u64>>,
+    interval: SlotInterval,
+}
+
+impl OwnerLamportsAggregator {
+    pub fn new(config: &OwnerLamportsConfig) -> GeyserResult<Self> {
+        let owners = config
+            .owners
+            .iter()
+            .map(|owner| Ok((parse_pubkey(owner)?, HashMap::default())))
+            .collect::<GeyserResult<_>>()?;
+
+        Ok(OwnerLamportsAggregator {
+            owners,
+            interval: SlotInterval::new(config.interval_slots),
+        })
+    }
+
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let slot = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                slot,
+                ..
+            }) => {
+                self.update(pubkey, owner, *lamports);
+                *slot
+            }
+            AccTx::Tx(TransactionUpdate { slot, .. }) => *slot,
+            _ => return Vec::new(),
+        };
+
+        if !self.interval.tick(slot) {
+            return Vec::new();
+        }
+
+        self.owners
+            .iter()
+            .map(|(owner, accounts)| AccTx::OwnerLamports {
+                owner: *owner,
+                slot,
+                lamports: accounts.values().fold(0, |a, b| a.saturating_add(*b)),
+                accounts: accounts.len() as u64,
+            })
+            .collect()
+    }
+
+    fn update(&mut self, pubkey: &[u8], owner: &[u8], lamports: u64) {
+        let (pubkey, owner) = match (Pubkey::try_from(pubkey), Pubkey::try_from(owner)) {
+            (Ok(pubkey), Ok(owner)) => (pubkey, owner),
+            _ => return,
+        };
+
+        // Reassigned accounts move from their previous owner's total.
+        for (configured_owner, accounts) in self.owners.iter_mut() {
+            if *configured_owner == owner && lamports > 0 {
+                accounts.insert(pubkey, lamports);
+            } else {
+                accounts.remove(&pubkey);
+            }
+        }
+    }
+}
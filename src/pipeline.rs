@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::{Result as GeyserResult, SlotStatus};
+
+use crate::{
+    decode_lookup_table, extract_memos, extract_sol_transfers, AccTx, AccountCache, AccountUpdate,
+    BubblegumParser, ConfirmationLatencyMonitor, EpochTracker, ForkStatsMonitor, Labels,
+    LifecycleTracker, MintSupplyAggregator, NftTransferDetector, NonceTracker,
+    OwnerLamportsAggregator, PluginConfig, PluginMetrics, ProgramUpgradeMonitor, RollbackTracker,
+    SlotClock, SlotStatsAggregator, SlotUpdate, ThroughputMonitor, TokenOwnerIndex,
+    TransactionUpdate, TxEffectsJoiner, TxErrorClass,
+};
+
+/// The stages every event goes through in the background writer before it
+/// reaches the outputs. Stateful stages may derive additional events.
+#[derive(Debug)]
+pub struct Pipeline {
+    account_cache: Option<AccountCache>,
+    lifecycle: Option<LifecycleTracker>,
+    mint_supply: Option<MintSupplyAggregator>,
+    owner_lamports: Option<OwnerLamportsAggregator>,
+    slot_stats: Option<SlotStatsAggregator>,
+    epochs: Option<EpochTracker>,
+    fork_stats: Option<ForkStatsMonitor>,
+    rollbacks: Option<RollbackTracker>,
+    confirmation_latency: Option<ConfirmationLatencyMonitor>,
+    throughput: Option<ThroughputMonitor>,
+    nft_transfers: Option<NftTransferDetector>,
+    compressed_nfts: Option<BubblegumParser>,
+    nonces: Option<NonceTracker>,
+    token_index: Option<Arc<TokenOwnerIndex>>,
+    decode_lookup_tables: bool,
+    program_upgrades: Option<ProgramUpgradeMonitor>,
+    extract_memos: bool,
+    extract_sol_transfers: bool,
+    classify_errors: bool,
+    tx_effects: Option<TxEffectsJoiner>,
+    labels: Labels,
+    slot_clock: Option<Arc<SlotClock>>,
+    latest_slot: u64,
+    latest_root: u64,
+}
+
+impl Pipeline {
+    pub fn new(
+        config: &PluginConfig,
+        metrics: Arc<PluginMetrics>,
+        slot_clock: Option<Arc<SlotClock>>,
+        token_index: Option<Arc<TokenOwnerIndex>>,
+    ) -> GeyserResult<Self> {
+        let account_cache = match (&config.account_cache, config.lifecycle_events) {
+            (Some(account_cache), _) => Some(AccountCache::new(account_cache, metrics.clone())),
+            (None, true) => Some(AccountCache::new(&Default::default(), metrics.clone())),
+            (None, false) => None,
+        };
+
+        Ok(Pipeline {
+            account_cache,
+            lifecycle: config.lifecycle_events.then(LifecycleTracker::default),
+            mint_supply: config
+                .mint_supply
+                .as_ref()
+                .map(MintSupplyAggregator::new)
+                .transpose()?,
+            owner_lamports: config
+                .owner_lamports
+                .as_ref()
+                .map(OwnerLamportsAggregator::new)
+                .transpose()?,
+            slot_stats: config.slot_stats.as_ref().map(SlotStatsAggregator::new),
+            epochs: config.epoch_events.as_ref().map(EpochTracker::new),
+            fork_stats: config.fork_stats.as_ref().map(ForkStatsMonitor::new),
+            rollbacks: config.rollback_events.then(RollbackTracker::default),
+            confirmation_latency: config
+                .confirmation_latency
+                .then(ConfirmationLatencyMonitor::default),
+            throughput: config
+                .throughput
+                .as_ref()
+                .map(|throughput| ThroughputMonitor::new(throughput, metrics)),
+            nft_transfers: config.nft_transfers.then(NftTransferDetector::default),
+            compressed_nfts: config.compressed_nfts.then(BubblegumParser::default),
+            nonces: config.nonce_events.then(NonceTracker::default),
+            token_index,
+            decode_lookup_tables: config.decode_lookup_tables,
+            program_upgrades: config.program_upgrades.then(ProgramUpgradeMonitor::default),
+            extract_memos: config.extract_memos,
+            extract_sol_transfers: config.extract_sol_transfers,
+            classify_errors: config.classify_errors,
+            tx_effects: config.tx_effects.then(TxEffectsJoiner::default),
+            labels: match &config.labels_file {
+                Some(labels_file) => Labels::load(labels_file)?,
+                None => Labels::default(),
+            },
+            slot_clock,
+            latest_slot: 0,
+            latest_root: 0,
+        })
+    }
+
+    pub fn process(&mut self, mut event: AccTx) -> Vec<AccTx> {
+        self.latest_slot = self.latest_slot.max(event.slot());
+        if let AccTx::Slot(SlotUpdate {
+            slot,
+            status: SlotStatus::Rooted,
+            ..
+        }) = event
+        {
+            self.latest_root = self.latest_root.max(slot);
+        }
+        self.enrich(&mut event);
+
+        // Stages decoding account data would misread a missing payload.
+        let full = !event.is_metadata_only();
+        let mut events = vec![];
+        let cached = match (full, &mut self.account_cache) {
+            (true, Some(account_cache)) => account_cache.observe(&event),
+            _ => None,
+        };
+        if let (Some(cached), Some(lifecycle)) = (&cached, &mut self.lifecycle) {
+            events.extend(lifecycle.observe(&event, cached));
+        }
+        if let (true, Some(mint_supply)) = (full, &mut self.mint_supply) {
+            events.extend(mint_supply.observe(&event));
+        }
+        if let Some(owner_lamports) = &mut self.owner_lamports {
+            events.extend(owner_lamports.observe(&event));
+        }
+        if let Some(slot_stats) = &mut self.slot_stats {
+            events.extend(slot_stats.observe(&event));
+        }
+        if let Some(epochs) = &mut self.epochs {
+            events.extend(epochs.observe(&event));
+        }
+        if let Some(fork_stats) = &mut self.fork_stats {
+            events.extend(fork_stats.observe(&event));
+        }
+        if let Some(rollbacks) = &mut self.rollbacks {
+            events.extend(rollbacks.observe(&event));
+        }
+        if let Some(confirmation_latency) = &mut self.confirmation_latency {
+            events.extend(confirmation_latency.observe(&event));
+        }
+        if let Some(nft_transfers) = &mut self.nft_transfers {
+            events.extend(nft_transfers.observe(&event));
+        }
+        if let Some(compressed_nfts) = &mut self.compressed_nfts {
+            events.extend(compressed_nfts.observe(&event));
+        }
+        if let (true, Some(nonces)) = (full, &mut self.nonces) {
+            events.extend(nonces.observe(&event));
+        }
+        if let (true, Some(token_index)) = (full, &self.token_index) {
+            events.extend(token_index.observe(&event));
+        }
+        if full && self.decode_lookup_tables {
+            events.extend(decode_lookup_table(&event));
+        }
+        if let (true, Some(program_upgrades)) = (full, &mut self.program_upgrades) {
+            events.extend(program_upgrades.observe(&event));
+        }
+        events.insert(0, event);
+
+        // Joins are built from finished events, so their parts are already
+        // redacted and labeled.
+        let mut events = self.finish(events);
+        if let Some(tx_effects) = &mut self.tx_effects {
+            let joined: Vec<AccTx> = events
+                .iter()
+                .flat_map(|event| tx_effects.observe(event))
+                .collect();
+            events.extend(joined);
+        }
+
+        events
+    }
+
+    /// Highest slot of any event processed so far.
+    pub fn latest_slot(&self) -> u64 {
+        self.latest_slot
+    }
+
+    /// Highest rooted slot seen so far.
+    pub fn latest_root(&self) -> u64 {
+        self.latest_root
+    }
+
+    fn enrich(&self, event: &mut AccTx) {
+        if let Some(slot_clock) = &self.slot_clock {
+            if let AccTx::Acc(AccountUpdate {
+                slot,
+                unix_timestamp,
+                ..
+            })
+            | AccTx::Tx(TransactionUpdate {
+                slot,
+                unix_timestamp,
+                ..
+            }) = event
+            {
+                *unix_timestamp = slot_clock.estimate(*slot);
+            }
+        }
+
+        if let AccTx::Tx(TransactionUpdate {
+            transaction,
+            transaction_status_meta,
+            memos,
+            sol_transfers,
+            error_class,
+            ..
+        }) = event
+        {
+            if self.extract_memos {
+                *memos = extract_memos(transaction, transaction_status_meta);
+            }
+            if self.extract_sol_transfers {
+                *sol_transfers = extract_sol_transfers(transaction, transaction_status_meta);
+            }
+            if self.classify_errors {
+                *error_class = transaction_status_meta
+                    .status
+                    .as_ref()
+                    .err()
+                    .map(TxErrorClass::of);
+            }
+        }
+    }
+
+    /// Runs the time-driven stages, called once a second by the writer.
+    pub fn tick(&mut self) -> Vec<AccTx> {
+        if let Some(account_cache) = &mut self.account_cache {
+            account_cache.expire();
+        }
+        let mut events = vec![];
+        if let Some(throughput) = &mut self.throughput {
+            events.extend(throughput.tick(self.latest_slot));
+        }
+
+        self.finish(events)
+    }
+
+    fn finish(&mut self, mut events: Vec<AccTx>) -> Vec<AccTx> {
+        for event in &mut events {
+            self.labels.attach(event);
+        }
+
+        events
+    }
+}
+
+/// Fires at most once every `interval_slots`, measured from the first slot
+/// it is ticked with.
+#[derive(Debug)]
+pub(crate) struct SlotInterval {
+    interval_slots: u64,
+    last_slot: Option<u64>,
+}
+
+impl SlotInterval {
+    pub(crate) fn new(interval_slots: u64) -> Self {
+        SlotInterval {
+            interval_slots: interval_slots.max(1),
+            last_slot: None,
+        }
+    }
+
+    pub(crate) fn tick(&mut self, slot: u64) -> bool {
+        let last_slot = *self.last_slot.get_or_insert(slot);
+        if slot < last_slot.saturating_add(self.interval_slots) {
+            return false;
+        }
+
+        self.last_slot = Some(slot);
+        true
+    }
+}
@@ -0,0 +1,623 @@
+//! Accounts, transactions and slots written to PostgreSQL tables, which are
+//! created and migrated on load.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use postgres::{Client, NoTls, Statement};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::{
+    AccTx, AccountUpdate, EncodedEvent, FailedEvents, PluginMetrics, Sink, SinkConfig, Undelivered,
+};
+
+/// Schema changes in order; the version of a database is the number of
+/// them applied, kept in `fusion_schema_version`.
+const MIGRATIONS: [&str; 1] = ["
+    CREATE TABLE account (
+        pubkey BYTEA PRIMARY KEY,
+        owner BYTEA NOT NULL,
+        lamports BIGINT NOT NULL,
+        executable BOOL NOT NULL,
+        rent_epoch BIGINT NOT NULL,
+        data BYTEA NOT NULL,
+        data_hash BYTEA,
+        write_version BIGINT NOT NULL,
+        slot BIGINT NOT NULL,
+        txn_signature BYTEA,
+        updated_on TIMESTAMP NOT NULL DEFAULT now()
+    );
+    CREATE INDEX account_owner ON account (owner);
+    CREATE TABLE transaction (
+        signature BYTEA NOT NULL,
+        slot BIGINT NOT NULL,
+        is_vote BOOL NOT NULL,
+        transaction_index BIGINT,
+        fee_payer BYTEA NOT NULL,
+        fee BIGINT NOT NULL,
+        err TEXT,
+        compute_units_consumed BIGINT,
+        log_messages TEXT[] NOT NULL,
+        message BYTEA NOT NULL,
+        PRIMARY KEY (slot, signature)
+    );
+    CREATE INDEX transaction_signature ON transaction (signature);
+    CREATE TABLE slot (
+        slot BIGINT PRIMARY KEY,
+        parent BIGINT,
+        status TEXT NOT NULL,
+        updated_on TIMESTAMP NOT NULL DEFAULT now()
+    );
+"];
+
+/// Accounts are only replaced by a later slot or write version.
+const UPSERT_ACCOUNT: &str = "
+    INSERT INTO account (pubkey, owner, lamports, executable, rent_epoch, data, data_hash,
+        write_version, slot, txn_signature)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+    ON CONFLICT (pubkey) DO UPDATE SET
+        owner = excluded.owner, lamports = excluded.lamports,
+        executable = excluded.executable, rent_epoch = excluded.rent_epoch,
+        data = excluded.data, data_hash = excluded.data_hash,
+        write_version = excluded.write_version, slot = excluded.slot,
+        txn_signature = excluded.txn_signature, updated_on = now()
+    WHERE (account.slot, account.write_version) < (excluded.slot, excluded.write_version)";
+
+/// Transactions replayed after a restart are already there.
+const INSERT_TRANSACTION: &str = "
+    INSERT INTO transaction (signature, slot, is_vote, transaction_index, fee_payer, fee, err,
+        compute_units_consumed, log_messages, message)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+    ON CONFLICT DO NOTHING";
+
+const UPSERT_SLOT: &str = "
+    INSERT INTO slot (slot, parent, status) VALUES ($1, $2, $3)
+    ON CONFLICT (slot) DO UPDATE SET
+        parent = coalesce(excluded.parent, slot.parent), status = excluded.status,
+        updated_on = now()";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostgresConfig {
+    /// e.g. `host=localhost user=solana dbname=solana`.
+    pub connection_string: String,
+    /// Connections, each written by a thread of its own. Accounts and
+    /// transactions are spread over them by pubkey and signature, so the
+    /// updates of one account stay in order.
+    #[serde(default = "default_connections")]
+    pub connections: usize,
+    /// Rows written in one database transaction.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Longest time rows wait before a batch is written.
+    #[serde(default = "default_batch_ms")]
+    pub batch_ms: u64,
+    /// Events each connection may fall behind by before the writer waits.
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Attempts at writing a batch, reconnecting in between, before it is
+    /// handed to the sink's `on_error` policy.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled on every further one.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_max_retry_backoff_ms")]
+    pub max_retry_backoff_ms: u64,
+    /// Encrypts the connections, which are in cleartext when unset.
+    #[serde(default)]
+    pub tls: Option<PostgresTlsConfig>,
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PostgresTlsConfig {
+    /// PEM certificates the server's is verified against, the Mozilla
+    /// roots when unset.
+    #[serde(default)]
+    pub ca_file: Option<PathBuf>,
+}
+
+fn default_connections() -> usize {
+    4
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+fn default_batch_ms() -> u64 {
+    100
+}
+
+fn default_queue_capacity() -> usize {
+    100_000
+}
+
+fn default_max_attempts() -> u32 {
+    10
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_retry_backoff_ms() -> u64 {
+    5_000
+}
+
+/// Writes events to PostgreSQL from a pool of connection threads.
+#[derive(Debug)]
+pub struct PostgresSink {
+    senders: Vec<SyncSender<AccTx>>,
+    threads: Vec<JoinHandle<()>>,
+    undelivered: Undelivered,
+    /// Batches the threads gave up on, with the last error, reported on
+    /// the next heartbeat.
+    failed: Receiver<(Vec<AccTx>, String)>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl PostgresSink {
+    pub fn start(config: &PostgresConfig, metrics: Arc<PluginMetrics>) -> GeyserResult<Self> {
+        let connector = Connector::new(config)?;
+        let mut client = connector.connect().map_err(|error| {
+            GeyserPluginError::Custom(format!("Failed to connect to PostgreSQL: {}", error).into())
+        })?;
+        migrate(&mut client)?;
+        let mut migrated = Some(client);
+
+        let mut senders = Vec::new();
+        let mut threads = Vec::new();
+        let undelivered = Undelivered::default();
+        let (failed_sender, failed) = mpsc::channel();
+        for number in 0..config.connections.max(1) {
+            let client = match migrated.take() {
+                Some(client) => client,
+                None => connector.connect().map_err(|error| {
+                    GeyserPluginError::Custom(
+                        format!("Failed to connect to PostgreSQL: {}", error).into(),
+                    )
+                })?,
+            };
+            let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+            let worker = PostgresWorker::new(
+                client,
+                connector.clone(),
+                config,
+                failed_sender.clone(),
+                metrics.clone(),
+                undelivered.clone(),
+            )?;
+            let thread = thread::Builder::new()
+                .name(format!("fusionPostgres{}", number))
+                .spawn(move || worker.run(receiver))
+                .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+            senders.push(sender);
+            threads.push(thread);
+        }
+        info!(
+            "Writing events to PostgreSQL over {} connections",
+            senders.len()
+        );
+
+        Ok(PostgresSink {
+            senders,
+            threads,
+            undelivered,
+            failed,
+            metrics,
+        })
+    }
+
+    fn stop(&mut self) {
+        // Closing the queues makes the threads write what is left and exit.
+        self.senders.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        // Nothing retries them any more.
+        for (batch, error) in self.failed.try_iter() {
+            self.undelivered.done(batch.iter().map(AccTx::slot));
+            dropped(&self.metrics, &batch, &error);
+        }
+    }
+
+    /// The connection that writes `event`, `None` for events without a
+    /// table.
+    fn route(&self, event: &AccTx) -> Option<&SyncSender<AccTx>> {
+        let mut hasher = DefaultHasher::new();
+        match event {
+            AccTx::Acc(AccountUpdate { pubkey, .. }) => pubkey.hash(&mut hasher),
+            AccTx::Tx(transaction) => transaction.signature.hash(&mut hasher),
+            // Slot statuses of one slot must not race each other.
+            AccTx::Slot(slot) => slot.slot.hash(&mut hasher),
+            _ => return None,
+        }
+        self.senders
+            .get(hasher.finish() as usize % self.senders.len())
+    }
+}
+
+impl Sink for PostgresSink {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    /// Waits while the connection's queue is full.
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        if let Some(sender) = self.route(event.event) {
+            let slot = event.event.slot();
+            self.undelivered.add(slot);
+            if sender.send(event.event.clone()).is_err() {
+                self.undelivered.done([slot]);
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "PostgreSQL connection thread stopped",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the batches the threads gave up on to the error policy.
+    fn heartbeat(&mut self, _slot: u64) -> io::Result<()> {
+        let mut events = Vec::new();
+        let mut last_error = None;
+        for (batch, error) in self.failed.try_iter() {
+            self.undelivered.done(batch.iter().map(AccTx::slot));
+            events.extend(batch);
+            last_error = Some(error);
+        }
+        match last_error {
+            Some(error) => Err(FailedEvents::wrap(events, error)),
+            None => Ok(()),
+        }
+    }
+
+    fn pending(&self) -> u64 {
+        self.undelivered.get()
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.undelivered.oldest()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl Drop for PostgresSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Opens connections, over TLS when configured.
+#[derive(Clone)]
+struct Connector {
+    connection_string: String,
+    tls: Option<MakeRustlsConnect>,
+}
+
+impl Connector {
+    fn new(config: &PostgresConfig) -> GeyserResult<Self> {
+        let tls = match &config.tls {
+            Some(tls) => Some(MakeRustlsConnect::new(tls_config(tls)?)),
+            None => None,
+        };
+        Ok(Connector {
+            connection_string: config.connection_string.clone(),
+            tls,
+        })
+    }
+
+    fn connect(&self) -> Result<Client, postgres::Error> {
+        match &self.tls {
+            Some(tls) => Client::connect(&self.connection_string, tls.clone()),
+            None => Client::connect(&self.connection_string, NoTls),
+        }
+    }
+}
+
+fn tls_config(config: &PostgresTlsConfig) -> GeyserResult<rustls::ClientConfig> {
+    let invalid = |error: String| GeyserPluginError::ConfigFileReadError {
+        msg: format!("Invalid PostgreSQL TLS configuration: {}", error),
+    };
+    let mut roots = rustls::RootCertStore::empty();
+    match &config.ca_file {
+        Some(ca_file) => {
+            let pem =
+                fs::read(ca_file).map_err(|error| invalid(format!("{:?}: {}", ca_file, error)))?;
+            let certs = rustls_pemfile::certs(&mut pem.as_slice())
+                .map_err(|error| invalid(format!("{:?}: {}", ca_file, error)))?;
+            for cert in certs {
+                roots
+                    .add(&rustls::Certificate(cert))
+                    .map_err(|error| invalid(format!("{:?}: {}", ca_file, error)))?;
+            }
+        }
+        None => roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        })),
+    }
+    Ok(rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Applies the migrations the database has not seen yet.
+fn migrate(client: &mut Client) -> GeyserResult<()> {
+    let failed = |error: postgres::Error| {
+        GeyserPluginError::Custom(
+            format!("Failed to migrate the PostgreSQL schema: {}", error).into(),
+        )
+    };
+    let mut transaction = client.transaction().map_err(failed)?;
+    transaction
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS fusion_schema_version (version INT NOT NULL);
+             LOCK TABLE fusion_schema_version IN EXCLUSIVE MODE;",
+        )
+        .map_err(failed)?;
+    let version: i32 = transaction
+        .query_opt("SELECT version FROM fusion_schema_version", &[])
+        .map_err(failed)?
+        .map(|row| row.get(0))
+        .unwrap_or_default();
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        transaction.batch_execute(migration).map_err(failed)?;
+    }
+    if version as usize != MIGRATIONS.len() {
+        transaction
+            .batch_execute(&format!(
+                "DELETE FROM fusion_schema_version;
+                 INSERT INTO fusion_schema_version VALUES ({});",
+                MIGRATIONS.len()
+            ))
+            .map_err(failed)?;
+        info!(
+            "Migrated the PostgreSQL schema from version {} to {}",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+    transaction.commit().map_err(failed)
+}
+
+struct Statements {
+    account: Statement,
+    transaction: Statement,
+    slot: Statement,
+}
+
+impl Statements {
+    fn prepare(client: &mut Client) -> Result<Self, postgres::Error> {
+        Ok(Statements {
+            account: client.prepare(UPSERT_ACCOUNT)?,
+            transaction: client.prepare(INSERT_TRANSACTION)?,
+            slot: client.prepare(UPSERT_SLOT)?,
+        })
+    }
+}
+
+struct PostgresWorker {
+    client: Client,
+    statements: Statements,
+    connector: Connector,
+    batch_size: usize,
+    batch_timeout: Duration,
+    max_attempts: u32,
+    retry_backoff_ms: u64,
+    max_retry_backoff_ms: u64,
+    failed: Sender<(Vec<AccTx>, String)>,
+    metrics: Arc<PluginMetrics>,
+    undelivered: Undelivered,
+}
+
+impl PostgresWorker {
+    fn new(
+        mut client: Client,
+        connector: Connector,
+        config: &PostgresConfig,
+        failed: Sender<(Vec<AccTx>, String)>,
+        metrics: Arc<PluginMetrics>,
+        undelivered: Undelivered,
+    ) -> GeyserResult<Self> {
+        let statements = Statements::prepare(&mut client)
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        Ok(PostgresWorker {
+            client,
+            statements,
+            connector,
+            batch_size: config.batch_size.max(1),
+            batch_timeout: Duration::from_millis(config.batch_ms.max(1)),
+            max_attempts: config.max_attempts.max(1),
+            retry_backoff_ms: config.retry_backoff_ms,
+            max_retry_backoff_ms: config.max_retry_backoff_ms,
+            failed,
+            metrics,
+            undelivered,
+        })
+    }
+
+    fn run(mut self, receiver: Receiver<AccTx>) {
+        let mut closed = false;
+        while !closed {
+            let mut batch = Vec::new();
+            let deadline = Instant::now() + self.batch_timeout;
+            while batch.len() < self.batch_size {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(event) => batch.push(event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+
+            if !batch.is_empty() {
+                self.store(batch);
+            }
+        }
+    }
+
+    /// Writes `batch` in one transaction, reconnecting after failures. A
+    /// batch failing every attempt goes back to the sink, its events still
+    /// undelivered until the sink takes them.
+    fn store(&mut self, batch: Vec<AccTx>) {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.try_store(&batch) {
+                Ok(()) => {
+                    self.undelivered.done(batch.iter().map(AccTx::slot));
+                    return;
+                }
+                Err(error) => error,
+            };
+            if attempt >= self.max_attempts {
+                let error = format!("after {} attempts: {}", attempt, error);
+                // Unless the sink is gone, with nothing left to retry them.
+                if let Err(mpsc::SendError((batch, error))) = self.failed.send((batch, error)) {
+                    self.undelivered.done(batch.iter().map(AccTx::slot));
+                    dropped(&self.metrics, &batch, &error);
+                }
+                return;
+            }
+            let backoff_ms = self
+                .retry_backoff_ms
+                .saturating_mul(1 << (attempt - 1).min(16))
+                .min(self.max_retry_backoff_ms);
+            warn!(
+                "PostgreSQL write failed on attempt {}, retrying in {} ms: {}",
+                attempt, backoff_ms, error
+            );
+            thread::sleep(Duration::from_millis(backoff_ms));
+            if self.client.is_closed() {
+                if let Err(error) = self.reconnect() {
+                    warn!("Failed to reconnect to PostgreSQL: {}", error);
+                }
+            }
+        }
+    }
+
+    fn try_store(&mut self, batch: &[AccTx]) -> Result<(), postgres::Error> {
+        let statements = &self.statements;
+        let mut transaction = self.client.transaction()?;
+        for event in batch {
+            match event {
+                AccTx::Acc(account) => {
+                    transaction.execute(
+                        &statements.account,
+                        &[
+                            &account.pubkey,
+                            &account.owner,
+                            &(account.lamports as i64),
+                            &account.executable,
+                            &(account.rent_epoch as i64),
+                            &account.data,
+                            &account
+                                .data_hash
+                                .map(|data_hash| data_hash.to_bytes().to_vec()),
+                            &(account.write_version as i64),
+                            &(account.slot as i64),
+                            &account
+                                .txn_signature
+                                .map(|signature| signature.as_ref().to_vec()),
+                        ],
+                    )?;
+                }
+                AccTx::Tx(transaction_update) => {
+                    let meta = &transaction_update.transaction_status_meta;
+                    let message = bincode::serialize(
+                        &transaction_update.transaction.to_versioned_transaction(),
+                    )
+                    .unwrap_or_default();
+                    transaction.execute(
+                        &statements.transaction,
+                        &[
+                            &transaction_update.signature.as_ref().to_vec(),
+                            &(transaction_update.slot as i64),
+                            &transaction_update.is_vote,
+                            &transaction_update.index.map(|index| index as i64),
+                            &transaction_update
+                                .transaction
+                                .message()
+                                .fee_payer()
+                                .to_bytes()
+                                .to_vec(),
+                            &(meta.fee as i64),
+                            &meta.status.as_ref().err().map(|err| err.to_string()),
+                            &meta.compute_units_consumed.map(|units| units as i64),
+                            &meta.log_messages.clone().unwrap_or_default(),
+                            &message,
+                        ],
+                    )?;
+                }
+                AccTx::Slot(slot) => {
+                    transaction.execute(
+                        &statements.slot,
+                        &[
+                            &(slot.slot as i64),
+                            &slot.parent.map(|parent| parent as i64),
+                            &format!("{:?}", slot.status).to_lowercase(),
+                        ],
+                    )?;
+                }
+                _ => {}
+            }
+        }
+        transaction.commit()
+    }
+
+    fn reconnect(&mut self) -> Result<(), postgres::Error> {
+        let mut client = self.connector.connect()?;
+        self.statements = Statements::prepare(&mut client)?;
+        self.client = client;
+        Ok(())
+    }
+}
+
+fn dropped(metrics: &PluginMetrics, batch: &[AccTx], error: &str) {
+    let events = batch.len() as u64;
+    let error = format!(
+        "Dropping {} events after PostgreSQL error {}",
+        events, error
+    );
+    error!("{}", error);
+    metrics.record_error(error);
+    let first_slot = batch.iter().map(AccTx::slot).min().unwrap_or_default();
+    let last_slot = batch.iter().map(AccTx::slot).max().unwrap_or_default();
+    metrics
+        .gaps
+        .record("postgres", first_slot, last_slot, events);
+    for _ in 0..events {
+        PluginMetrics::increment(&metrics.dropped_events);
+    }
+}
@@ -0,0 +1,489 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use crate::{
+    AccTx, AccountChange, AccountUpdate, BlockUpdate, Codec, EncodedEvent, Sink, SlotUpdate,
+    TransactionUpdate,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+/// One-line summaries of every event, for watching the plugin on the box.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PrettyConfig {
+    /// Stdout when unset.
+    pub path: Option<PathBuf>,
+    /// ANSI colors, on by default for stdout only.
+    pub color: Option<bool>,
+}
+
+/// Aligned summaries with abbreviated pubkeys and lamports in SOL.
+#[derive(Debug, Default)]
+pub struct PrettyCodec {
+    pub color: bool,
+}
+
+/// Writes [`PrettyCodec`] lines to a file or stdout.
+pub struct PrettySink {
+    codec: PrettyCodec,
+    out: Box<dyn Write + Send>,
+}
+
+impl PrettySink {
+    pub fn open(config: &PrettyConfig) -> GeyserResult<Self> {
+        let out: Box<dyn Write + Send> = match &config.path {
+            Some(path) => Box::new(File::create(path).map_err(|error| {
+                GeyserPluginError::Custom(format!("Failed to create {:?}: {}", path, error).into())
+            })?),
+            None => Box::new(io::stdout()),
+        };
+        let codec = PrettyCodec {
+            color: config.color.unwrap_or(config.path.is_none()),
+        };
+
+        Ok(PrettySink { codec, out })
+    }
+}
+
+impl Sink for PrettySink {
+    fn name(&self) -> &'static str {
+        "pretty"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        match self.codec.encode(event.event) {
+            Some(line) => self.out.write_all(&line),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Codec for PrettyCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        let mut line = self.render(event);
+        line.push('\n');
+        Some(line.into_bytes())
+    }
+}
+
+impl PrettyCodec {
+    fn paint(&self, color: &str, text: impl AsRef<str>) -> String {
+        if self.color {
+            format!("{}{}{}", color, text.as_ref(), RESET)
+        } else {
+            text.as_ref().to_string()
+        }
+    }
+
+    fn render(&self, event: &AccTx) -> String {
+        let (kind, details) = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                lamports,
+                owner,
+                data,
+                is_startup,
+                data_hash,
+                data_len,
+                labels,
+                ..
+            }) => {
+                let data = match data_hash {
+                    Some(data_hash) => format!(
+                        "hash={} data={}B",
+                        short(data_hash),
+                        data_len.unwrap_or_default()
+                    ),
+                    None => format!("data={}B", data.len()),
+                };
+                let mut details = format!(
+                    "{} owner={} {} {}",
+                    short_bytes(pubkey),
+                    short_bytes(owner),
+                    sol(*lamports),
+                    data
+                );
+                for labeled in labels {
+                    let _ = write!(details, " [{}]", labeled.label.name);
+                }
+                if *is_startup {
+                    details.push_str(&self.paint(DIM, " startup"));
+                }
+                ("account", details)
+            }
+            AccTx::Tx(TransactionUpdate {
+                signature,
+                is_vote,
+                transaction_status_meta,
+                memos,
+                ..
+            }) => {
+                let status = match &transaction_status_meta.status {
+                    Ok(()) => self.paint(GREEN, "ok"),
+                    Err(err) => self.paint(RED, format!("failed: {}", err)),
+                };
+                let mut details = format!(
+                    "{} {} fee={}",
+                    short(signature),
+                    status,
+                    sol(transaction_status_meta.fee)
+                );
+                if let Some(units) = transaction_status_meta.compute_units_consumed {
+                    let _ = write!(details, " cu={}", units);
+                }
+                if *is_vote {
+                    details.push_str(&self.paint(DIM, " vote"));
+                }
+                for memo in memos {
+                    let _ = write!(details, " memo={:?}", memo);
+                }
+                ("transaction", details)
+            }
+            AccTx::Lifecycle {
+                pubkey,
+                owner,
+                change,
+                ..
+            } => {
+                let change = match change {
+                    AccountChange::Created => "created".to_string(),
+                    AccountChange::Reassigned { previous_owner } => {
+                        format!("reassigned from {}", short_bytes(previous_owner))
+                    }
+                    AccountChange::Resized { previous_len, len } => {
+                        format!("resized {}B -> {}B", previous_len, len)
+                    }
+                    AccountChange::Closed => "closed".to_string(),
+                };
+                (
+                    "lifecycle",
+                    format!(
+                        "{} owner={} {}",
+                        short_bytes(pubkey),
+                        short_bytes(owner),
+                        change
+                    ),
+                )
+            }
+            AccTx::MintSupply {
+                mint,
+                supply,
+                holders,
+                token_accounts,
+                ..
+            } => (
+                "mint_supply",
+                format!(
+                    "{} supply={} holders={} accounts={}",
+                    short(mint),
+                    supply,
+                    holders,
+                    token_accounts
+                ),
+            ),
+            AccTx::OwnerLamports {
+                owner,
+                lamports,
+                accounts,
+                ..
+            } => (
+                "owner_lamports",
+                format!("{} {} accounts={}", short(owner), sol(*lamports), accounts),
+            ),
+            AccTx::SlotStats {
+                transactions,
+                vote_transactions,
+                failed_transactions,
+                fees,
+                fee_payers,
+                ..
+            } => (
+                "slot_stats",
+                format!(
+                    "txs={} votes={} failed={} fees={} payers={}",
+                    transactions,
+                    vote_transactions,
+                    failed_transactions,
+                    sol(*fees),
+                    fee_payers
+                ),
+            ),
+            AccTx::Throughput {
+                window_secs,
+                transactions_per_sec,
+                account_updates_per_sec,
+                dropped_events_per_sec,
+                ..
+            } => (
+                "throughput",
+                format!(
+                    "{}s txs/s={:.1} accounts/s={:.1} dropped/s={:.1}",
+                    window_secs,
+                    transactions_per_sec,
+                    account_updates_per_sec,
+                    dropped_events_per_sec
+                ),
+            ),
+            AccTx::NftTransfer {
+                mint,
+                from,
+                to,
+                signature,
+                ..
+            } => (
+                "nft_transfer",
+                format!(
+                    "{} {} -> {} tx={}",
+                    short(mint),
+                    short_opt(from),
+                    short_opt(to),
+                    short(signature)
+                ),
+            ),
+            AccTx::CompressedNft {
+                action,
+                tree,
+                asset_id,
+                owner,
+                new_owner,
+                signature,
+                ..
+            } => (
+                "compressed_nft",
+                format!(
+                    "{:?} tree={} asset={} {} -> {} tx={}",
+                    action,
+                    short(tree),
+                    short_opt(asset_id),
+                    short_opt(owner),
+                    short_opt(new_owner),
+                    short(signature)
+                ),
+            ),
+            AccTx::NonceAdvanced {
+                nonce_account,
+                authority,
+                failed,
+                signature,
+                ..
+            } => {
+                let mut details = format!(
+                    "{} authority={} tx={}",
+                    short(nonce_account),
+                    short_opt(authority),
+                    short(signature)
+                );
+                if *failed {
+                    details.push_str(&self.paint(RED, " failed"));
+                }
+                ("nonce_advanced", details)
+            }
+            AccTx::NonceAccountChanged {
+                nonce_account,
+                authority,
+                blockhash,
+                ..
+            } => (
+                "nonce_account",
+                format!(
+                    "{} authority={} blockhash={}",
+                    short(nonce_account),
+                    short(authority),
+                    short(blockhash)
+                ),
+            ),
+            AccTx::TokenOwnerChanged {
+                account,
+                mint,
+                previous_owner,
+                owner,
+                ..
+            } => (
+                "token_owner",
+                format!(
+                    "{} mint={} {} -> {}",
+                    short(account),
+                    short(mint),
+                    short_opt(previous_owner),
+                    short_opt(owner)
+                ),
+            ),
+            AccTx::AddressLookupTable {
+                address,
+                authority,
+                deactivation_slot,
+                addresses,
+                ..
+            } => {
+                let mut details = format!(
+                    "{} authority={} addresses={}",
+                    short(address),
+                    short_opt(authority),
+                    addresses.len()
+                );
+                if let Some(slot) = deactivation_slot {
+                    details.push_str(&self.paint(YELLOW, format!(" deactivated@{}", slot)));
+                }
+                ("lookup_table", details)
+            }
+            AccTx::ProgramUpgraded {
+                action,
+                program,
+                authority,
+                new_authority,
+                signature,
+                ..
+            } => (
+                "program",
+                format!(
+                    "{:?} {} authority={} -> {} tx={}",
+                    action,
+                    short_opt(program),
+                    short_opt(authority),
+                    short_opt(new_authority),
+                    short(signature)
+                ),
+            ),
+            AccTx::ProgramDataChanged {
+                programdata,
+                program,
+                deployed_slot,
+                upgrade_authority,
+                ..
+            } => (
+                "programdata",
+                format!(
+                    "{} program={} deployed@{} authority={}",
+                    short(programdata),
+                    short_opt(program),
+                    deployed_slot,
+                    short_opt(upgrade_authority)
+                ),
+            ),
+            AccTx::EpochStarted {
+                epoch, first_slot, ..
+            } => (
+                "epoch",
+                format!("epoch {} first_slot={}", epoch, first_slot),
+            ),
+            AccTx::Slot(SlotUpdate { parent, status, .. }) => (
+                "slot",
+                format!(
+                    "{:?} parent={}",
+                    status,
+                    parent.map_or_else(|| "-".to_string(), |parent| parent.to_string())
+                ),
+            ),
+            AccTx::SlotRollback { .. } => ("rollback", "dead fork".to_string()),
+            AccTx::Block(BlockUpdate {
+                blockhash,
+                block_height,
+                executed_transaction_count,
+                rewards,
+                ..
+            }) => (
+                "block",
+                format!(
+                    "{} height={} txs={} rewards={}",
+                    short(blockhash),
+                    block_height.map_or_else(|| "-".to_string(), |height| height.to_string()),
+                    executed_transaction_count
+                        .map_or_else(|| "-".to_string(), |count| count.to_string()),
+                    rewards.len()
+                ),
+            ),
+            AccTx::ForkStats {
+                first_slot,
+                rooted_slots,
+                skipped_slots,
+                fork_switches,
+                ..
+            } => (
+                "fork_stats",
+                format!(
+                    "from={} rooted={} skipped={} switches={}",
+                    first_slot, rooted_slots, skipped_slots, fork_switches
+                ),
+            ),
+            AccTx::ConfirmationLatency {
+                status, latency_ms, ..
+            } => ("latency", format!("{:?} after {}ms", status, latency_ms)),
+            AccTx::TxWithEffects {
+                signature,
+                accounts,
+                ..
+            } => (
+                "tx_effects",
+                format!("{} accounts={}", short(signature), accounts.len()),
+            ),
+            AccTx::Heartbeat { healthy, .. } => (
+                "heartbeat",
+                if *healthy {
+                    self.paint(GREEN, "healthy")
+                } else {
+                    self.paint(RED, "unhealthy")
+                },
+            ),
+            AccTx::Watermark { max_sequence, .. } => (
+                "watermark",
+                format!("complete through record {}", max_sequence),
+            ),
+        };
+
+        format!(
+            "{} {} {}",
+            self.paint(DIM, format!("{:>10}", event.slot())),
+            self.paint(BOLD, self.paint(CYAN, format!("{:<14}", kind))),
+            details
+        )
+    }
+}
+
+/// First and last four characters of a base58 string.
+fn short(value: &impl ToString) -> String {
+    let value = value.to_string();
+    if value.len() <= 10 {
+        return value;
+    }
+    format!("{}..{}", &value[..4], &value[value.len() - 4..])
+}
+
+fn short_bytes(pubkey: &[u8]) -> String {
+    match Pubkey::try_from(pubkey) {
+        Ok(pubkey) => short(&pubkey),
+        Err(_) => "?".to_string(),
+    }
+}
+
+fn short_opt(pubkey: &Option<Pubkey>) -> String {
+    pubkey.as_ref().map_or_else(|| "-".to_string(), short)
+}
+
+fn sol(lamports: u64) -> String {
+    format!(
+        "{}.{:09} SOL",
+        lamports / LAMPORTS_PER_SOL,
+        lamports % LAMPORTS_PER_SOL
+    )
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use solana_sdk::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    loader_upgradeable_instruction::UpgradeableLoaderInstruction,
+    pubkey::Pubkey,
+};
+
+use crate::{instructions::resolve_instructions, AccTx, AccountUpdate, TransactionUpdate};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramAction {
+    Deployed,
+    Upgraded,
+    AuthorityChanged,
+    Extended,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProgramDataState {
+    deployed_slot: u64,
+    upgrade_authority: Option<Pubkey>,
+}
+
+/// Reports BPF Upgradeable Loader activity: [`AccTx::ProgramUpgraded`] from
+/// successful loader instructions and [`AccTx::ProgramDataChanged`] when a
+/// ProgramData account's deployment slot or upgrade authority changes.
+#[derive(Debug, Default)]
+pub struct ProgramUpgradeMonitor {
+    programs: HashMap<Pubkey, Pubkey>,
+    programdata: HashMap<Pubkey, ProgramDataState>,
+}
+
+impl ProgramUpgradeMonitor {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                data,
+                txn_signature,
+                slot,
+                is_startup,
+                ..
+            }) => {
+                if owner.as_slice() != bpf_loader_upgradeable::id().as_ref() {
+                    return Vec::new();
+                }
+                let pubkey = match Pubkey::try_from(pubkey.as_slice()) {
+                    Ok(pubkey) => pubkey,
+                    Err(_) => return Vec::new(),
+                };
+
+                let state = match bincode::deserialize(data) {
+                    Ok(state) if *lamports > 0 => state,
+                    _ => {
+                        self.programdata.remove(&pubkey);
+                        return Vec::new();
+                    }
+                };
+                let current = match state {
+                    UpgradeableLoaderState::Program {
+                        programdata_address,
+                    } => {
+                        self.programs.insert(programdata_address, pubkey);
+                        return Vec::new();
+                    }
+                    UpgradeableLoaderState::ProgramData {
+                        slot: deployed_slot,
+                        upgrade_authority_address,
+                    } => ProgramDataState {
+                        deployed_slot,
+                        upgrade_authority: upgrade_authority_address,
+                    },
+                    _ => return Vec::new(),
+                };
+
+                let previous = self.programdata.insert(pubkey, current);
+                if *is_startup || previous == Some(current) {
+                    return Vec::new();
+                }
+
+                vec![AccTx::ProgramDataChanged {
+                    programdata: pubkey,
+                    program: self.programs.get(&pubkey).copied(),
+                    deployed_slot: current.deployed_slot,
+                    upgrade_authority: current.upgrade_authority,
+                    previous_deployed_slot: previous.map(|previous| previous.deployed_slot),
+                    previous_upgrade_authority: previous
+                        .and_then(|previous| previous.upgrade_authority),
+                    slot: *slot,
+                    txn_signature: *txn_signature,
+                }]
+            }
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => {
+                if transaction_status_meta.status.is_err() {
+                    return Vec::new();
+                }
+
+                resolve_instructions(transaction, transaction_status_meta)
+                    .into_iter()
+                    .filter(|instruction| bpf_loader_upgradeable::check_id(instruction.program_id))
+                    .filter_map(|instruction| {
+                        let loader_instruction = bincode::deserialize(instruction.data).ok()?;
+                        let (action, programdata, program, authority, new_authority) =
+                            match loader_instruction {
+                                UpgradeableLoaderInstruction::DeployWithMaxDataLen { .. } => (
+                                    ProgramAction::Deployed,
+                                    instruction.account(1),
+                                    instruction.account(2),
+                                    instruction.account(7),
+                                    None,
+                                ),
+                                UpgradeableLoaderInstruction::Upgrade => (
+                                    ProgramAction::Upgraded,
+                                    instruction.account(0),
+                                    instruction.account(1),
+                                    instruction.account(6),
+                                    None,
+                                ),
+                                UpgradeableLoaderInstruction::SetAuthority
+                                | UpgradeableLoaderInstruction::SetAuthorityChecked => (
+                                    ProgramAction::AuthorityChanged,
+                                    instruction.account(0),
+                                    None,
+                                    instruction.account(1),
+                                    instruction.account(2),
+                                ),
+                                UpgradeableLoaderInstruction::ExtendProgram { .. } => (
+                                    ProgramAction::Extended,
+                                    instruction.account(0),
+                                    instruction.account(1),
+                                    None,
+                                    None,
+                                ),
+                                UpgradeableLoaderInstruction::Close => (
+                                    ProgramAction::Closed,
+                                    instruction.account(0),
+                                    instruction.account(3),
+                                    instruction.account(2),
+                                    None,
+                                ),
+                                _ => return None,
+                            };
+                        let programdata = programdata.copied();
+
+                        Some(AccTx::ProgramUpgraded {
+                            action,
+                            program: program
+                                .copied()
+                                .or_else(|| self.programs.get(programdata.as_ref()?).copied()),
+                            programdata,
+                            authority: authority.copied(),
+                            new_authority: new_authority.copied(),
+                            slot: *slot,
+                            signature: *signature,
+                        })
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
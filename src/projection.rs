@@ -0,0 +1,565 @@
+use std::{collections::HashSet, fmt};
+
+use serde::Deserialize;
+
+use crate::{AccTx, AccountUpdate, BlockUpdate, SlotUpdate, TransactionUpdate};
+
+/// Selects which fields of an event a sink serializes.
+///
+/// With neither list set every field is emitted, matching the plain
+/// `Debug` output of [`AccTx`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FieldProjection {
+    /// Only these fields are emitted when set.
+    pub include_fields: Option<HashSet<String>>,
+    /// These fields are never emitted, even if listed in `include_fields`.
+    pub exclude_fields: HashSet<String>,
+}
+
+impl FieldProjection {
+    pub fn includes(&self, field: &str) -> bool {
+        let included = match &self.include_fields {
+            Some(include_fields) => include_fields.contains(field),
+            None => true,
+        };
+
+        included && !self.exclude_fields.contains(field)
+    }
+
+    /// Resets `value` when `field` is left out, for formats whose records
+    /// keep every field.
+    pub fn clear<T: Default>(&self, field: &str, value: &mut T) {
+        if !self.includes(field) {
+            *value = T::default();
+        }
+    }
+}
+
+/// An event rendered through a [`FieldProjection`].
+pub struct Projected<'a> {
+    pub event: &'a AccTx,
+    pub projection: &'a FieldProjection,
+}
+
+macro_rules! project_fields {
+    ($f:expr, $projection:expr, $name:literal, $($field:ident),* $(,)?) => {{
+        let mut debug_struct = $f.debug_struct($name);
+        $(
+            if $projection.includes(stringify!($field)) {
+                debug_struct.field(stringify!($field), $field);
+            }
+        )*
+        debug_struct.finish()
+    }};
+}
+
+impl fmt::Debug for Projected<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                lamports,
+                owner,
+                executable,
+                rent_epoch,
+                data,
+                write_version,
+                txn_signature,
+                slot,
+                is_startup,
+                data_hash,
+                data_len,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+            }) => project_fields!(
+                f,
+                self.projection,
+                "Acc",
+                pubkey,
+                lamports,
+                owner,
+                executable,
+                rent_epoch,
+                data,
+                write_version,
+                txn_signature,
+                slot,
+                is_startup,
+                data_hash,
+                data_len,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+            ),
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                index,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+                memos,
+                sol_transfers,
+                error_class,
+            }) => project_fields!(
+                f,
+                self.projection,
+                "Tx",
+                slot,
+                signature,
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                index,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+                memos,
+                sol_transfers,
+                error_class,
+            ),
+            AccTx::Lifecycle {
+                pubkey,
+                owner,
+                slot,
+                write_version,
+                change,
+            } => project_fields!(
+                f,
+                self.projection,
+                "Lifecycle",
+                pubkey,
+                owner,
+                slot,
+                write_version,
+                change,
+            ),
+            AccTx::MintSupply {
+                mint,
+                slot,
+                supply,
+                holders,
+                token_accounts,
+            } => project_fields!(
+                f,
+                self.projection,
+                "MintSupply",
+                mint,
+                slot,
+                supply,
+                holders,
+                token_accounts,
+            ),
+            AccTx::OwnerLamports {
+                owner,
+                slot,
+                lamports,
+                accounts,
+            } => project_fields!(
+                f,
+                self.projection,
+                "OwnerLamports",
+                owner,
+                slot,
+                lamports,
+                accounts,
+            ),
+            AccTx::SlotStats {
+                slot,
+                transactions,
+                vote_transactions,
+                failed_transactions,
+                fees,
+                fee_payers,
+            } => project_fields!(
+                f,
+                self.projection,
+                "SlotStats",
+                slot,
+                transactions,
+                vote_transactions,
+                failed_transactions,
+                fees,
+                fee_payers,
+            ),
+            AccTx::Throughput {
+                slot,
+                window_secs,
+                transactions_per_sec,
+                account_updates_per_sec,
+                dropped_events_per_sec,
+            } => project_fields!(
+                f,
+                self.projection,
+                "Throughput",
+                slot,
+                window_secs,
+                transactions_per_sec,
+                account_updates_per_sec,
+                dropped_events_per_sec,
+            ),
+            AccTx::NftTransfer {
+                mint,
+                from,
+                to,
+                slot,
+                signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "NftTransfer",
+                mint,
+                from,
+                to,
+                slot,
+                signature,
+            ),
+            AccTx::CompressedNft {
+                action,
+                tree,
+                asset_id,
+                leaf_index,
+                seq,
+                owner,
+                new_owner,
+                slot,
+                signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "CompressedNft",
+                action,
+                tree,
+                asset_id,
+                leaf_index,
+                seq,
+                owner,
+                new_owner,
+                slot,
+                signature,
+            ),
+            AccTx::NonceAdvanced {
+                nonce_account,
+                authority,
+                failed,
+                slot,
+                signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "NonceAdvanced",
+                nonce_account,
+                authority,
+                failed,
+                slot,
+                signature,
+            ),
+            AccTx::NonceAccountChanged {
+                nonce_account,
+                authority,
+                blockhash,
+                slot,
+                txn_signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "NonceAccountChanged",
+                nonce_account,
+                authority,
+                blockhash,
+                slot,
+                txn_signature,
+            ),
+            AccTx::TokenOwnerChanged {
+                account,
+                mint,
+                previous_owner,
+                owner,
+                slot,
+            } => project_fields!(
+                f,
+                self.projection,
+                "TokenOwnerChanged",
+                account,
+                mint,
+                previous_owner,
+                owner,
+                slot,
+            ),
+            AccTx::AddressLookupTable {
+                address,
+                authority,
+                deactivation_slot,
+                last_extended_slot,
+                addresses,
+                slot,
+                write_version,
+            } => project_fields!(
+                f,
+                self.projection,
+                "AddressLookupTable",
+                address,
+                authority,
+                deactivation_slot,
+                last_extended_slot,
+                addresses,
+                slot,
+                write_version,
+            ),
+            AccTx::ProgramUpgraded {
+                action,
+                program,
+                programdata,
+                authority,
+                new_authority,
+                slot,
+                signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "ProgramUpgraded",
+                action,
+                program,
+                programdata,
+                authority,
+                new_authority,
+                slot,
+                signature,
+            ),
+            AccTx::ProgramDataChanged {
+                programdata,
+                program,
+                deployed_slot,
+                upgrade_authority,
+                previous_deployed_slot,
+                previous_upgrade_authority,
+                slot,
+                txn_signature,
+            } => project_fields!(
+                f,
+                self.projection,
+                "ProgramDataChanged",
+                programdata,
+                program,
+                deployed_slot,
+                upgrade_authority,
+                previous_deployed_slot,
+                previous_upgrade_authority,
+                slot,
+                txn_signature,
+            ),
+            AccTx::EpochStarted {
+                epoch,
+                first_slot,
+                slot,
+            } => project_fields!(f, self.projection, "EpochStarted", epoch, first_slot, slot),
+            AccTx::Block(BlockUpdate {
+                slot,
+                blockhash,
+                parent_slot,
+                parent_blockhash,
+                block_time,
+                block_height,
+                executed_transaction_count,
+                rewards,
+            }) => project_fields!(
+                f,
+                self.projection,
+                "Block",
+                slot,
+                blockhash,
+                parent_slot,
+                parent_blockhash,
+                block_time,
+                block_height,
+                executed_transaction_count,
+                rewards,
+            ),
+            AccTx::Slot(SlotUpdate {
+                slot,
+                parent,
+                status,
+            }) => project_fields!(f, self.projection, "Slot", slot, parent, status),
+            AccTx::SlotRollback { slot } => {
+                project_fields!(f, self.projection, "SlotRollback", slot)
+            }
+            AccTx::ForkStats {
+                first_slot,
+                slot,
+                rooted_slots,
+                skipped_slots,
+                fork_switches,
+            } => project_fields!(
+                f,
+                self.projection,
+                "ForkStats",
+                first_slot,
+                slot,
+                rooted_slots,
+                skipped_slots,
+                fork_switches,
+            ),
+            AccTx::ConfirmationLatency {
+                slot,
+                status,
+                latency_ms,
+            } => project_fields!(
+                f,
+                self.projection,
+                "ConfirmationLatency",
+                slot,
+                status,
+                latency_ms,
+            ),
+            AccTx::TxWithEffects {
+                slot,
+                signature,
+                transaction,
+                accounts,
+            } => project_fields!(
+                f,
+                self.projection,
+                "TxWithEffects",
+                slot,
+                signature,
+                transaction,
+                accounts,
+            ),
+            AccTx::Heartbeat { slot, healthy } => {
+                project_fields!(f, self.projection, "Heartbeat", slot, healthy)
+            }
+            AccTx::Watermark { slot, max_sequence } => {
+                project_fields!(f, self.projection, "Watermark", slot, max_sequence)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        grpc::proto::{subscribe_update::Update, SubscribeUpdate},
+        CodecRegistry, EventRecord, SinkConfig,
+    };
+
+    /// Keeps `pubkey` and `lamports`: `data` is excluded although included,
+    /// and every other field is left out.
+    fn projection() -> FieldProjection {
+        FieldProjection {
+            include_fields: Some(
+                ["pubkey", "lamports", "data"]
+                    .map(String::from)
+                    .into_iter()
+                    .collect(),
+            ),
+            exclude_fields: HashSet::from(["data".to_string()]),
+        }
+    }
+
+    fn encode(codec: &str) -> Vec<u8> {
+        let sink = SinkConfig {
+            codec: Some(codec.to_string()),
+            projection: projection(),
+            ..SinkConfig::default()
+        };
+        let account = AccTx::Acc(
+            AccountUpdate::new(vec![1; 32], vec![2; 32], 7)
+                .with_lamports(42)
+                .with_data(vec![3; 16]),
+        );
+        CodecRegistry::default()
+            .build(&sink, "debug")
+            .unwrap()
+            .encode(&account)
+            .unwrap()
+    }
+
+    #[test]
+    fn excludes_over_includes() {
+        let projection = projection();
+        assert!(projection.includes("lamports"));
+        assert!(!projection.includes("data"));
+        assert!(!projection.includes("owner"));
+        assert!(FieldProjection::default().includes("owner"));
+    }
+
+    #[test]
+    fn debug_codec_leaves_out_fields() {
+        let text = String::from_utf8(encode("debug")).unwrap();
+        assert!(text.starts_with("Acc { pubkey: "), "{}", text);
+        assert!(text.contains("lamports: 42"), "{}", text);
+        assert!(
+            !text.contains("owner") && !text.contains("data"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("slot"), "{}", text);
+    }
+
+    #[test]
+    fn json_codec_clears_fields() {
+        let record: serde_json::Value = serde_json::from_slice(&encode("json")).unwrap();
+        let account = &record["account"];
+        assert_eq!(account["lamports"], 42);
+        assert_eq!(account["pubkey"], Pubkey::from([1; 32]).to_string());
+        assert_eq!(account["owner"], "");
+        assert_eq!(account["data"], serde_json::json!([]));
+        assert_eq!(account["slot"], 0);
+    }
+
+    #[test]
+    fn bincode_codec_clears_fields() {
+        let bytes = encode("bincode");
+        match bincode::deserialize(&bytes[4..]).unwrap() {
+            EventRecord::Account(account) => {
+                assert_eq!(account.lamports, 42);
+                assert_eq!(account.pubkey, Pubkey::from([1; 32]).to_string());
+                assert_eq!(account.owner, "");
+                assert!(account.data.is_empty());
+                assert_eq!(account.slot, 0);
+            }
+            record => panic!("unexpected record {:?}", record),
+        }
+    }
+
+    #[test]
+    fn protobuf_codec_clears_fields() {
+        let update = SubscribeUpdate::decode(encode("protobuf").as_slice()).unwrap();
+        match update.update {
+            Some(Update::Account(account)) => {
+                assert_eq!(account.lamports, 42);
+                assert_eq!(account.pubkey, vec![1; 32]);
+                assert!(account.owner.is_empty());
+                assert!(account.data.is_empty());
+                assert_eq!(account.slot, 0);
+            }
+            update => panic!("unexpected update {:?}", update),
+        }
+    }
+
+    #[test]
+    fn codecs_without_projections_are_refused() {
+        let sink = SinkConfig {
+            projection: projection(),
+            ..SinkConfig::default()
+        };
+        assert!(CodecRegistry::default().build(&sink, "pretty").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Plugin counters in the Prometheus text format, served on
+//! `GET /metrics` for scraping.
+
+use std::{fmt::Write, net::SocketAddr, sync::Arc};
+
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use log::{info, warn};
+use serde::Deserialize;
+use smol::{
+    channel::Sender,
+    net::{TcpListener, TcpStream},
+    Task,
+};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::{AccTx, PluginMetrics, LATENCY_BUCKETS};
+
+const MAX_REQUEST_LEN: usize = 16 << 10;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PrometheusConfig {
+    pub address: SocketAddr,
+    /// Metric names are `<prefix>_<counter>`.
+    pub prefix: String,
+}
+
+impl Default for PrometheusConfig {
+    fn default() -> Self {
+        PrometheusConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 9102)),
+            prefix: "fusion_engine".to_string(),
+        }
+    }
+}
+
+/// Accept loop of the metrics endpoint, stopped on drop.
+#[derive(Debug)]
+pub struct PrometheusExporter {
+    _listener: Task<()>,
+}
+
+impl PrometheusExporter {
+    /// `queue` is only read for its depth.
+    pub fn start(
+        config: &PrometheusConfig,
+        metrics: Arc<PluginMetrics>,
+        queue: Sender<AccTx>,
+    ) -> GeyserResult<Self> {
+        let listener = smol::block_on(TcpListener::bind(config.address)).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to listen for metrics scrapes on {}: {}",
+                    config.address, error
+                )
+                .into(),
+            )
+        })?;
+        let exporter = Arc::new(Exporter {
+            prefix: config.prefix.clone(),
+            metrics,
+            queue,
+        });
+
+        let listener = smol::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let exporter = exporter.clone();
+                        smol::spawn(async move {
+                            if let Err(error) = exporter.serve(stream).await {
+                                warn!("Metrics scrape failed: {}", error);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(error) => warn!("Failed to accept metrics scrape: {}", error),
+                }
+            }
+        });
+        info!("Serving metrics on http://{}/metrics", config.address);
+
+        Ok(PrometheusExporter {
+            _listener: listener,
+        })
+    }
+}
+
+struct Exporter {
+    prefix: String,
+    metrics: Arc<PluginMetrics>,
+    queue: Sender<AccTx>,
+}
+
+impl Exporter {
+    async fn serve(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 || request.len() + read > MAX_REQUEST_LEN {
+                return Ok(());
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut request_line = request
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .split_whitespace();
+        let (status, body) = match (request_line.next(), request_line.next()) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", self.render()),
+            _ => ("404 Not Found", String::new()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    fn render(&self) -> String {
+        let metrics = &self.metrics;
+        let prefix = &self.prefix;
+        let mut text = String::new();
+
+        let received = [
+            ("account", &metrics.account_updates),
+            ("transaction", &metrics.transactions),
+            ("slot", &metrics.slot_updates),
+            ("block", &metrics.blocks),
+        ];
+        header(
+            &mut text,
+            prefix,
+            "events_received_total",
+            "counter",
+            "Notifications from the validator, by kind.",
+        );
+        for (kind, counter) in received {
+            let _ = writeln!(
+                text,
+                "{}_events_received_total{{kind=\"{}\"}} {}",
+                prefix,
+                kind,
+                PluginMetrics::get(counter)
+            );
+        }
+
+        header(
+            &mut text,
+            prefix,
+            "events_written_total",
+            "counter",
+            "Events handed to the sinks, by kind.",
+        );
+        for (kind, events) in metrics.written() {
+            let _ = writeln!(
+                text,
+                "{}_events_written_total{{kind=\"{}\"}} {}",
+                prefix, kind, events
+            );
+        }
+
+        let dropped = [
+            ("writer", &metrics.dropped_events),
+            ("quota", &metrics.quota_dropped_events),
+            ("overflow", &metrics.overflow_dropped_events),
+        ];
+        header(
+            &mut text,
+            prefix,
+            "events_dropped_total",
+            "counter",
+            "Events lost before reaching a sink, by cause.",
+        );
+        for (cause, counter) in dropped {
+            let _ = writeln!(
+                text,
+                "{}_events_dropped_total{{cause=\"{}\"}} {}",
+                prefix,
+                cause,
+                PluginMetrics::get(counter)
+            );
+        }
+
+        header(
+            &mut text,
+            prefix,
+            "queue_depth",
+            "gauge",
+            "Events waiting for the background writer.",
+        );
+        let _ = writeln!(text, "{}_queue_depth {}", prefix, self.queue.len());
+
+        header(
+            &mut text,
+            prefix,
+            "startup_accounts_total",
+            "counter",
+            "Accounts replayed at startup written so far.",
+        );
+        let _ = writeln!(
+            text,
+            "{}_startup_accounts_total {}",
+            prefix,
+            PluginMetrics::get(&metrics.startup_accounts)
+        );
+
+        header(
+            &mut text,
+            prefix,
+            "sink_write_seconds",
+            "histogram",
+            "Time taken by each write to a sink.",
+        );
+        for (sink, histogram) in metrics.sink_latency() {
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    text,
+                    "{}_sink_write_seconds_bucket{{sink=\"{}\",le=\"{}\"}} {}",
+                    prefix, sink, bound, count
+                );
+            }
+            let _ = writeln!(
+                text,
+                "{}_sink_write_seconds_bucket{{sink=\"{}\",le=\"+Inf\"}} {}",
+                prefix, sink, histogram.count
+            );
+            let _ = writeln!(
+                text,
+                "{}_sink_write_seconds_sum{{sink=\"{}\"}} {}",
+                prefix, sink, histogram.sum
+            );
+            let _ = writeln!(
+                text,
+                "{}_sink_write_seconds_count{{sink=\"{}\"}} {}",
+                prefix, sink, histogram.count
+            );
+        }
+
+        text
+    }
+}
+
+fn header(text: &mut String, prefix: &str, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {}_{} {}", prefix, name, help);
+    let _ = writeln!(text, "# TYPE {}_{} {}", prefix, name, kind);
+}
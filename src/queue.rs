@@ -0,0 +1,202 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use log::warn;
+use serde::Deserialize;
+use smol::channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+
+use crate::{AccTx, PluginMetrics, WriteAheadLog};
+
+/// The queue between the plugin callbacks and the background writer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Events the writer may fall behind by, unbounded when 0.
+    pub capacity: usize,
+    /// What the callbacks do with new events while the queue is full.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capacity: 1_000_000,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Wait for room, holding up the validator.
+    Block,
+    /// Make room by dropping the event queued longest.
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+}
+
+/// Sending end of the writer's queue. Dropped events are counted, recorded
+/// as a gap of the `queue` and removed from the write-ahead log.
+#[derive(Debug, Clone)]
+pub struct EventQueue {
+    sender: Sender<AccTx>,
+    /// Kept to take the oldest event out when dropping it.
+    receiver: Receiver<AccTx>,
+    overflow: OverflowPolicy,
+    metrics: Arc<PluginMetrics>,
+    wal: Option<Arc<WriteAheadLog>>,
+}
+
+impl EventQueue {
+    /// The queue and the writer's end of it.
+    pub fn new(
+        config: &QueueConfig,
+        metrics: Arc<PluginMetrics>,
+        wal: Option<Arc<WriteAheadLog>>,
+    ) -> (Self, Receiver<AccTx>) {
+        let (sender, receiver) = match config.capacity {
+            0 => unbounded(),
+            capacity => bounded(capacity),
+        };
+        let queue = EventQueue {
+            sender,
+            receiver: receiver.clone(),
+            overflow: config.overflow,
+            metrics,
+            wal,
+        };
+        (queue, receiver)
+    }
+
+    /// For producers that always wait for room, such as the bootstrap.
+    pub fn sender(&self) -> &Sender<AccTx> {
+        &self.sender
+    }
+
+    /// Queues `event` as the overflow policy says, returning false once
+    /// the writer is gone. Runs on the validator's threads without an
+    /// executor, and only waits with `block` while the queue is full.
+    pub fn push(&self, mut event: AccTx) -> bool {
+        loop {
+            match self.sender.try_send(event) {
+                Ok(()) => return true,
+                Err(TrySendError::Closed(_)) => return false,
+                Err(TrySendError::Full(rejected)) => match self.overflow {
+                    OverflowPolicy::Block => return self.sender.send_blocking(rejected).is_ok(),
+                    OverflowPolicy::DropNewest => {
+                        self.dropped(&rejected);
+                        return true;
+                    }
+                    OverflowPolicy::DropOldest => {
+                        if let Ok(oldest) = self.receiver.try_recv() {
+                            self.dropped(&oldest);
+                        }
+                        event = rejected;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Stops taking events. The writer still gets those already queued.
+    pub fn close(&self) {
+        self.sender.close();
+    }
+
+    fn dropped(&self, event: &AccTx) {
+        let dropped = self
+            .metrics
+            .overflow_dropped_events
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        self.metrics
+            .gaps
+            .record("queue", event.slot(), event.slot(), 1);
+        if let Some(wal) = &self.wal {
+            wal.remove(event);
+        }
+        if dropped.is_power_of_two() {
+            warn!(
+                "Writer queue full, {} events dropped so far ({:?})",
+                dropped, self.overflow
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::AccountUpdate;
+
+    fn account(slot: u64) -> AccTx {
+        AccTx::Acc(AccountUpdate::new(vec![1; 32], vec![2; 32], slot))
+    }
+
+    fn queue(overflow: OverflowPolicy) -> (EventQueue, Receiver<AccTx>, Arc<PluginMetrics>) {
+        let metrics = Arc::new(PluginMetrics::default());
+        let config = QueueConfig {
+            capacity: 1,
+            overflow,
+        };
+        let (queue, receiver) = EventQueue::new(&config, metrics.clone(), None);
+        (queue, receiver, metrics)
+    }
+
+    /// The slot ranges and event counts of the gaps of the queue.
+    fn gaps(metrics: &PluginMetrics) -> Vec<(u64, u64, u64)> {
+        metrics
+            .gaps
+            .list()
+            .into_iter()
+            .filter(|gap| gap.sink == "queue")
+            .map(|gap| (gap.first_slot, gap.last_slot, gap.dropped_events))
+            .collect()
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_queued_event() {
+        let (queue, receiver, metrics) = queue(OverflowPolicy::DropNewest);
+        assert!(queue.push(account(5)));
+        assert!(queue.push(account(6)));
+
+        assert_eq!(receiver.try_recv().unwrap().slot(), 5);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(metrics.overflow_dropped_events.load(Ordering::Relaxed), 1);
+        assert_eq!(gaps(&metrics), [(6, 6, 1)]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room() {
+        let (queue, receiver, metrics) = queue(OverflowPolicy::DropOldest);
+        assert!(queue.push(account(5)));
+        assert!(queue.push(account(6)));
+
+        assert_eq!(receiver.try_recv().unwrap().slot(), 6);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(metrics.overflow_dropped_events.load(Ordering::Relaxed), 1);
+        assert_eq!(gaps(&metrics), [(5, 5, 1)]);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (queue, receiver, metrics) = queue(OverflowPolicy::Block);
+        assert!(queue.push(account(5)));
+        let blocked = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.push(account(6)))
+        };
+
+        assert_eq!(receiver.recv_blocking().unwrap().slot(), 5);
+        assert!(blocked.join().unwrap());
+        assert_eq!(receiver.recv_blocking().unwrap().slot(), 6);
+        assert_eq!(metrics.overflow_dropped_events.load(Ordering::Relaxed), 0);
+        assert!(gaps(&metrics).is_empty());
+
+        queue.close();
+        assert!(!queue.push(account(7)));
+    }
+}
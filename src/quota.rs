@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::Result as GeyserResult;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{config::parse_pubkey, PluginMetrics};
+
+/// Caps the account updates per second of each owning program, so one
+/// program touching millions of accounts cannot starve the others.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProgramQuotasConfig {
+    /// Limit of every program not listed in `programs`, none when unset.
+    pub default_events_per_sec: Option<u64>,
+    pub programs: Vec<ProgramQuotaConfig>,
+    pub excess: QuotaExcess,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProgramQuotaConfig {
+    pub program: String,
+    pub events_per_sec: u64,
+}
+
+/// What happens to updates over a program's quota.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaExcess {
+    #[default]
+    Drop,
+    /// Keeps one in every `keep_one_in` updates over the quota.
+    Sample { keep_one_in: u64 },
+}
+
+/// Counts account updates per owner over one second windows. Updates it
+/// turns away are counted in `quota_dropped_events`.
+#[derive(Debug)]
+pub struct ProgramQuotas {
+    default_limit: Option<u64>,
+    limits: HashMap<Pubkey, u64>,
+    excess: QuotaExcess,
+    window_start: Instant,
+    counts: HashMap<Pubkey, u64>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl ProgramQuotas {
+    pub fn new(config: &ProgramQuotasConfig, metrics: Arc<PluginMetrics>) -> GeyserResult<Self> {
+        let mut limits = HashMap::new();
+        for quota in &config.programs {
+            limits.insert(parse_pubkey(&quota.program)?, quota.events_per_sec);
+        }
+
+        Ok(ProgramQuotas {
+            default_limit: config.default_events_per_sec,
+            limits,
+            excess: config.excess,
+            window_start: Instant::now(),
+            counts: HashMap::new(),
+            metrics,
+        })
+    }
+
+    /// Whether an update of an account owned by `owner` is within quota.
+    pub fn admit(&mut self, owner: &[u8]) -> bool {
+        let owner = match Pubkey::try_from(owner) {
+            Ok(owner) => owner,
+            Err(_) => return true,
+        };
+        let limit = match self.limits.get(&owner).copied().or(self.default_limit) {
+            Some(limit) => limit,
+            None => return true,
+        };
+
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.counts.clear();
+            self.window_start = Instant::now();
+        }
+        let count = self.counts.entry(owner).or_default();
+        *count += 1;
+        if *count <= limit {
+            return true;
+        }
+
+        let admitted = match self.excess {
+            QuotaExcess::Drop => false,
+            QuotaExcess::Sample { keep_one_in } => (*count - limit) % keep_one_in.max(1) == 0,
+        };
+        if !admitted {
+            PluginMetrics::increment(&self.metrics.quota_dropped_events);
+        }
+        admitted
+    }
+}
@@ -0,0 +1,257 @@
+//! Debug record of every notification the validator delivers, taken before
+//! any filtering and without payloads, for checking "missing data" reports
+//! against what actually arrived.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::JoinHandle,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
+    ReplicaTransactionInfoVersions, Result as GeyserResult, SlotStatus,
+};
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RawTraceConfig {
+    /// JSON lines file, one line per notification.
+    pub path: PathBuf,
+    /// Lines the file may fall behind by before further ones are dropped,
+    /// so tracing never holds up the validator.
+    pub capacity: usize,
+}
+
+impl Default for RawTraceConfig {
+    fn default() -> Self {
+        RawTraceConfig {
+            path: PathBuf::from("raw_notifications.jsonl"),
+            capacity: 100_000,
+        }
+    }
+}
+
+/// What the validator said about one notification.
+#[derive(Debug, Serialize)]
+#[serde(tag = "notification", rename_all = "snake_case")]
+pub enum RawNotification {
+    Account {
+        slot: u64,
+        pubkey: String,
+        owner: String,
+        data_len: usize,
+        write_version: u64,
+        is_startup: bool,
+    },
+    Transaction {
+        slot: u64,
+        signature: String,
+        is_vote: bool,
+        index: Option<usize>,
+        account_keys: usize,
+        instructions: usize,
+    },
+    Slot {
+        slot: u64,
+        parent: Option<u64>,
+        status: String,
+    },
+    Block {
+        slot: u64,
+        blockhash: String,
+        rewards: usize,
+        executed_transaction_count: Option<u64>,
+    },
+    EndOfStartup,
+}
+
+impl RawNotification {
+    pub fn account(slot: u64, is_startup: bool, account: &ReplicaAccountInfoVersions) -> Self {
+        let (pubkey, owner, data_len, write_version) = match account {
+            ReplicaAccountInfoVersions::V0_0_1(account) => (
+                account.pubkey,
+                account.owner,
+                account.data.len(),
+                account.write_version,
+            ),
+            ReplicaAccountInfoVersions::V0_0_2(account) => (
+                account.pubkey,
+                account.owner,
+                account.data.len(),
+                account.write_version,
+            ),
+        };
+        RawNotification::Account {
+            slot,
+            pubkey: base58(pubkey),
+            owner: base58(owner),
+            data_len,
+            write_version,
+            is_startup,
+        }
+    }
+
+    pub fn transaction(slot: u64, transaction: &ReplicaTransactionInfoVersions) -> Self {
+        let (signature, is_vote, index, message) = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(transaction) => (
+                transaction.signature,
+                transaction.is_vote,
+                None,
+                transaction.transaction.message(),
+            ),
+            ReplicaTransactionInfoVersions::V0_0_2(transaction) => (
+                transaction.signature,
+                transaction.is_vote,
+                Some(transaction.index),
+                transaction.transaction.message(),
+            ),
+        };
+        RawNotification::Transaction {
+            slot,
+            signature: signature.to_string(),
+            is_vote,
+            index,
+            account_keys: message.account_keys().len(),
+            instructions: message.instructions().len(),
+        }
+    }
+
+    pub fn slot(slot: u64, parent: Option<u64>, status: SlotStatus) -> Self {
+        RawNotification::Slot {
+            slot,
+            parent,
+            status: format!("{:?}", status),
+        }
+    }
+
+    pub fn block(block: &ReplicaBlockInfoVersions) -> Self {
+        match block {
+            ReplicaBlockInfoVersions::V0_0_1(block) => RawNotification::Block {
+                slot: block.slot,
+                blockhash: block.blockhash.to_string(),
+                rewards: block.rewards.len(),
+                executed_transaction_count: None,
+            },
+            ReplicaBlockInfoVersions::V0_0_2(block) => RawNotification::Block {
+                slot: block.slot,
+                blockhash: block.blockhash.to_string(),
+                rewards: block.rewards.len(),
+                executed_transaction_count: Some(block.executed_transaction_count),
+            },
+        }
+    }
+}
+
+fn base58(bytes: &[u8]) -> String {
+    Pubkey::try_from(bytes)
+        .map(|pubkey| pubkey.to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct RawLine<'a> {
+    /// Unix time the callback ran at.
+    received_us: u128,
+    /// Since the previous notification of any kind.
+    gap_us: u128,
+    #[serde(flatten)]
+    notification: &'a RawNotification,
+}
+
+/// Writes [`RawNotification`]s from a thread of its own until dropped.
+#[derive(Debug)]
+pub struct RawTrace {
+    sender: Option<SyncSender<Vec<u8>>>,
+    thread: Option<JoinHandle<()>>,
+    previous: Option<Instant>,
+    dropped: u64,
+}
+
+impl RawTrace {
+    pub fn start(config: &RawTraceConfig) -> GeyserResult<Self> {
+        let file = File::create(&config.path).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!("Failed to create {:?}: {}", config.path, error).into(),
+            )
+        })?;
+        let (sender, receiver) = mpsc::sync_channel(config.capacity.max(1));
+        let thread = std::thread::Builder::new()
+            .name("fusionRawTrace".to_string())
+            .spawn(move || write_lines(receiver, BufWriter::new(file)))
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+        info!("Tracing raw notifications to {:?}", config.path);
+
+        Ok(RawTrace {
+            sender: Some(sender),
+            thread: Some(thread),
+            previous: None,
+            dropped: 0,
+        })
+    }
+
+    pub fn record(&mut self, notification: RawNotification) {
+        let now = Instant::now();
+        let gap_us = self
+            .previous
+            .replace(now)
+            .map(|previous| now.duration_since(previous).as_micros())
+            .unwrap_or_default();
+        let received_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_micros())
+            .unwrap_or_default();
+        let line = RawLine {
+            received_us,
+            gap_us,
+            notification: &notification,
+        };
+        let mut line = match serde_json::to_vec(&line) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.try_send(line).is_ok());
+        if !sent {
+            self.dropped += 1;
+            if self.dropped.is_power_of_two() {
+                warn!(
+                    "Raw notification trace behind, {} lines dropped",
+                    self.dropped
+                );
+            }
+        }
+    }
+}
+
+impl Drop for RawTrace {
+    fn drop(&mut self) {
+        // Disconnecting the channel makes the thread flush and exit.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn write_lines(receiver: Receiver<Vec<u8>>, mut out: BufWriter<File>) {
+    for line in receiver {
+        if let Err(error) = out.write_all(&line) {
+            warn!("Failed to write raw notification trace: {}", error);
+            return;
+        }
+    }
+    if let Err(error) = out.flush() {
+        warn!("Failed to flush raw notification trace: {}", error);
+    }
+}
@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    codec::frame, AccTx, AccountUpdate, Codec, FieldProjection, LabeledPubkey, SlotUpdate,
+    SolTransfer, TransactionUpdate,
+};
+
+/// Serde mirror of the events the `json` and `bincode` codecs write, with
+/// pubkeys, signatures and hashes in base58.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventRecord {
+    Account(AccountRecord),
+    Transaction(TransactionRecord),
+    Slot(SlotRecord),
+    Heartbeat { slot: u64, healthy: bool },
+    Watermark { slot: u64, max_sequence: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountRecord {
+    pub pubkey: String,
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: Vec<u8>,
+    pub write_version: u64,
+    pub txn_signature: Option<String>,
+    pub slot: u64,
+    pub is_startup: bool,
+    pub data_hash: Option<String>,
+    pub data_len: Option<u64>,
+    pub capture_ns: Option<u64>,
+    pub wall_clock_ms: Option<i64>,
+    pub unix_timestamp: Option<i64>,
+    pub trace_id: Option<String>,
+    pub labels: Vec<LabelRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub signature: String,
+    pub slot: u64,
+    pub is_vote: bool,
+    pub index: Option<u64>,
+    /// Bincode-encoded `VersionedTransaction`.
+    pub transaction: Vec<u8>,
+    pub err: Option<String>,
+    pub fee: u64,
+    pub log_messages: Vec<String>,
+    pub compute_units_consumed: Option<u64>,
+    pub capture_ns: Option<u64>,
+    pub wall_clock_ms: Option<i64>,
+    pub unix_timestamp: Option<i64>,
+    pub trace_id: Option<String>,
+    pub labels: Vec<LabelRecord>,
+    pub memos: Vec<String>,
+    pub sol_transfers: Vec<SolTransferRecord>,
+    pub error_code: Option<String>,
+    pub error_category: Option<String>,
+    pub fee_payer: String,
+    /// Signers, the fee payer first.
+    pub signers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotRecord {
+    pub slot: u64,
+    pub parent: Option<u64>,
+    /// `processed`, `confirmed` or `rooted`.
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelRecord {
+    pub pubkey: String,
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolTransferRecord {
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+}
+
+impl EventRecord {
+    /// Converts the events that have a record, as the protobuf codec does.
+    pub fn from_event(event: &AccTx) -> Option<Self> {
+        let record = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                lamports,
+                owner,
+                executable,
+                rent_epoch,
+                data,
+                write_version,
+                txn_signature,
+                slot,
+                is_startup,
+                data_hash,
+                data_len,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+            }) => EventRecord::Account(AccountRecord {
+                pubkey: base58(pubkey),
+                lamports: *lamports,
+                owner: base58(owner),
+                executable: *executable,
+                rent_epoch: *rent_epoch,
+                data: data.clone(),
+                write_version: *write_version,
+                txn_signature: txn_signature.map(|signature| signature.to_string()),
+                slot: *slot,
+                is_startup: *is_startup,
+                data_hash: data_hash.map(|data_hash| data_hash.to_string()),
+                data_len: *data_len,
+                capture_ns: *capture_ns,
+                wall_clock_ms: *wall_clock_ms,
+                unix_timestamp: *unix_timestamp,
+                trace_id: trace_id.map(|trace_id| trace_id.to_string()),
+                labels: label_records(labels),
+            }),
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                index,
+                capture_ns,
+                wall_clock_ms,
+                unix_timestamp,
+                trace_id,
+                labels,
+                memos,
+                sol_transfers,
+                error_class,
+            }) => EventRecord::Transaction(TransactionRecord {
+                signature: signature.to_string(),
+                slot: *slot,
+                is_vote: *is_vote,
+                index: index.map(|index| index as u64),
+                transaction: bincode::serialize(&transaction.to_versioned_transaction()).ok()?,
+                err: transaction_status_meta
+                    .status
+                    .as_ref()
+                    .err()
+                    .map(|err| err.to_string()),
+                fee: transaction_status_meta.fee,
+                log_messages: transaction_status_meta
+                    .log_messages
+                    .clone()
+                    .unwrap_or_default(),
+                compute_units_consumed: transaction_status_meta.compute_units_consumed,
+                capture_ns: *capture_ns,
+                wall_clock_ms: *wall_clock_ms,
+                unix_timestamp: *unix_timestamp,
+                trace_id: trace_id.map(|trace_id| trace_id.to_string()),
+                labels: label_records(labels),
+                memos: memos.clone(),
+                sol_transfers: sol_transfers.iter().map(SolTransferRecord::from).collect(),
+                error_code: error_class.as_ref().map(|class| class.code.clone()),
+                error_category: error_class.as_ref().map(|class| class.category.to_string()),
+                fee_payer: transaction.message().fee_payer().to_string(),
+                signers: event
+                    .signers()
+                    .iter()
+                    .map(|signer| signer.to_string())
+                    .collect(),
+            }),
+            AccTx::Slot(SlotUpdate {
+                slot,
+                parent,
+                status,
+            }) => EventRecord::Slot(SlotRecord {
+                slot: *slot,
+                parent: *parent,
+                status: match status {
+                    SlotStatus::Processed => "processed",
+                    SlotStatus::Confirmed => "confirmed",
+                    SlotStatus::Rooted => "rooted",
+                }
+                .to_string(),
+            }),
+            AccTx::Heartbeat { slot, healthy } => EventRecord::Heartbeat {
+                slot: *slot,
+                healthy: *healthy,
+            },
+            AccTx::Watermark { slot, max_sequence } => EventRecord::Watermark {
+                slot: *slot,
+                max_sequence: *max_sequence,
+            },
+            _ => return None,
+        };
+
+        Some(record)
+    }
+
+    /// Clears the fields `projection` leaves out, by their names in
+    /// [`AccTx`]; fields derived from one go with it.
+    pub fn project(&mut self, projection: &FieldProjection) {
+        if *projection == FieldProjection::default() {
+            return;
+        }
+        match self {
+            EventRecord::Account(account) => {
+                projection.clear("pubkey", &mut account.pubkey);
+                projection.clear("lamports", &mut account.lamports);
+                projection.clear("owner", &mut account.owner);
+                projection.clear("executable", &mut account.executable);
+                projection.clear("rent_epoch", &mut account.rent_epoch);
+                projection.clear("data", &mut account.data);
+                projection.clear("write_version", &mut account.write_version);
+                projection.clear("txn_signature", &mut account.txn_signature);
+                projection.clear("slot", &mut account.slot);
+                projection.clear("is_startup", &mut account.is_startup);
+                projection.clear("data_hash", &mut account.data_hash);
+                projection.clear("data_len", &mut account.data_len);
+                projection.clear("capture_ns", &mut account.capture_ns);
+                projection.clear("wall_clock_ms", &mut account.wall_clock_ms);
+                projection.clear("unix_timestamp", &mut account.unix_timestamp);
+                projection.clear("trace_id", &mut account.trace_id);
+                projection.clear("labels", &mut account.labels);
+            }
+            EventRecord::Transaction(transaction) => {
+                projection.clear("signature", &mut transaction.signature);
+                projection.clear("slot", &mut transaction.slot);
+                projection.clear("is_vote", &mut transaction.is_vote);
+                projection.clear("index", &mut transaction.index);
+                projection.clear("transaction", &mut transaction.transaction);
+                projection.clear("transaction", &mut transaction.fee_payer);
+                projection.clear("transaction", &mut transaction.signers);
+                projection.clear("transaction_status_meta", &mut transaction.err);
+                projection.clear("transaction_status_meta", &mut transaction.fee);
+                projection.clear("transaction_status_meta", &mut transaction.log_messages);
+                projection.clear(
+                    "transaction_status_meta",
+                    &mut transaction.compute_units_consumed,
+                );
+                projection.clear("capture_ns", &mut transaction.capture_ns);
+                projection.clear("wall_clock_ms", &mut transaction.wall_clock_ms);
+                projection.clear("unix_timestamp", &mut transaction.unix_timestamp);
+                projection.clear("trace_id", &mut transaction.trace_id);
+                projection.clear("labels", &mut transaction.labels);
+                projection.clear("memos", &mut transaction.memos);
+                projection.clear("sol_transfers", &mut transaction.sol_transfers);
+                projection.clear("error_class", &mut transaction.error_code);
+                projection.clear("error_class", &mut transaction.error_category);
+            }
+            EventRecord::Slot(slot) => {
+                projection.clear("slot", &mut slot.slot);
+                projection.clear("parent", &mut slot.parent);
+                projection.clear("status", &mut slot.status);
+            }
+            EventRecord::Heartbeat { slot, healthy } => {
+                projection.clear("slot", slot);
+                projection.clear("healthy", healthy);
+            }
+            EventRecord::Watermark { slot, max_sequence } => {
+                projection.clear("slot", slot);
+                projection.clear("max_sequence", max_sequence);
+            }
+        }
+    }
+}
+
+impl From<&SolTransfer> for SolTransferRecord {
+    fn from(transfer: &SolTransfer) -> Self {
+        SolTransferRecord {
+            from: transfer.from.to_string(),
+            to: transfer.to.to_string(),
+            lamports: transfer.lamports,
+        }
+    }
+}
+
+fn label_records(labels: &[LabeledPubkey]) -> Vec<LabelRecord> {
+    labels
+        .iter()
+        .map(|labeled| LabelRecord {
+            pubkey: labeled.pubkey.to_string(),
+            name: labeled.label.name.clone(),
+            tags: labeled.label.tags.clone(),
+        })
+        .collect()
+}
+
+fn base58(pubkey: &[u8]) -> String {
+    match Pubkey::try_from(pubkey) {
+        Ok(pubkey) => pubkey.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// [`EventRecord`]s as JSON lines, with the fields the projection leaves
+/// out cleared.
+#[derive(Debug)]
+pub struct JsonCodec {
+    projection: FieldProjection,
+}
+
+impl JsonCodec {
+    pub fn new(projection: FieldProjection) -> Self {
+        JsonCodec { projection }
+    }
+}
+
+impl Codec for JsonCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        let mut record = EventRecord::from_event(event)?;
+        record.project(&self.projection);
+        let mut line = serde_json::to_vec(&record).ok()?;
+        line.push(b'\n');
+        Some(line)
+    }
+
+    fn projects(&self) -> bool {
+        true
+    }
+}
+
+/// [`EventRecord`]s in bincode, length-prefixed as bincode records do not
+/// delimit themselves, with the fields the projection leaves out cleared.
+#[derive(Debug)]
+pub struct BincodeCodec {
+    projection: FieldProjection,
+}
+
+impl BincodeCodec {
+    pub fn new(projection: FieldProjection) -> Self {
+        BincodeCodec { projection }
+    }
+}
+
+impl Codec for BincodeCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        let mut record = EventRecord::from_event(event)?;
+        record.project(&self.projection);
+        Some(frame(&bincode::serialize(&record).ok()?))
+    }
+
+    fn framed(&self) -> bool {
+        true
+    }
+
+    fn projects(&self) -> bool {
+        true
+    }
+}
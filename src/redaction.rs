@@ -0,0 +1,340 @@
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::Result as GeyserResult;
+use solana_sdk::{
+    hash::hash,
+    message::{v0::LoadedAddresses, SanitizedMessage, SimpleAddressLoader, VersionedMessage},
+    pubkey::Pubkey,
+    transaction::SanitizedTransaction,
+};
+use solana_transaction_status::TransactionStatusMeta;
+
+use crate::{
+    config::parse_pubkey, AccTx, AccountUpdate, TransactionUpdate, MEMO_PROGRAM_ID,
+    MEMO_V1_PROGRAM_ID,
+};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub rules: Vec<RedactionRule>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionRule {
+    /// Base58 program id. Matches accounts owned by it and transactions
+    /// invoking it; the rule applies to every event when unset.
+    pub program: Option<String>,
+    /// Fields replaced wholesale by their SHA-256 hash.
+    pub fields: Vec<RedactedField>,
+    /// Ranges of account data overwritten in place with the leading bytes
+    /// of their hash, so that offsets of the remaining layout still hold.
+    pub data_ranges: Vec<ByteRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactedField {
+    Pubkey,
+    Owner,
+    Data,
+    LogMessages,
+    /// Memos extracted onto the event, the data of the memo instructions
+    /// in the transaction and the memos the Memo program logged.
+    Memos,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ByteRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Redactor {
+    rules: Vec<(Option<Pubkey>, RedactionRule)>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> GeyserResult<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let program = rule.program.as_deref().map(parse_pubkey).transpose()?;
+                Ok((program, rule.clone()))
+            })
+            .collect::<GeyserResult<Vec<_>>>()?;
+
+        Ok(Redactor { rules })
+    }
+
+    /// Redacts an account or transaction as it arrives. Derived events are
+    /// left alone: they are built from events this already redacted.
+    pub fn redact(&self, event: &mut AccTx) {
+        for (program, rule) in &self.rules {
+            match event {
+                AccTx::Acc(AccountUpdate {
+                    pubkey,
+                    owner,
+                    data,
+                    ..
+                }) => {
+                    if let Some(program) = program {
+                        if program.as_ref() != owner.as_slice() {
+                            continue;
+                        }
+                    }
+
+                    for range in &rule.data_ranges {
+                        redact_range(data, range);
+                    }
+                    for field in &rule.fields {
+                        match field {
+                            RedactedField::Pubkey => *pubkey = hash_bytes(pubkey),
+                            RedactedField::Owner => *owner = hash_bytes(owner),
+                            RedactedField::Data => *data = hash_bytes(data),
+                            RedactedField::LogMessages | RedactedField::Memos => {}
+                        }
+                    }
+                }
+                AccTx::Tx(TransactionUpdate {
+                    transaction,
+                    transaction_status_meta,
+                    memos,
+                    ..
+                }) => {
+                    if let Some(program) = program {
+                        let invoked = transaction
+                            .message()
+                            .program_instructions_iter()
+                            .any(|(program_id, _)| program_id == program);
+                        if !invoked {
+                            continue;
+                        }
+                    }
+
+                    if rule.fields.contains(&RedactedField::LogMessages) {
+                        if let Some(log_messages) = &mut transaction_status_meta.log_messages {
+                            for log_message in log_messages.iter_mut() {
+                                *log_message = hash(log_message.as_bytes()).to_string();
+                            }
+                        }
+                    }
+                    if rule.fields.contains(&RedactedField::Memos) {
+                        for memo in memos.iter_mut() {
+                            *memo = hash(memo.as_bytes()).to_string();
+                        }
+                        redact_memo_instructions(transaction, transaction_status_meta);
+                    }
+                }
+                // Derived from accounts and transactions redacted before the
+                // pipeline stages saw them.
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Replaces the data of every memo instruction, including those invoked
+/// through CPI, with the hash of the memo, as extracted memos are, and
+/// hashes the lines the Memo program logged them in.
+fn redact_memo_instructions(
+    transaction: &mut SanitizedTransaction,
+    meta: &mut TransactionStatusMeta,
+) {
+    let account_keys: Vec<Pubkey> = transaction
+        .message()
+        .account_keys()
+        .iter()
+        .copied()
+        .collect();
+    let is_memo = |program_id_index: u8| {
+        account_keys
+            .get(program_id_index as usize)
+            .map_or(false, |program_id| {
+                *program_id == MEMO_PROGRAM_ID || *program_id == MEMO_V1_PROGRAM_ID
+            })
+    };
+    let redact_data = |data: &mut Vec<u8>| *data = hash(data).to_string().into_bytes();
+
+    for inner_instruction in meta
+        .inner_instructions
+        .iter_mut()
+        .flatten()
+        .flat_map(|inner_instructions| &mut inner_instructions.instructions)
+    {
+        let instruction = &mut inner_instruction.instruction;
+        if is_memo(instruction.program_id_index) {
+            redact_data(&mut instruction.data);
+        }
+    }
+    for log_message in meta.log_messages.iter_mut().flatten() {
+        if log_message.starts_with("Program log: Memo (len ") {
+            *log_message = hash(log_message.as_bytes()).to_string();
+        }
+    }
+
+    let mut versioned = transaction.to_versioned_transaction();
+    let instructions = match &mut versioned.message {
+        VersionedMessage::Legacy(message) => &mut message.instructions,
+        VersionedMessage::V0(message) => &mut message.instructions,
+    };
+    let mut redacted = false;
+    for instruction in instructions.iter_mut() {
+        if is_memo(instruction.program_id_index) {
+            redact_data(&mut instruction.data);
+            redacted = true;
+        }
+    }
+    if !redacted {
+        return;
+    }
+
+    let address_loader = match transaction.message() {
+        SanitizedMessage::Legacy(_) => SimpleAddressLoader::Disabled,
+        SanitizedMessage::V0(message) => {
+            SimpleAddressLoader::Enabled(LoadedAddresses::clone(&message.loaded_addresses))
+        }
+    };
+    // Only instruction data changed, which sanitizing does not look at, so
+    // this cannot fail where the original transaction passed.
+    if let Ok(scrubbed) = SanitizedTransaction::try_create(
+        versioned,
+        *transaction.message_hash(),
+        Some(transaction.is_simple_vote_transaction()),
+        address_loader,
+    ) {
+        *transaction = scrubbed;
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> Vec<u8> {
+    hash(bytes).to_bytes().to_vec()
+}
+
+fn redact_range(data: &mut [u8], range: &ByteRange) {
+    let end = range.end.min(data.len());
+    if range.start >= end {
+        return;
+    }
+
+    let digest = hash(&data[range.start..end]).to_bytes();
+    for (byte, replacement) in data[range.start..end]
+        .iter_mut()
+        .zip(digest.iter().chain(std::iter::repeat(&0)))
+    {
+        *byte = *replacement;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        hash::Hash, instruction::Instruction, message::v0, signature::Signature,
+        transaction::VersionedTransaction,
+    };
+
+    use super::*;
+    use crate::{CacheUpdate, LifecycleTracker};
+
+    fn redactor(program: Option<Pubkey>, fields: Vec<RedactedField>) -> Redactor {
+        Redactor::new(&RedactionConfig {
+            rules: vec![RedactionRule {
+                program: program.map(|program| program.to_string()),
+                fields,
+                data_ranges: Vec::new(),
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn clamps_data_ranges() {
+        let original: Vec<u8> = (0..8).collect();
+
+        let mut data = original.clone();
+        redact_range(&mut data, &ByteRange { start: 8, end: 12 });
+        redact_range(&mut data, &ByteRange { start: 10, end: 20 });
+        redact_range(&mut data, &ByteRange { start: 5, end: 5 });
+        assert_eq!(data, original);
+
+        redact_range(&mut data, &ByteRange { start: 4, end: 100 });
+        assert_eq!(data[..4], original[..4]);
+        assert_eq!(data[4..], hash(&original[4..]).to_bytes()[..4]);
+    }
+
+    #[test]
+    fn scrubs_memos_of_v0_transactions() {
+        let payer = Pubkey::new_unique();
+        let memo = Instruction::new_with_bytes(MEMO_PROGRAM_ID, b"secret memo", Vec::new());
+        let message = v0::Message::try_compile(&payer, &[memo], &[], Hash::new_unique()).unwrap();
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction {
+                signatures: vec![Signature::new_unique()],
+                message: VersionedMessage::V0(message),
+            },
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Disabled,
+        )
+        .unwrap();
+        let meta = TransactionStatusMeta {
+            log_messages: Some(vec![
+                "Program log: Memo (len 11): \"secret memo\"".to_string(),
+                "Program consumed 1000 compute units".to_string(),
+            ]),
+            ..TransactionStatusMeta::default()
+        };
+        let mut update = TransactionUpdate::new(7, transaction, meta);
+        update.memos = vec!["secret memo".to_string()];
+        let mut event = AccTx::Tx(update);
+
+        redactor(Some(MEMO_PROGRAM_ID), vec![RedactedField::Memos]).redact(&mut event);
+
+        let update = match &event {
+            AccTx::Tx(update) => update,
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            update.transaction.message(),
+            SanitizedMessage::V0(_)
+        ));
+        let data = &update.transaction.message().instructions()[0].data;
+        assert_eq!(data, &hash(b"secret memo").to_string().into_bytes());
+        assert_eq!(update.memos, vec![hash(b"secret memo").to_string()]);
+        let log_messages = update
+            .transaction_status_meta
+            .log_messages
+            .as_ref()
+            .unwrap();
+        assert!(log_messages.iter().all(|line| !line.contains("secret")));
+        assert_eq!(log_messages[1], "Program consumed 1000 compute units");
+        assert!(!format!("{:?}", event).contains("secret memo"));
+    }
+
+    #[test]
+    fn derived_events_carry_redacted_fields() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut account = AccTx::Acc(
+            AccountUpdate::new(pubkey.to_bytes().to_vec(), owner.to_bytes().to_vec(), 7)
+                .with_lamports(1),
+        );
+        redactor(None, vec![RedactedField::Pubkey, RedactedField::Owner]).redact(&mut account);
+
+        let derived = LifecycleTracker.observe(&account, &CacheUpdate::Applied(None));
+        assert_eq!(derived.len(), 1);
+        match &derived[0] {
+            AccTx::Lifecycle {
+                pubkey: derived_pubkey,
+                owner: derived_owner,
+                ..
+            } => {
+                assert_eq!(*derived_pubkey, hash_bytes(pubkey.as_ref()));
+                assert_eq!(*derived_owner, hash_bytes(owner.as_ref()));
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+use crate::{AccTx, SlotUpdate};
+
+/// Tracks the slots seen since the latest root along with their parents.
+/// When a new root arrives, every tracked slot below it that is not one of
+/// its ancestors was on a fork that died, and is reported as
+/// [`AccTx::SlotRollback`] so consumers can invalidate what they stored
+/// for it.
+#[derive(Debug, Default)]
+pub struct RollbackTracker {
+    /// Unrooted slots, with their parent when known.
+    slots: BTreeMap<u64, Option<u64>>,
+    root: Option<u64>,
+}
+
+impl RollbackTracker {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let slot = event.slot();
+        if self.root.is_some_and(|root| slot <= root) || event.is_control() {
+            return Vec::new();
+        }
+        let parent = match event {
+            AccTx::Slot(SlotUpdate { parent, .. }) => *parent,
+            _ => None,
+        };
+        let known = self.slots.entry(slot).or_default();
+        if known.is_none() {
+            *known = parent;
+        }
+
+        match event {
+            AccTx::Slot(SlotUpdate {
+                status: SlotStatus::Rooted,
+                ..
+            }) => self.root(slot),
+            _ => Vec::new(),
+        }
+    }
+
+    fn root(&mut self, root: u64) -> Vec<AccTx> {
+        // Slots past a gap in the known ancestry cannot be judged.
+        let mut oldest_ancestor = root;
+        let mut ancestors = vec![root];
+        while let Some(Some(parent)) = self.slots.get(&oldest_ancestor) {
+            oldest_ancestor = *parent;
+            ancestors.push(oldest_ancestor);
+        }
+
+        let unrooted = self.slots.split_off(&(root + 1));
+        let rolled_back = std::mem::replace(&mut self.slots, unrooted)
+            .into_keys()
+            .filter(|slot| *slot > oldest_ancestor && !ancestors.contains(slot))
+            .map(|slot| AccTx::SlotRollback { slot })
+            .collect();
+        self.root = Some(root);
+        rolled_back
+    }
+}
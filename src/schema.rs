@@ -0,0 +1,185 @@
+//! Schemas of what the plugin emits, for consumers to generate code from.
+//! Only the protobuf codec has a schema here; the json and bincode codecs
+//! follow [`crate::EventRecord`], and the debug and pretty codecs are for
+//! people.
+
+use serde_json::{json, Value};
+
+use crate::SCHEMA_VERSION;
+
+/// Messages of the `protobuf` codec, the gRPC service and the stream
+/// handshake, matching [`crate::proto`] and [`crate::Hello`].
+pub const PROTOBUF_SCHEMA: &str = r#"syntax = "proto3";
+
+package fusion_engine;
+
+service Geyser {
+  rpc Subscribe(stream SubscribeRequest) returns (stream SubscribeUpdate);
+  rpc SubscribeAccounts(AccountsFilter) returns (stream SubscribeUpdate);
+  rpc SubscribeTransactions(TransactionsFilter) returns (stream SubscribeUpdate);
+}
+
+message SubscribeRequest {
+  AccountsFilter accounts = 1;
+  TransactionsFilter transactions = 2;
+  bool slots = 3;
+  string group = 4;
+}
+
+message AccountsFilter {
+  repeated string pubkeys = 1;
+  repeated string owners = 2;
+}
+
+message TransactionsFilter {
+  bool include_votes = 1;
+  bool include_failed = 2;
+  repeated string account_include = 3;
+}
+
+message SubscribeUpdate {
+  oneof update {
+    AccountUpdate account = 1;
+    TransactionUpdate transaction = 2;
+    SlotUpdate slot = 3;
+    FiltersApplied filters_applied = 4;
+  }
+}
+
+message AccountUpdate {
+  bytes pubkey = 1;
+  uint64 lamports = 2;
+  bytes owner = 3;
+  bool executable = 4;
+  uint64 rent_epoch = 5;
+  bytes data = 6;
+  uint64 write_version = 7;
+  optional bytes txn_signature = 8;
+  uint64 slot = 9;
+  bool is_startup = 10;
+  optional bytes data_hash = 11;
+  optional string trace_id = 12;
+  // Length of the data data_hash replaces.
+  optional uint64 data_len = 13;
+}
+
+message TransactionUpdate {
+  bytes signature = 1;
+  uint64 slot = 2;
+  bool is_vote = 3;
+  optional uint64 index = 4;
+  // Bincode-encoded VersionedTransaction.
+  bytes transaction = 5;
+  optional string err = 6;
+  uint64 fee = 7;
+  repeated string log_messages = 8;
+  optional uint64 compute_units_consumed = 9;
+  optional string trace_id = 10;
+  // Set when errors are classified, e.g. "InstructionError:Custom(6001)".
+  optional string error_code = 11;
+  optional string error_category = 12;
+  bytes fee_payer = 13;
+  // Signers, the fee payer first.
+  repeated bytes signers = 14;
+  // Keys of the message, then the writable and readonly ones loaded from
+  // lookup tables, in the order instructions index them.
+  repeated bytes account_keys = 15;
+  bytes recent_blockhash = 16;
+  repeated CompiledInstruction instructions = 17;
+}
+
+// A top-level instruction, its program and accounts indexing
+// TransactionUpdate.account_keys.
+message CompiledInstruction {
+  uint32 program_id_index = 1;
+  // One byte per account index.
+  bytes accounts = 2;
+  bytes data = 3;
+}
+
+enum SlotStatus {
+  PROCESSED = 0;
+  CONFIRMED = 1;
+  ROOTED = 2;
+}
+
+message SlotUpdate {
+  uint64 slot = 1;
+  optional uint64 parent = 2;
+  SlotStatus status = 3;
+}
+
+message FiltersApplied {
+  uint64 slot = 1;
+}
+
+message Hello {
+  uint32 min_version = 1;
+  uint32 max_version = 2;
+  repeated string codecs = 3;
+  SubscribeRequest filters = 4;
+}
+
+message HandshakeResponse {
+  oneof result {
+    Accepted accepted = 1;
+    Rejected rejected = 2;
+  }
+}
+
+message Accepted {
+  uint32 version = 1;
+  string codec = 2;
+  uint64 slot = 3;
+}
+
+message Rejected {
+  string reason = 1;
+  uint32 min_version = 2;
+  uint32 max_version = 3;
+  repeated string codecs = 4;
+}
+"#;
+
+/// The protobuf schema headed by the versions it belongs to.
+pub fn protobuf_schema() -> String {
+    format!(
+        "// fusion-engine-geyser {}, schema version {}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        SCHEMA_VERSION,
+        PROTOBUF_SCHEMA
+    )
+}
+
+/// JSON schema of the metadata sent alongside every message, as Kafka
+/// headers.
+pub fn envelope_json_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("fusion-engine-geyser/envelope/v{}", SCHEMA_VERSION),
+        "title": "Envelope",
+        "description": format!(
+            "Headers of fusion-engine-geyser {} messages",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "type": "object",
+        "properties": {
+            "event_type": {
+                "type": "string",
+                "description": "snake_case event kind, e.g. account, transaction, slot, heartbeat",
+            },
+            "slot": { "type": "string", "pattern": "^[0-9]+$" },
+            "schema_version": { "const": SCHEMA_VERSION.to_string() },
+            "owner": {
+                "type": "string",
+                "description": "Base58 owner program of account and lifecycle events",
+            },
+            "trace_id": {
+                "type": "string",
+                "pattern": "^[0-9a-f]{32}$",
+                "description": "Set on accounts and transactions when trace ids are on",
+            },
+        },
+        "required": ["event_type", "slot", "schema_version"],
+    })
+}
@@ -0,0 +1,377 @@
+//! Single-producer ring buffer in a memory-mapped file, for consumers on
+//! the same host that poll for events instead of reading a socket. The
+//! plugin writes it through [`ShmRingSink`](crate::ShmRingSink); consumers
+//! read it with [`RingReader`].
+//!
+//! The file starts with a 128 byte header: the magic `FEGRING\0`, the
+//! layout version (`u32`), the capacity of the data region (`u64` at 16),
+//! the epoch the writer started at (`u64` at 24), and two positions written
+//! seqlock style: `claimed` (`u64` at 64) is raised before a record is
+//! written and `published` (`u64` at 72) once it is complete. Positions
+//! count bytes since the epoch started and wrap around the data region,
+//! which follows the header.
+//!
+//! Records are 16 byte aligned: a `u32` length, 4 reserved bytes, then the
+//! payload. A length of `u32::MAX` means the record continues at the start
+//! of the region. Integers are in native byte order. The writer never waits
+//! for readers; a reader whose record was overwritten while copying it, or
+//! who fell a whole region behind, is told and skips to the newest record.
+//! A restarted writer starts a new epoch, which readers are told about too.
+//! The length of a file never changes while it may be mapped: a writer
+//! restarting with another capacity retires the old file and creates a new
+//! one in its place.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::Path,
+    ptr,
+    sync::atomic::{fence, AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use memmap2::{Mmap, MmapMut};
+
+pub const RING_MAGIC: [u8; 8] = *b"FEGRING\0";
+pub const RING_VERSION: u32 = 1;
+const HEADER_LEN: usize = 128;
+const CAPACITY_OFFSET: usize = 16;
+const EPOCH_OFFSET: usize = 24;
+const CLAIMED_OFFSET: usize = 64;
+const PUBLISHED_OFFSET: usize = 72;
+const RECORD_HEADER_LEN: u64 = 8;
+const ALIGN: u64 = 16;
+const WRAP: u32 = u32::MAX;
+
+fn aligned(len: u64) -> u64 {
+    (len + ALIGN - 1) & !(ALIGN - 1)
+}
+
+fn counter(map: &[u8], offset: usize) -> &AtomicU64 {
+    // Safety: the mapping is page aligned and outlives the reference.
+    unsafe { &*(map.as_ptr().add(offset) as *const AtomicU64) }
+}
+
+fn new_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Starts a new epoch in a ring file about to be replaced, so that its
+/// readers reopen the path.
+fn retire(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    if file.metadata()?.len() < HEADER_LEN as u64 {
+        return Ok(());
+    }
+    // Safety: the length of the file does not change while it is mapped.
+    let map = unsafe { MmapMut::map_mut(&file)? };
+    if map.len() >= HEADER_LEN && map[..8] == RING_MAGIC {
+        counter(&map, EPOCH_OFFSET).store(new_epoch(), Ordering::Release);
+    }
+    Ok(())
+}
+
+/// The writing end, owned by the plugin.
+#[derive(Debug)]
+pub struct RingWriter {
+    map: MmapMut,
+    capacity: u64,
+    position: u64,
+}
+
+impl RingWriter {
+    /// Creates the file or starts a new epoch in it, so readers mapping it
+    /// already notice. `capacity` is rounded up to a power of two; a file
+    /// of another capacity is replaced rather than resized, as shrinking it
+    /// would fault the reads of processes mapping it.
+    pub fn create(path: &Path, capacity: usize) -> io::Result<Self> {
+        let capacity = capacity.max(ALIGN as usize).next_power_of_two() as u64;
+        let len = HEADER_LEN as u64 + capacity;
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.len() != len => {
+                retire(path)?;
+                fs::remove_file(path)?;
+            }
+            _ => {}
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() != len {
+            // Only a file just created, which nobody has mapped yet.
+            file.set_len(len)?;
+        }
+        // Safety: the file is only written through this mapping, and its
+        // length does not change while it is mapped.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+
+        map[..8].copy_from_slice(&RING_MAGIC);
+        map[8..12].copy_from_slice(&RING_VERSION.to_ne_bytes());
+        map[CAPACITY_OFFSET..CAPACITY_OFFSET + 8].copy_from_slice(&capacity.to_ne_bytes());
+        let writer = RingWriter {
+            map,
+            capacity,
+            position: 0,
+        };
+        writer.counter(CLAIMED_OFFSET).store(0, Ordering::Relaxed);
+        writer.counter(PUBLISHED_OFFSET).store(0, Ordering::Relaxed);
+        writer
+            .counter(EPOCH_OFFSET)
+            .store(new_epoch(), Ordering::Release);
+        Ok(writer)
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Bytes written since the epoch started.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        counter(&self.map, offset)
+    }
+
+    /// Returns whether `payload` fits the region at all.
+    pub fn push(&mut self, payload: &[u8]) -> bool {
+        let size = aligned(RECORD_HEADER_LEN + payload.len() as u64);
+        if size > self.capacity || payload.len() as u64 >= WRAP as u64 {
+            return false;
+        }
+        let offset = self.position % self.capacity;
+        let skip = match self.capacity - offset {
+            remaining if remaining < size => remaining,
+            _ => 0,
+        };
+        let end = self.position + skip + size;
+
+        self.counter(CLAIMED_OFFSET).store(end, Ordering::Relaxed);
+        fence(Ordering::Release);
+        if skip > 0 {
+            self.write_at(offset, &WRAP.to_ne_bytes());
+        }
+        let offset = (self.position + skip) % self.capacity;
+        self.write_at(offset, &(payload.len() as u32).to_ne_bytes());
+        self.write_at(offset + RECORD_HEADER_LEN, payload);
+        self.counter(PUBLISHED_OFFSET).store(end, Ordering::Release);
+        self.position = end;
+        true
+    }
+
+    fn write_at(&mut self, offset: u64, bytes: &[u8]) {
+        let start = HEADER_LEN + offset as usize;
+        self.map[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+/// What [`RingReader::next`] found.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RingRead<'a> {
+    Record(&'a [u8]),
+    /// Nothing new yet.
+    Empty,
+    /// Records were overwritten before they were read; reading resumes at
+    /// the newest one.
+    Overrun,
+    /// The writer restarted, maybe with another capacity; open a new
+    /// reader to follow it.
+    Restarted,
+}
+
+/// The reading end, for consumers. Readers never write the file, so any
+/// number of them can follow the ring at their own pace.
+#[derive(Debug)]
+pub struct RingReader {
+    map: Mmap,
+    capacity: u64,
+    epoch: u64,
+    position: u64,
+}
+
+impl RingReader {
+    /// Starts at the newest record, skipping what is already in the ring.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: writers never change the length of a file, they replace
+        // it with a new one, so the mapping stays whole.
+        let map = unsafe { Mmap::map(&file)? };
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        if map.len() < HEADER_LEN || map[..8] != RING_MAGIC {
+            return Err(invalid("not a ring buffer file"));
+        }
+        if map[8..12] != RING_VERSION.to_ne_bytes() {
+            return Err(invalid("unsupported ring buffer version"));
+        }
+        let mut capacity = [0; 8];
+        capacity.copy_from_slice(&map[CAPACITY_OFFSET..CAPACITY_OFFSET + 8]);
+        let capacity = u64::from_ne_bytes(capacity);
+        if map.len() < HEADER_LEN + capacity as usize || !capacity.is_power_of_two() {
+            return Err(invalid("truncated ring buffer file"));
+        }
+
+        let mut reader = RingReader {
+            map,
+            capacity,
+            epoch: 0,
+            position: 0,
+        };
+        reader.epoch = reader.counter(EPOCH_OFFSET).load(Ordering::Acquire);
+        reader.position = reader.counter(PUBLISHED_OFFSET).load(Ordering::Acquire);
+        Ok(reader)
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        counter(&self.map, offset)
+    }
+
+    /// Copies the next record into `buffer`, which is reused between calls
+    /// to avoid allocating per record.
+    pub fn next<'a>(&mut self, buffer: &'a mut Vec<u8>) -> RingRead<'a> {
+        if self.counter(EPOCH_OFFSET).load(Ordering::Acquire) != self.epoch {
+            return RingRead::Restarted;
+        }
+        loop {
+            let published = self.counter(PUBLISHED_OFFSET).load(Ordering::Acquire);
+            if self.position >= published {
+                return RingRead::Empty;
+            }
+            if published - self.position > self.capacity {
+                self.position = published;
+                return RingRead::Overrun;
+            }
+
+            let offset = self.position % self.capacity;
+            let len = self.read_u32(offset);
+            if len == WRAP {
+                self.position += self.capacity - offset;
+                continue;
+            }
+            let size = aligned(RECORD_HEADER_LEN + len as u64);
+            if size <= self.capacity - offset {
+                buffer.resize(len as usize, 0);
+                // Safety: in bounds, checked against the capacity above.
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        self.map
+                            .as_ptr()
+                            .add(HEADER_LEN + (offset + RECORD_HEADER_LEN) as usize),
+                        buffer.as_mut_ptr(),
+                        len as usize,
+                    );
+                }
+            }
+            // The copy is only whole if the writer has not claimed the
+            // bytes since; a length torn the same way fails this too.
+            fence(Ordering::Acquire);
+            let claimed = self.counter(CLAIMED_OFFSET).load(Ordering::Relaxed);
+            if claimed > self.position + self.capacity || size > self.capacity - offset {
+                self.position = self.counter(PUBLISHED_OFFSET).load(Ordering::Acquire);
+                return RingRead::Overrun;
+            }
+            self.position += size;
+            return RingRead::Record(buffer.as_slice());
+        }
+    }
+
+    fn read_u32(&self, offset: u64) -> u32 {
+        // Safety: records are 16 byte aligned within the mapping.
+        unsafe {
+            ptr::read_volatile(self.map.as_ptr().add(HEADER_LEN + offset as usize) as *const u32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn ring_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("fusion-ring-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn wraps_around_the_region() {
+        let path = ring_path("wrap");
+        let mut writer = RingWriter::create(&path, 64).unwrap();
+        let mut reader = RingReader::open(&path).unwrap();
+        let mut buffer = Vec::new();
+
+        // Three records of 16 bytes leave 16 bytes at the end, too few for
+        // the fourth, which continues at the start.
+        for payload in [b"record 1", b"record 2", b"record 3"] {
+            assert!(writer.push(payload));
+            assert_eq!(reader.next(&mut buffer), RingRead::Record(payload));
+        }
+        assert!(writer.push(b"a record of 20 bytes"));
+        assert_eq!(writer.position(), 96);
+        assert_eq!(
+            reader.next(&mut buffer),
+            RingRead::Record(b"a record of 20 bytes")
+        );
+        assert_eq!(reader.next(&mut buffer), RingRead::Empty);
+        assert!(!writer.push(&[0; 64]));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn skips_to_the_newest_record_after_an_overrun() {
+        let path = ring_path("overrun");
+        let mut writer = RingWriter::create(&path, 64).unwrap();
+        let mut reader = RingReader::open(&path).unwrap();
+        let mut buffer = Vec::new();
+
+        for index in 0..5u64 {
+            assert!(writer.push(&index.to_ne_bytes()));
+        }
+        assert_eq!(reader.next(&mut buffer), RingRead::Overrun);
+        assert_eq!(reader.next(&mut buffer), RingRead::Empty);
+        assert!(writer.push(b"record 6"));
+        assert_eq!(reader.next(&mut buffer), RingRead::Record(b"record 6"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tells_readers_about_a_restarted_writer() {
+        let path = ring_path("restart");
+        let mut writer = RingWriter::create(&path, 64).unwrap();
+        let mut reader = RingReader::open(&path).unwrap();
+        let mut buffer = Vec::new();
+        assert!(writer.push(b"record 1"));
+        assert_eq!(reader.next(&mut buffer), RingRead::Record(b"record 1"));
+
+        // Same capacity: the file is reused.
+        drop(writer);
+        let mut writer = RingWriter::create(&path, 64).unwrap();
+        assert!(writer.push(b"record 2"));
+        assert_eq!(reader.next(&mut buffer), RingRead::Restarted);
+        let mut reader = RingReader::open(&path).unwrap();
+        assert_eq!(reader.next(&mut buffer), RingRead::Empty);
+
+        // Smaller capacity: the old file is retired, and its readers keep
+        // a whole mapping.
+        drop(writer);
+        let mut writer = RingWriter::create(&path, 32).unwrap();
+        assert_eq!(reader.next(&mut buffer), RingRead::Restarted);
+        let mut reader = RingReader::open(&path).unwrap();
+        assert!(writer.push(b"record 3"));
+        assert_eq!(reader.next(&mut buffer), RingRead::Record(b"record 3"));
+        assert_eq!(fs::metadata(&path).unwrap().len(), HEADER_LEN as u64 + 32);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+use std::{io, path::PathBuf, sync::Arc};
+
+use log::info;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+
+use crate::{Codec, CodecRegistry, EncodedEvent, PluginMetrics, RingWriter, Sink, SinkConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShmRingConfig {
+    /// Ideally on a tmpfs such as `/dev/shm`, so pages are never written
+    /// back to disk.
+    pub path: PathBuf,
+    /// Bytes of events the ring holds, rounded up to a power of two.
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+fn default_capacity() -> usize {
+    64 << 20
+}
+
+/// Writes encoded events to a shared-memory ring, see [`crate::shm_ring`].
+/// Writing never blocks; readers that fall behind lose events instead.
+#[derive(Debug)]
+pub struct ShmRingSink {
+    path: PathBuf,
+    ring: RingWriter,
+    codec: Arc<dyn Codec>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl ShmRingSink {
+    pub fn create(
+        config: &ShmRingConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let ring = RingWriter::create(&config.path, config.capacity).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!("Failed to map ring buffer {:?}: {}", config.path, error).into(),
+            )
+        })?;
+        info!(
+            "Writing events to a {} byte ring buffer at {:?}",
+            ring.capacity(),
+            config.path
+        );
+
+        Ok(ShmRingSink {
+            path: config.path.clone(),
+            ring,
+            codec: codecs.build(&config.sink, "protobuf")?,
+            metrics,
+        })
+    }
+}
+
+impl Sink for ShmRingSink {
+    fn name(&self) -> &'static str {
+        "shm_ring"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let record = match event.encode(&self.codec) {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        if !self.ring.push(&record) {
+            let slot = event.event.slot();
+            PluginMetrics::increment(&self.metrics.dropped_events);
+            self.metrics.gaps.record("shm_ring", slot, slot, 1);
+            self.metrics.record_error(format!(
+                "Event of {} bytes does not fit the ring buffer of {} bytes",
+                record.len(),
+                self.ring.capacity()
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "path": self.path,
+            "capacity": self.ring.capacity(),
+            "position": self.ring.position(),
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! Manifests of archive segments, written next to each file of a file sink
+//! with `signature_bloom` set as `<file>.manifest.json`. A manifest holds
+//! the slot range of the segment and a bloom filter of the transaction
+//! signatures in it, so that `fusion-cat --key SIGNATURE` skips the
+//! segments that cannot hold the signature instead of scanning them.
+//!
+//! A manifest covers the first `bytes` of its file; one whose file has
+//! grown since is out of date and ignored.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_sdk::signature::Signature;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SignatureBloomConfig {
+    /// Signatures a segment is sized for; segments holding more have more
+    /// false positives.
+    pub expected_signatures: u64,
+    pub false_positive_rate: f64,
+}
+
+impl Default for SignatureBloomConfig {
+    fn default() -> Self {
+        SignatureBloomConfig {
+            expected_signatures: 1_000_000,
+            false_positive_rate: 0.01,
+        }
+    }
+}
+
+/// Bloom filter of transaction signatures. Signatures are uniformly
+/// distributed already, so their own bytes are the hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureBloom {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl SignatureBloom {
+    pub fn new(config: &SignatureBloomConfig) -> Self {
+        let expected = config.expected_signatures.max(1) as f64;
+        let rate = config.false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-expected * rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let hashes = (bits / expected * ln2).round().clamp(1.0, 16.0);
+        SignatureBloom {
+            bits: vec![0; (bits / 64.0).ceil() as usize],
+            hashes: hashes as u32,
+        }
+    }
+
+    /// Bits of `signature`, by double hashing.
+    fn positions(&self, signature: &Signature) -> impl Iterator<Item = usize> {
+        let bytes = signature.as_ref();
+        let first = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let second = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) | 1;
+        let len = self.bits.len() as u64 * 64;
+        (0..self.hashes as u64)
+            .map(move |hash| (first.wrapping_add(hash.wrapping_mul(second)) % len) as usize)
+    }
+
+    pub fn insert(&mut self, signature: &Signature) {
+        for position in self.positions(signature) {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    /// False only when `signature` was never inserted.
+    pub fn contains(&self, signature: &Signature) -> bool {
+        self.positions(signature)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|word| *word = 0);
+    }
+
+    /// The bits as 64 bit words in hex.
+    pub fn to_json(&self) -> Value {
+        let bits: String = self
+            .bits
+            .iter()
+            .map(|word| format!("{:016x}", word))
+            .collect();
+        json!({ "hashes": self.hashes, "bits": bits })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let hashes = u32::try_from(value.get("hashes")?.as_u64()?).ok()?;
+        let hex = value.get("bits")?.as_str()?;
+        if hashes == 0 || hex.is_empty() || hex.len() % 16 != 0 {
+            return None;
+        }
+        let bits = (0..hex.len())
+            .step_by(16)
+            .map(|start| u64::from_str_radix(hex.get(start..start + 16)?, 16).ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(SignatureBloom { bits, hashes })
+    }
+}
+
+/// What a manifest records of its segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentManifest {
+    /// Length of the file the manifest covers.
+    pub bytes: u64,
+    pub records: u64,
+    /// Slot range of the records, `None` for an empty segment.
+    pub slots: Option<(u64, u64)>,
+    pub signatures: SignatureBloom,
+}
+
+impl SegmentManifest {
+    /// `accs.txt` has `accs.txt.manifest.json`.
+    pub fn path(segment: &Path) -> PathBuf {
+        let mut path = segment.as_os_str().to_owned();
+        path.push(".manifest.json");
+        PathBuf::from(path)
+    }
+
+    pub fn write(&self, segment: &Path) -> io::Result<()> {
+        let manifest = json!({
+            "bytes": self.bytes,
+            "records": self.records,
+            "first_slot": self.slots.map(|(first_slot, _)| first_slot),
+            "last_slot": self.slots.map(|(_, last_slot)| last_slot),
+            "signature_bloom": self.signatures.to_json(),
+        });
+        // Renamed into place so that readers never see half of it.
+        let path = Self::path(segment);
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        fs::write(&partial, manifest.to_string())?;
+        fs::rename(&partial, &path)
+    }
+
+    /// The manifest of `segment`, `None` when it has none or it is out of
+    /// date.
+    pub fn read(segment: &Path) -> io::Result<Option<SegmentManifest>> {
+        let manifest = match fs::read(Self::path(segment)) {
+            Ok(manifest) => manifest,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let manifest: Value = serde_json::from_slice(&manifest)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid manifest of {:?}", segment),
+            )
+        };
+        let field = |name: &str| manifest.get(name).and_then(Value::as_u64);
+        let manifest = SegmentManifest {
+            bytes: field("bytes").ok_or_else(invalid)?,
+            records: field("records").ok_or_else(invalid)?,
+            slots: field("first_slot").zip(field("last_slot")),
+            signatures: manifest
+                .get("signature_bloom")
+                .and_then(SignatureBloom::from_json)
+                .ok_or_else(invalid)?,
+        };
+        if fs::metadata(segment)?.len() != manifest.bytes {
+            return Ok(None);
+        }
+        Ok(Some(manifest))
+    }
+
+    pub fn remove(segment: &Path) -> io::Result<()> {
+        match fs::remove_file(Self::path(segment)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bloom() -> SignatureBloom {
+        SignatureBloom::new(&SignatureBloomConfig {
+            expected_signatures: 1_000,
+            false_positive_rate: 0.01,
+        })
+    }
+
+    #[test]
+    fn holds_inserted_signatures() {
+        let mut bloom = bloom();
+        let inserted: Vec<Signature> = (0..1_000).map(|_| Signature::new_unique()).collect();
+        for signature in &inserted {
+            bloom.insert(signature);
+        }
+        assert!(inserted.iter().all(|signature| bloom.contains(signature)));
+
+        let false_positives = (0..10_000)
+            .filter(|_| bloom.contains(&Signature::new_unique()))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+
+        bloom.clear();
+        assert!(!bloom.contains(&inserted[0]));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut bloom = bloom();
+        bloom.insert(&Signature::new_unique());
+        assert_eq!(SignatureBloom::from_json(&bloom.to_json()), Some(bloom));
+        assert_eq!(SignatureBloom::from_json(&json!({ "hashes": 3 })), None);
+    }
+
+    #[test]
+    fn ignores_out_of_date_manifests() {
+        let segment =
+            std::env::temp_dir().join(format!("fusion-segment-{}.txt", std::process::id()));
+        fs::write(&segment, b"records").unwrap();
+        let mut manifest = SegmentManifest {
+            bytes: 7,
+            records: 1,
+            slots: Some((5, 5)),
+            signatures: bloom(),
+        };
+        manifest.signatures.insert(&Signature::new_unique());
+        manifest.write(&segment).unwrap();
+        assert_eq!(SegmentManifest::read(&segment).unwrap(), Some(manifest));
+
+        fs::write(&segment, b"more records").unwrap();
+        assert_eq!(SegmentManifest::read(&segment).unwrap(), None);
+
+        SegmentManifest::remove(&segment).unwrap();
+        fs::remove_file(&segment).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::{error::Error, fmt, io, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{grpc::proto::SubscribeUpdate, AccTx, EncodedEvent, Subscribers};
+
+/// An output of the background writer. Every sink sees every event and
+/// writes the ones it is meant for, so backends are added in
+/// `spawn_writer` without touching the plugin callbacks.
+pub trait Sink: Send {
+    /// Gaps and statuses are reported under this name, e.g. `kafka`.
+    fn name(&self) -> &'static str;
+
+    /// Sinks encode through `event` so that each codec runs once per event.
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()>;
+
+    /// Writes the accounts replayed at startup, along with the events they
+    /// lead to, in one go for sinks that have a cheaper bulk path. A batch
+    /// cut short fails with a [`PartialWrite`].
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        for (written, event) in events.iter().enumerate() {
+            self.write(event)
+                .map_err(|error| PartialWrite::wrap(written, error))?;
+        }
+        Ok(())
+    }
+
+    /// Called once a second with the latest slot, for sinks that signal
+    /// they are alive while idle. Sinks delivering in the background fail
+    /// it with [`FailedEvents`] for what they gave up on since.
+    fn heartbeat(&mut self, _slot: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Marks everything up to the rooted `slot` as complete.
+    fn watermark(&mut self, _slot: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Moves the current output aside and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Sends an archived update again to backfill a gap, returning whether
+    /// it was accepted.
+    fn replay(&mut self, _update: &SubscribeUpdate) -> bool {
+        false
+    }
+
+    fn status(&self) -> Value {
+        Value::Null
+    }
+
+    /// Events taken from the writer and not delivered yet, which an unload
+    /// now would abandon.
+    fn pending(&self) -> u64 {
+        0
+    }
+
+    /// Lowest slot of the events taken from the writer and not delivered
+    /// yet, `None` once all were. The write-ahead log keeps what it logged
+    /// from this slot on.
+    fn undelivered_from(&self) -> Option<u64> {
+        None
+    }
+
+    /// Called once when the writer stops.
+    fn close(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Error of a batch cut short after its first `written` events, so that
+/// retrying it does not write them twice.
+#[derive(Debug)]
+pub struct PartialWrite {
+    pub written: usize,
+    pub error: io::Error,
+}
+
+impl PartialWrite {
+    pub fn wrap(written: usize, error: io::Error) -> io::Error {
+        io::Error::new(error.kind(), PartialWrite { written, error })
+    }
+
+    /// Events of the batch written before `error`, none unless it is a
+    /// [`PartialWrite`].
+    pub fn written(error: &io::Error) -> usize {
+        error
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<PartialWrite>())
+            .map_or(0, |partial| partial.written)
+    }
+}
+
+impl fmt::Display for PartialWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} events", self.error, self.written)
+    }
+}
+
+impl Error for PartialWrite {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Error of events an earlier write took that failed to be delivered after
+/// all, so that the error policy of the sink applies to them.
+#[derive(Debug)]
+pub struct FailedEvents {
+    pub events: Vec<AccTx>,
+    pub error: String,
+}
+
+impl FailedEvents {
+    pub fn wrap(events: Vec<AccTx>, error: String) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, FailedEvents { events, error })
+    }
+}
+
+impl fmt::Display for FailedEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} events failed: {}", self.events.len(), self.error)
+    }
+}
+
+impl Error for FailedEvents {}
+
+impl Sink for Arc<Subscribers> {
+    fn name(&self) -> &'static str {
+        "subscribers"
+    }
+
+    /// Subscriptions are encoded by the server delivering them.
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        self.publish(event.event);
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    grpc::proto::SubscribeUpdate, AccTx, EncodedEvent, FailedEvents, PartialWrite, PluginMetrics,
+    Sink, SinkConfig,
+};
+
+/// What the writer does when a sink fails to write, e.g.
+/// `{ "policy": "retry", "attempts": 5, "backoff_ms": 100 }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum SinkErrorPolicy {
+    /// Tries again up to `attempts` times, doubling the wait from
+    /// `backoff_ms` each time up to `max_backoff_ms`, then skips. Events
+    /// written meanwhile wait behind the failed ones.
+    Retry {
+        #[serde(default = "default_attempts")]
+        attempts: u32,
+        #[serde(default = "default_backoff_ms")]
+        backoff_ms: u64,
+        #[serde(default = "default_max_backoff_ms")]
+        max_backoff_ms: u64,
+    },
+    /// Drops what failed to be written, counting it as a gap of the sink.
+    #[default]
+    Skip,
+    /// Stops the writer, after which the plugin callbacks return errors.
+    Escalate,
+}
+
+fn default_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    5_000
+}
+
+/// Applies the error policy of the sink it wraps. Every failure is logged
+/// and kept in the recent errors, and only escalated ones reach the writer.
+///
+/// Events being retried are held here rather than waited for, so a failing
+/// sink never stalls the writer's executor. They are written again on the
+/// first call after their backoff, at the latest the heartbeat of the
+/// writer's next tick.
+pub struct GuardedSink {
+    sink: Box<dyn Sink>,
+    policy: SinkErrorPolicy,
+    metrics: Arc<PluginMetrics>,
+    /// Events that failed to be written, and those written since.
+    retrying: VecDeque<AccTx>,
+    /// Failed attempts at writing `retrying` again.
+    attempt: u32,
+    next_attempt: Instant,
+}
+
+impl GuardedSink {
+    pub fn wrap(
+        sink: Box<dyn Sink>,
+        config: &SinkConfig,
+        metrics: Arc<PluginMetrics>,
+    ) -> Box<dyn Sink> {
+        Box::new(GuardedSink {
+            sink,
+            policy: config.on_error.clone(),
+            metrics,
+            retrying: VecDeque::new(),
+            attempt: 0,
+            next_attempt: Instant::now(),
+        })
+    }
+
+    fn report(&self, action: &str, error: &io::Error) {
+        let message = format!(
+            "Failed to {} {} output: {}",
+            action,
+            self.sink.name(),
+            error
+        );
+        warn!("{}", message);
+        self.metrics.record_error(message);
+    }
+
+    /// Runs an action that writes no events, which is skipped on failure
+    /// unless escalated, as the writer repeats it anyway.
+    fn guarded(&mut self, action: &str, done: io::Result<()>) -> io::Result<()> {
+        match done {
+            Err(error) => {
+                self.report(action, &error);
+                match self.policy {
+                    SinkErrorPolicy::Escalate => Err(error),
+                    _ => Ok(()),
+                }
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Applies the policy to `events` that failed to be written.
+    fn failed(
+        &mut self,
+        events: impl IntoIterator<Item = AccTx>,
+        error: io::Error,
+    ) -> io::Result<()> {
+        self.report("write to", &error);
+        match self.policy {
+            SinkErrorPolicy::Retry { attempts, .. } if attempts > 0 => {
+                // Behind events already held, if a sink reported these late.
+                let retrying = self.retrying.is_empty();
+                self.retrying.extend(events);
+                if retrying {
+                    self.attempt = 0;
+                    self.next_attempt = Instant::now() + self.backoff();
+                }
+                Ok(())
+            }
+            SinkErrorPolicy::Escalate => Err(error),
+            _ => {
+                let slots: Vec<u64> = events.into_iter().map(|event| event.slot()).collect();
+                self.skipped(slots.into_iter());
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait before the next attempt.
+    fn backoff(&self) -> Duration {
+        match self.policy {
+            SinkErrorPolicy::Retry {
+                backoff_ms,
+                max_backoff_ms,
+                ..
+            } => Duration::from_millis(
+                backoff_ms
+                    .saturating_mul(1 << self.attempt.min(16))
+                    .min(max_backoff_ms),
+            ),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Writes the held events again once their backoff elapsed, only those
+    /// not written yet, and gives them up as a gap after the last attempt.
+    fn retry(&mut self) {
+        let attempts = match self.policy {
+            SinkErrorPolicy::Retry { attempts, .. } => attempts,
+            _ => 0,
+        };
+        if self.retrying.is_empty() || Instant::now() < self.next_attempt {
+            return;
+        }
+
+        let written = {
+            let events: Vec<EncodedEvent> = self
+                .retrying
+                .make_contiguous()
+                .iter()
+                .map(EncodedEvent::new)
+                .collect();
+            self.sink.write_batch(&events)
+        };
+        let error = match written {
+            Ok(()) => {
+                self.retrying.clear();
+                return;
+            }
+            Err(error) => error,
+        };
+        let written = PartialWrite::written(&error).min(self.retrying.len());
+        self.retrying.drain(..written);
+        self.report("write to", &error);
+
+        self.attempt += 1;
+        if self.attempt >= attempts {
+            let retrying = std::mem::take(&mut self.retrying);
+            self.skipped(retrying.iter().map(AccTx::slot));
+        } else {
+            self.next_attempt = Instant::now() + self.backoff();
+        }
+    }
+
+    /// Records events given up on as a gap of the sink.
+    fn skipped(&self, slots: impl Iterator<Item = u64>) {
+        let (events, first_slot, last_slot) =
+            slots.fold((0, u64::MAX, 0), |(events, first_slot, last_slot), slot| {
+                (events + 1, first_slot.min(slot), last_slot.max(slot))
+            });
+        if events == 0 {
+            return;
+        }
+        self.metrics
+            .dropped_events
+            .fetch_add(events, Ordering::Relaxed);
+        self.metrics
+            .gaps
+            .record(self.sink.name(), first_slot, last_slot, events);
+    }
+}
+
+impl Sink for GuardedSink {
+    fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        if !self.retrying.is_empty() {
+            self.retrying.push_back(event.event.clone());
+            self.retry();
+            return Ok(());
+        }
+        match self.sink.write(event) {
+            Ok(()) => Ok(()),
+            Err(error) => self.failed([event.event.clone()], error),
+        }
+    }
+
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        if !self.retrying.is_empty() {
+            self.retrying
+                .extend(events.iter().map(|event| event.event.clone()));
+            self.retry();
+            return Ok(());
+        }
+        match self.sink.write_batch(events) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                let written = PartialWrite::written(&error).min(events.len());
+                let failed = events[written..].iter().map(|event| event.event.clone());
+                self.failed(failed, error)
+            }
+        }
+    }
+
+    /// Applies the policy to events the sink failed to deliver since.
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        self.retry();
+        match self.sink.heartbeat(slot) {
+            Err(error)
+                if error
+                    .get_ref()
+                    .is_some_and(|error| error.is::<FailedEvents>()) =>
+            {
+                let kind = error.kind();
+                let failed = error
+                    .into_inner()
+                    .and_then(|error| error.downcast::<FailedEvents>().ok())
+                    .unwrap();
+                let error = io::Error::new(kind, failed.error);
+                self.failed(failed.events, error)
+            }
+            done => self.guarded("send a heartbeat to", done),
+        }
+    }
+
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        let done = self.sink.watermark(slot);
+        self.guarded("send a watermark to", done)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.retry();
+        self.sink.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.sink.rotate()
+    }
+
+    fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        self.sink.replay(update)
+    }
+
+    fn status(&self) -> Value {
+        self.sink.status()
+    }
+
+    fn pending(&self) -> u64 {
+        self.retrying.len() as u64 + self.sink.pending()
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.retrying
+            .iter()
+            .map(AccTx::slot)
+            .chain(self.sink.undelivered_from())
+            .min()
+    }
+
+    /// Tries the held events once more without waiting; those still
+    /// failing are left undelivered.
+    fn close(&mut self) -> io::Result<()> {
+        self.next_attempt = Instant::now();
+        self.retry();
+        self.sink.close()
+    }
+}
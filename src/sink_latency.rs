@@ -0,0 +1,349 @@
+//! Per-sink write latency, and isolation of sinks that keep exceeding their
+//! budget so they stop holding up the others.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{grpc::proto::SubscribeUpdate, AccTx, EncodedEvent, PluginMetrics, Sink, Undelivered};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SinkLatencyConfig {
+    /// Longest a sink should take to write an event.
+    pub budget_ms: u64,
+    /// Consecutive writes over budget after which a sink is isolated.
+    pub strikes: u32,
+    /// Events an isolated sink may fall behind by before further ones are
+    /// dropped.
+    pub spill_capacity: usize,
+}
+
+impl Default for SinkLatencyConfig {
+    fn default() -> Self {
+        SinkLatencyConfig {
+            budget_ms: 50,
+            strikes: 100,
+            spill_capacity: 100_000,
+        }
+    }
+}
+
+enum SpillMessage {
+    Event(AccTx),
+    Batch(Vec<AccTx>),
+    Heartbeat(u64),
+    Watermark(u64),
+    Flush,
+    Rotate,
+}
+
+struct Spill {
+    sender: SyncSender<SpillMessage>,
+    thread: JoinHandle<()>,
+}
+
+/// [`Sink::pending`] and [`Sink::undelivered_from`] of an isolated sink.
+#[derive(Debug, Default)]
+struct Reported {
+    pending: u64,
+    undelivered_from: Option<u64>,
+}
+
+impl Reported {
+    fn of(sink: &dyn Sink) -> Self {
+        Reported {
+            pending: sink.pending(),
+            undelivered_from: sink.undelivered_from(),
+        }
+    }
+}
+
+/// Times every write of the sink it wraps. Once the sink has been over
+/// budget for `strikes` writes in a row, it is moved onto a thread of its
+/// own fed by a spill queue for the rest of the run, and events that do not
+/// fit the queue are recorded as a gap of the sink.
+pub struct BudgetedSink {
+    name: &'static str,
+    sink: Arc<Mutex<Box<dyn Sink>>>,
+    budget: Duration,
+    strikes: u32,
+    over_budget: u32,
+    spill_capacity: usize,
+    spill: Option<Spill>,
+    /// Moving average of the write latency, in microseconds.
+    latency_us: Arc<AtomicU64>,
+    /// Events in the spill queue, and the one being written.
+    spilled: Undelivered,
+    /// What an isolated sink reported after its latest write, as it is not
+    /// waited for.
+    reported: Arc<Mutex<Reported>>,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl BudgetedSink {
+    pub fn new(
+        sink: Box<dyn Sink>,
+        config: &SinkLatencyConfig,
+        metrics: Arc<PluginMetrics>,
+    ) -> Self {
+        BudgetedSink {
+            name: sink.name(),
+            sink: Arc::new(Mutex::new(sink)),
+            budget: Duration::from_millis(config.budget_ms),
+            strikes: config.strikes.max(1),
+            over_budget: 0,
+            spill_capacity: config.spill_capacity.max(1),
+            spill: None,
+            latency_us: Arc::default(),
+            spilled: Undelivered::default(),
+            reported: Arc::default(),
+            metrics,
+        }
+    }
+
+    /// Runs `write` on the sink in place, isolating it once it has been
+    /// over budget for too long.
+    fn timed(
+        &mut self,
+        events: usize,
+        write: impl FnOnce(&mut dyn Sink) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let started = Instant::now();
+        let written = write(self.sink.lock().unwrap().as_mut());
+        let per_event = started.elapsed() / events.max(1) as u32;
+        observe(&self.latency_us, per_event);
+
+        if per_event > self.budget {
+            self.over_budget += 1;
+        } else {
+            self.over_budget = 0;
+        }
+        if self.over_budget >= self.strikes {
+            self.isolate();
+        }
+        written
+    }
+
+    fn isolate(&mut self) {
+        let (sender, receiver) = mpsc::sync_channel(self.spill_capacity);
+        *self.reported.lock().unwrap() = Reported::of(&**self.sink.lock().unwrap());
+        let sink = self.sink.clone();
+        let latency_us = self.latency_us.clone();
+        let spilled = self.spilled.clone();
+        let reported = self.reported.clone();
+        let metrics = self.metrics.clone();
+        let thread = std::thread::Builder::new()
+            .name("fusionSlowSink".to_string())
+            .spawn(move || drain(receiver, sink, latency_us, spilled, reported, metrics));
+        match thread {
+            Ok(thread) => {
+                warn!(
+                    "Isolating the {} output, over its {:?} budget for {} writes",
+                    self.name, self.budget, self.over_budget
+                );
+                self.spill = Some(Spill { sender, thread });
+            }
+            Err(error) => warn!("Failed to isolate the {} output: {}", self.name, error),
+        }
+        self.over_budget = 0;
+    }
+
+    /// Queues `message` for the isolated sink, dropping its events when the
+    /// queue is full.
+    fn send(&self, spill: &Spill, message: SpillMessage) {
+        let events = match &message {
+            SpillMessage::Event(event) => vec![event.slot()],
+            SpillMessage::Batch(events) => events.iter().map(AccTx::slot).collect(),
+            _ => Vec::new(),
+        };
+        for slot in &events {
+            self.spilled.add(*slot);
+        }
+        if spill.sender.try_send(message).is_err() {
+            self.spilled.done(events.iter().copied());
+            if let (Some(first_slot), Some(last_slot)) = (events.iter().min(), events.iter().max())
+            {
+                self.metrics
+                    .dropped_events
+                    .fetch_add(events.len() as u64, Ordering::Relaxed);
+                self.metrics
+                    .gaps
+                    .record(self.name, *first_slot, *last_slot, events.len() as u64);
+            }
+        }
+    }
+}
+
+/// Writes what the writer queued for an isolated sink until the queue
+/// closes.
+fn drain(
+    receiver: Receiver<SpillMessage>,
+    sink: Arc<Mutex<Box<dyn Sink>>>,
+    latency_us: Arc<AtomicU64>,
+    spilled: Undelivered,
+    reported: Arc<Mutex<Reported>>,
+    metrics: Arc<PluginMetrics>,
+) {
+    for message in receiver {
+        let mut sink = sink.lock().unwrap();
+        let started = Instant::now();
+        let (slots, done) = match &message {
+            SpillMessage::Event(event) => {
+                (vec![event.slot()], sink.write(&EncodedEvent::new(event)))
+            }
+            SpillMessage::Batch(events) => {
+                let encoded: Vec<EncodedEvent> = events.iter().map(EncodedEvent::new).collect();
+                (
+                    events.iter().map(AccTx::slot).collect(),
+                    sink.write_batch(&encoded),
+                )
+            }
+            SpillMessage::Heartbeat(slot) => (Vec::new(), sink.heartbeat(*slot)),
+            SpillMessage::Watermark(slot) => (Vec::new(), sink.watermark(*slot)),
+            SpillMessage::Flush => (Vec::new(), sink.flush()),
+            SpillMessage::Rotate => (Vec::new(), sink.rotate()),
+        };
+        // Before the events are taken off, so they are accounted for
+        // throughout.
+        *reported.lock().unwrap() = Reported::of(&**sink);
+        if !slots.is_empty() {
+            observe(&latency_us, started.elapsed() / slots.len() as u32);
+            spilled.done(slots);
+        }
+        if let Err(error) = done {
+            let error = format!("Failed to write to the {} output: {}", sink.name(), error);
+            warn!("{}", error);
+            metrics.record_error(error);
+        }
+    }
+}
+
+/// Folds `latency` into a moving average over roughly the last 8 writes.
+fn observe(latency_us: &AtomicU64, latency: Duration) {
+    let sample = latency.as_micros() as u64;
+    let average = latency_us.load(Ordering::Relaxed);
+    latency_us.store(average - average / 8 + sample / 8, Ordering::Relaxed);
+}
+
+impl Sink for BudgetedSink {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                self.send(spill, SpillMessage::Event(event.event.clone()));
+                Ok(())
+            }
+            None => self.timed(1, |sink| sink.write(event)),
+        }
+    }
+
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                let events = events.iter().map(|event| event.event.clone()).collect();
+                self.send(spill, SpillMessage::Batch(events));
+                Ok(())
+            }
+            None => self.timed(events.len(), |sink| sink.write_batch(events)),
+        }
+    }
+
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                self.send(spill, SpillMessage::Heartbeat(slot));
+                Ok(())
+            }
+            None => self.sink.lock().unwrap().heartbeat(slot),
+        }
+    }
+
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                self.send(spill, SpillMessage::Watermark(slot));
+                Ok(())
+            }
+            None => self.sink.lock().unwrap().watermark(slot),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                self.send(spill, SpillMessage::Flush);
+                Ok(())
+            }
+            None => self.sink.lock().unwrap().flush(),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => {
+                self.send(spill, SpillMessage::Rotate);
+                Ok(())
+            }
+            None => self.sink.lock().unwrap().rotate(),
+        }
+    }
+
+    /// Waits for an isolated sink to finish its current write.
+    fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        self.sink.lock().unwrap().replay(update)
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "latency_us": self.latency_us.load(Ordering::Relaxed),
+            "isolated": self.spill.is_some(),
+            "spilled": self.spilled.get(),
+            // Isolated sinks are not waited for.
+            "output": self
+                .sink
+                .try_lock()
+                .map(|sink| sink.status())
+                .unwrap_or(Value::Null),
+        })
+    }
+
+    fn pending(&self) -> u64 {
+        let output = match &self.spill {
+            Some(_) => self.reported.lock().unwrap().pending,
+            None => self.sink.lock().unwrap().pending(),
+        };
+        self.spilled.get() + output
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        let output = match &self.spill {
+            Some(_) => self.reported.lock().unwrap().undelivered_from,
+            None => self.sink.lock().unwrap().undelivered_from(),
+        };
+        self.spilled.oldest().into_iter().chain(output).min()
+    }
+
+    /// Lets an isolated sink write what it has queued before closing it.
+    fn close(&mut self) -> io::Result<()> {
+        if let Some(Spill { sender, thread }) = self.spill.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+        self.sink.lock().unwrap().close()
+    }
+}
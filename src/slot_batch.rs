@@ -0,0 +1,158 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use serde_json::Value;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+use crate::{grpc::proto::SubscribeUpdate, AccTx, EncodedEvent, Sink, SinkConfig, SlotUpdate};
+
+/// Holds the events of one sink and writes them in one batch per slot when
+/// the first status update of the slot arrives: its accounts, then its
+/// transactions, then whatever was derived from them, ending with that
+/// slot update. Later events of a written slot pass straight through.
+#[derive(Debug)]
+pub struct SlotBatchSink {
+    sink: Box<dyn Sink>,
+    slots: BTreeMap<u64, Vec<AccTx>>,
+    /// Slots written since the latest root.
+    written: BTreeSet<u64>,
+}
+
+impl SlotBatchSink {
+    pub fn new(sink: Box<dyn Sink>) -> Self {
+        SlotBatchSink {
+            sink,
+            slots: BTreeMap::new(),
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// `sink` as is, or batched by slot when its `config` asks for it.
+    pub fn wrap(sink: Box<dyn Sink>, config: &SinkConfig) -> Box<dyn Sink> {
+        match config.batch_by_slot {
+            true => Box::new(SlotBatchSink::new(sink)),
+            false => sink,
+        }
+    }
+
+    /// Events that can be written now, in order.
+    fn push(&mut self, event: AccTx) -> Vec<AccTx> {
+        let slot = event.slot();
+        let status = match &event {
+            AccTx::Slot(SlotUpdate { status, .. }) => Some(*status),
+            _ => None,
+        };
+        if let AccTx::SlotRollback { .. } = event {
+            self.slots.remove(&slot);
+            return vec![event];
+        }
+
+        let mut released = Vec::new();
+        if let Some(SlotStatus::Rooted) = status {
+            // Slots a root leaves behind unwritten never got a status
+            // update; their events still go out, without a marker.
+            let kept = self.slots.split_off(&slot);
+            let behind = std::mem::replace(&mut self.slots, kept);
+            released.extend(behind.into_values().flat_map(batch));
+            self.written = self.written.split_off(&slot);
+        }
+        if event.is_control() || self.written.contains(&slot) {
+            released.push(event);
+        } else if status.is_some() {
+            released.extend(batch(self.slots.remove(&slot).unwrap_or_default()));
+            released.push(event);
+            self.written.insert(slot);
+        } else {
+            self.slots.entry(slot).or_default().push(event);
+        }
+        released
+    }
+
+    fn write_released(&mut self, released: Vec<AccTx>) -> io::Result<()> {
+        match released.len() {
+            0 => Ok(()),
+            1 => self.sink.write(&EncodedEvent::new(&released[0])),
+            _ => {
+                let released: Vec<EncodedEvent> = released.iter().map(EncodedEvent::new).collect();
+                self.sink.write_batch(&released)
+            }
+        }
+    }
+}
+
+/// Accounts first, then transactions, then the rest, each in arrival order.
+fn batch(mut events: Vec<AccTx>) -> Vec<AccTx> {
+    events.sort_by_key(|event| match event {
+        AccTx::Acc(_) => 0,
+        AccTx::Tx(_) => 1,
+        _ => 2,
+    });
+    events
+}
+
+impl Sink for SlotBatchSink {
+    fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let released = self.push(event.event.clone());
+        self.write_released(released)
+    }
+
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        let mut released = Vec::new();
+        for event in events {
+            released.extend(self.push(event.event.clone()));
+        }
+        self.write_released(released)
+    }
+
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        self.sink.heartbeat(slot)
+    }
+
+    /// Held slots are not complete yet.
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        let held = self.slots.keys().next().map(|held| held.saturating_sub(1));
+        self.sink
+            .watermark(held.map_or(slot, |held| held.min(slot)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.sink.rotate()
+    }
+
+    fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        self.sink.replay(update)
+    }
+
+    fn status(&self) -> Value {
+        self.sink.status()
+    }
+
+    fn pending(&self) -> u64 {
+        let held: usize = self.slots.values().map(Vec::len).sum();
+        held as u64 + self.sink.pending()
+    }
+
+    /// Held events keep their slot logged until released.
+    fn undelivered_from(&self) -> Option<u64> {
+        let held = self.slots.keys().next().copied();
+        held.into_iter().chain(self.sink.undelivered_from()).min()
+    }
+
+    /// Writes the slots still held, without their markers.
+    fn close(&mut self) -> io::Result<()> {
+        let held = std::mem::take(&mut self.slots);
+        let released = held.into_values().flat_map(batch).collect();
+        self.write_released(released)?;
+        self.sink.close()
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+use crate::{grpc::proto::SubscribeUpdate, AccTx, EncodedEvent, Sink, SinkConfig, SlotUpdate};
+
+/// Holds events in memory until their slot is `confirmation_depth` slots
+/// behind the newest one seen, for consumers that want data unlikely to be
+/// reorged without waiting for commitment. Events still held when the
+/// plugin unloads are not written.
+///
+/// With `commitment` set, events are instead held until their slot reaches
+/// that commitment, and those of slots a root leaves behind without
+/// reaching it, which were on abandoned forks, are discarded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SlotBufferConfig {
+    pub confirmation_depth: u64,
+    pub commitment: Option<BufferCommitment>,
+}
+
+impl Default for SlotBufferConfig {
+    fn default() -> Self {
+        SlotBufferConfig {
+            confirmation_depth: 32,
+            commitment: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BufferCommitment {
+    Confirmed,
+    #[serde(alias = "finalized")]
+    Rooted,
+}
+
+#[derive(Debug)]
+pub struct SlotBuffer {
+    confirmation_depth: u64,
+    commitment: Option<BufferCommitment>,
+    slots: BTreeMap<u64, Vec<AccTx>>,
+    /// Slots at the commitment since the latest root, whose events pass
+    /// straight through.
+    committed: BTreeSet<u64>,
+    latest_slot: u64,
+}
+
+impl SlotBuffer {
+    pub fn new(config: &SlotBufferConfig) -> Self {
+        SlotBuffer {
+            confirmation_depth: config.confirmation_depth,
+            commitment: config.commitment,
+            slots: BTreeMap::new(),
+            committed: BTreeSet::new(),
+            latest_slot: 0,
+        }
+    }
+
+    /// Buffers `events` and returns those of every slot now deep enough,
+    /// or at the commitment, oldest slot first. Rollbacks pass straight
+    /// through and drop what is still held of their slot.
+    pub fn push(&mut self, events: Vec<AccTx>) -> Vec<AccTx> {
+        let (rollbacks, events): (Vec<AccTx>, Vec<AccTx>) = events
+            .into_iter()
+            .partition(|event| matches!(event, AccTx::SlotRollback { .. }));
+        for rollback in &rollbacks {
+            self.slots.remove(&rollback.slot());
+        }
+
+        let mut released = match self.commitment {
+            Some(commitment) => self.push_committed(commitment, events),
+            None => self.push_deep(events),
+        };
+        released.extend(rollbacks);
+        released
+    }
+
+    fn push_deep(&mut self, events: Vec<AccTx>) -> Vec<AccTx> {
+        for event in events {
+            self.latest_slot = self.latest_slot.max(event.slot());
+            self.slots.entry(event.slot()).or_default().push(event);
+        }
+
+        let held = self.slots.split_off(&(self.released_through() + 1));
+        std::mem::replace(&mut self.slots, held)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    fn push_committed(&mut self, commitment: BufferCommitment, events: Vec<AccTx>) -> Vec<AccTx> {
+        let mut released = Vec::new();
+        for event in events {
+            let slot = event.slot();
+            let status = match &event {
+                AccTx::Slot(SlotUpdate { status, .. }) => Some(*status),
+                _ => None,
+            };
+            if self.committed.contains(&slot) {
+                released.push(event);
+            } else {
+                self.slots.entry(slot).or_default().push(event);
+            }
+
+            let reached = matches!(
+                (commitment, status),
+                (_, Some(SlotStatus::Rooted))
+                    | (BufferCommitment::Confirmed, Some(SlotStatus::Confirmed))
+            );
+            if reached && self.committed.insert(slot) {
+                released.extend(self.slots.remove(&slot).unwrap_or_default());
+                self.latest_slot = self.latest_slot.max(slot);
+            }
+            if let Some(SlotStatus::Rooted) = status {
+                self.slots = self.slots.split_off(&slot);
+                self.committed = self.committed.split_off(&slot);
+            }
+        }
+        released
+    }
+
+    /// Events waiting for their slot to be released.
+    pub fn held(&self) -> u64 {
+        self.slots.values().map(|events| events.len() as u64).sum()
+    }
+
+    /// Lowest slot with events still held.
+    pub fn oldest_held(&self) -> Option<u64> {
+        self.slots.keys().next().copied()
+    }
+
+    /// Highest slot whose events have all been released; with a
+    /// commitment, the highest slot that reached it.
+    pub fn released_through(&self) -> u64 {
+        match self.commitment {
+            Some(_) => self.latest_slot,
+            None => self.latest_slot.saturating_sub(self.confirmation_depth),
+        }
+    }
+}
+
+/// Holds the events of one sink until their slot reaches its `commitment`,
+/// so that sinks sharing the writer emit at commitments of their own.
+pub struct CommittedSink {
+    sink: Box<dyn Sink>,
+    buffer: SlotBuffer,
+}
+
+impl CommittedSink {
+    pub fn new(sink: Box<dyn Sink>, commitment: BufferCommitment) -> Self {
+        CommittedSink {
+            sink,
+            buffer: SlotBuffer::new(&SlotBufferConfig {
+                commitment: Some(commitment),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// `sink` as is, or held to the commitment its `config` asks for.
+    pub fn wrap(sink: Box<dyn Sink>, config: &SinkConfig) -> Box<dyn Sink> {
+        match config.commitment {
+            Some(commitment) => Box::new(CommittedSink::new(sink, commitment)),
+            None => sink,
+        }
+    }
+}
+
+impl Sink for CommittedSink {
+    fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        for event in self.buffer.push(vec![event.event.clone()]) {
+            self.sink.write(&EncodedEvent::new(&event))?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self, events: &[EncodedEvent]) -> io::Result<()> {
+        let released = self
+            .buffer
+            .push(events.iter().map(|event| event.event.clone()).collect());
+        let released: Vec<EncodedEvent> = released.iter().map(EncodedEvent::new).collect();
+        self.sink.write_batch(&released)
+    }
+
+    fn heartbeat(&mut self, slot: u64) -> io::Result<()> {
+        self.sink.heartbeat(slot)
+    }
+
+    /// Held slots are not complete yet.
+    fn watermark(&mut self, slot: u64) -> io::Result<()> {
+        self.sink
+            .watermark(slot.min(self.buffer.released_through()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.sink.rotate()
+    }
+
+    fn replay(&mut self, update: &SubscribeUpdate) -> bool {
+        self.sink.replay(update)
+    }
+
+    fn status(&self) -> Value {
+        self.sink.status()
+    }
+
+    /// Held events are dropped on close.
+    fn pending(&self) -> u64 {
+        self.buffer.held() + self.sink.pending()
+    }
+
+    /// Held events keep their slot logged until released.
+    fn undelivered_from(&self) -> Option<u64> {
+        self.buffer
+            .oldest_held()
+            .into_iter()
+            .chain(self.sink.undelivered_from())
+            .min()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.sink.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccountUpdate;
+
+    fn account(slot: u64) -> AccTx {
+        AccTx::Acc(AccountUpdate::new(vec![1; 32], vec![2; 32], slot))
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> AccTx {
+        AccTx::Slot(SlotUpdate::new(slot, slot.checked_sub(1), status))
+    }
+
+    fn committed(commitment: BufferCommitment) -> SlotBuffer {
+        SlotBuffer::new(&SlotBufferConfig {
+            commitment: Some(commitment),
+            ..SlotBufferConfig::default()
+        })
+    }
+
+    /// Kinds and slots of `events`, in order.
+    fn released(events: Vec<AccTx>) -> Vec<(&'static str, u64)> {
+        events
+            .iter()
+            .map(|event| (event.kind(), event.slot()))
+            .collect()
+    }
+
+    #[test]
+    fn releases_at_confirmed() {
+        let mut buffer = committed(BufferCommitment::Confirmed);
+        assert!(buffer.push(vec![account(5)]).is_empty());
+        assert!(buffer.push(vec![slot(5, SlotStatus::Processed)]).is_empty());
+        assert_eq!(buffer.held(), 2);
+        assert_eq!(buffer.oldest_held(), Some(5));
+
+        let events = buffer.push(vec![slot(5, SlotStatus::Confirmed)]);
+        assert_eq!(released(events), [("account", 5), ("slot", 5), ("slot", 5)]);
+        assert_eq!(buffer.held(), 0);
+        assert_eq!(buffer.oldest_held(), None);
+        assert_eq!(buffer.released_through(), 5);
+
+        // Later events of a confirmed slot pass straight through.
+        assert_eq!(released(buffer.push(vec![account(5)])), [("account", 5)]);
+    }
+
+    #[test]
+    fn releases_at_rooted() {
+        let mut buffer = committed(BufferCommitment::Rooted);
+        assert!(buffer
+            .push(vec![account(5), slot(5, SlotStatus::Confirmed)])
+            .is_empty());
+        assert_eq!(buffer.held(), 2);
+
+        let events = buffer.push(vec![slot(5, SlotStatus::Rooted)]);
+        assert_eq!(released(events), [("account", 5), ("slot", 5), ("slot", 5)]);
+        assert_eq!(buffer.held(), 0);
+    }
+
+    #[test]
+    fn discards_dead_forks() {
+        let mut buffer = committed(BufferCommitment::Confirmed);
+        assert!(buffer
+            .push(vec![account(5), account(6), account(7)])
+            .is_empty());
+        assert_eq!(buffer.oldest_held(), Some(5));
+
+        // Slot 6 is rooted on a fork that skipped 5, which is never
+        // released, while 7 may still be confirmed.
+        let events = buffer.push(vec![slot(6, SlotStatus::Rooted)]);
+        assert_eq!(released(events), [("account", 6), ("slot", 6)]);
+        assert_eq!(buffer.held(), 1);
+        assert_eq!(buffer.oldest_held(), Some(7));
+    }
+
+    #[test]
+    fn releases_deep_slots() {
+        let mut buffer = SlotBuffer::new(&SlotBufferConfig {
+            confirmation_depth: 2,
+            commitment: None,
+        });
+        assert!(buffer.push(vec![account(3), account(4)]).is_empty());
+        assert_eq!(buffer.oldest_held(), Some(3));
+        assert_eq!(buffer.released_through(), 2);
+
+        let events = buffer.push(vec![account(6)]);
+        assert_eq!(released(events), [("account", 3), ("account", 4)]);
+        assert_eq!(buffer.oldest_held(), Some(6));
+        assert_eq!(buffer.released_through(), 4);
+    }
+
+    #[test]
+    fn rollbacks_drop_held_events() {
+        let mut buffer = committed(BufferCommitment::Confirmed);
+        assert!(buffer.push(vec![account(5), account(6)]).is_empty());
+
+        let events = buffer.push(vec![AccTx::SlotRollback { slot: 5 }]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(buffer.oldest_held(), Some(6));
+    }
+}
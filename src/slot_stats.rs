@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{AccTx, TransactionUpdate};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SlotStatsConfig {
+    /// A slot's statistics are emitted once a transaction this many slots
+    /// newer has been seen; later transactions for it are not counted.
+    pub flush_after_slots: u64,
+}
+
+impl Default for SlotStatsConfig {
+    fn default() -> Self {
+        SlotStatsConfig {
+            flush_after_slots: 4,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SlotCounters {
+    transactions: u64,
+    vote_transactions: u64,
+    failed_transactions: u64,
+    fees: u64,
+    fee_payers: HashSet<Pubkey>,
+}
+
+/// Summarizes the transactions of each slot as an [`AccTx::SlotStats`].
+#[derive(Debug)]
+pub struct SlotStatsAggregator {
+    slots: BTreeMap<u64, SlotCounters>,
+    flush_after_slots: u64,
+    flushed_slot: Option<u64>,
+}
+
+impl SlotStatsAggregator {
+    pub fn new(config: &SlotStatsConfig) -> Self {
+        SlotStatsAggregator {
+            slots: BTreeMap::new(),
+            flush_after_slots: config.flush_after_slots,
+            flushed_slot: None,
+        }
+    }
+
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        let slot = match event {
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                is_vote,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => {
+                if matches!(self.flushed_slot, Some(flushed) if *slot <= flushed) {
+                    return Vec::new();
+                }
+
+                let counters = self.slots.entry(*slot).or_default();
+                counters.transactions += 1;
+                if *is_vote {
+                    counters.vote_transactions += 1;
+                }
+                if transaction_status_meta.status.is_err() {
+                    counters.failed_transactions += 1;
+                }
+                counters.fees = counters.fees.saturating_add(transaction_status_meta.fee);
+                counters
+                    .fee_payers
+                    .insert(*transaction.message().fee_payer());
+                *slot
+            }
+            _ => return Vec::new(),
+        };
+
+        let flush_below = slot.saturating_sub(self.flush_after_slots);
+        let pending = self.slots.split_off(&flush_below);
+        let flushed = std::mem::replace(&mut self.slots, pending);
+
+        flushed
+            .into_iter()
+            .map(|(slot, counters)| {
+                self.flushed_slot = Some(slot);
+                AccTx::SlotStats {
+                    slot,
+                    transactions: counters.transactions,
+                    vote_transactions: counters.vote_transactions,
+                    failed_transactions: counters.failed_transactions,
+                    fees: counters.fees,
+                    fee_payers: counters.fee_payers.len() as u64,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+
+use log::info;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{AccTx, AccountUpdate, PluginMetrics};
+
+/// Accounts replayed from the snapshot at startup are written to the sinks
+/// in bulk rather than one by one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Most startup accounts the writer takes from its queue at once.
+    pub batch_size: usize,
+    /// Accounts the snapshot is expected to hold, for an ETA in progress
+    /// logs.
+    pub expected_accounts: Option<u64>,
+    /// Only pass on a startup account when its write version is above
+    /// every one seen for it before, as snapshot storages can hold several
+    /// versions of an account. Costs memory for every account until the
+    /// replay ends.
+    pub latest_versions_only: bool,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        StartupConfig {
+            batch_size: 10_000,
+            expected_accounts: None,
+            latest_versions_only: false,
+        }
+    }
+}
+
+/// Counts the startup accounts written and logs the replay's rate and ETA
+/// every second until it ends.
+#[derive(Debug)]
+pub struct StartupProgress {
+    expected_accounts: Option<u64>,
+    metrics: Arc<PluginMetrics>,
+    started: Option<Instant>,
+    accounts: u64,
+    last_tick: u64,
+    done: bool,
+}
+
+impl StartupProgress {
+    pub fn new(config: &StartupConfig, metrics: Arc<PluginMetrics>) -> Self {
+        StartupProgress {
+            expected_accounts: config.expected_accounts,
+            metrics,
+            started: None,
+            accounts: 0,
+            last_tick: 0,
+            done: false,
+        }
+    }
+
+    pub fn is_startup(event: &AccTx) -> bool {
+        matches!(
+            event,
+            AccTx::Acc(AccountUpdate {
+                is_startup: true,
+                ..
+            })
+        )
+    }
+
+    pub fn observe(&mut self, accounts: u64) {
+        self.started.get_or_insert_with(Instant::now);
+        self.accounts += accounts;
+        self.metrics
+            .startup_accounts
+            .fetch_add(accounts, Ordering::Relaxed);
+    }
+
+    /// Called once a second by the writer. The replay is over once a second
+    /// passes without startup accounts.
+    pub fn tick(&mut self) {
+        let started = match self.started {
+            Some(started) if !self.done => started,
+            _ => return,
+        };
+        let per_sec = self.accounts - self.last_tick;
+        self.last_tick = self.accounts;
+        self.metrics
+            .startup_accounts_per_sec
+            .store(per_sec, Ordering::Relaxed);
+
+        if per_sec == 0 {
+            self.done = true;
+            info!(
+                "Startup replay wrote {} accounts in {:.1}s",
+                self.accounts,
+                started.elapsed().as_secs_f64()
+            );
+            return;
+        }
+
+        match self.expected_accounts {
+            Some(expected) => info!(
+                "Startup replay at {} of ~{} accounts, {}/s, ETA {}s",
+                self.accounts,
+                expected,
+                per_sec,
+                expected.saturating_sub(self.accounts) / per_sec
+            ),
+            None => info!(
+                "Startup replay at {} accounts, {}/s",
+                self.accounts, per_sec
+            ),
+        }
+    }
+}
+
+/// Highest write version of every account seen during the startup replay.
+#[derive(Debug, Default)]
+pub struct StartupVersions {
+    versions: HashMap<Pubkey, u64>,
+    stale: u64,
+}
+
+impl StartupVersions {
+    /// Whether `write_version` is the newest of `pubkey` so far.
+    pub fn is_latest(&mut self, pubkey: &[u8], write_version: u64) -> bool {
+        let pubkey = match Pubkey::try_from(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return true,
+        };
+        match self.versions.get_mut(&pubkey) {
+            Some(latest) if *latest >= write_version => {
+                self.stale += 1;
+                false
+            }
+            Some(latest) => {
+                *latest = write_version;
+                true
+            }
+            None => {
+                self.versions.insert(pubkey, write_version);
+                true
+            }
+        }
+    }
+
+    /// Older versions skipped so far.
+    pub fn stale(&self) -> u64 {
+        self.stale
+    }
+}
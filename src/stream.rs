@@ -0,0 +1,324 @@
+//! Raw TCP and Unix domain socket streams of length-prefixed frames.
+//!
+//! A connection starts with the client sending a [`Hello`] frame and the
+//! server answering with a [`HandshakeResponse`]. On success every following
+//! frame is an event encoded with the negotiated codec. Frames are a
+//! big-endian `u32` length followed by that many bytes; handshake frames are
+//! always protobuf so they stay readable across protocol versions.
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use log::{info, warn};
+use prost::Message;
+use serde::Deserialize;
+use smol::{
+    net::{unix::UnixListener, TcpListener},
+    Task,
+};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    grpc::{proto::SubscribeRequest, SubscriberUpdate},
+    Codec, CodecRegistry, SinkConfig, Subscribers, SubscriptionFilter,
+};
+
+/// The newest protocol version this server speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version still accepted.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Codec used when the client expresses no preference.
+pub const DEFAULT_CODEC: &str = "protobuf";
+
+const MAX_HANDSHAKE_LEN: usize = 1 << 20;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamConfig {
+    pub tcp_address: Option<SocketAddr>,
+    pub uds_path: Option<PathBuf>,
+    /// Events buffered per connection; connections falling this far behind
+    /// are closed.
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+}
+
+fn default_channel_capacity() -> usize {
+    100_000
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Hello {
+    /// Range of protocol versions the client speaks.
+    #[prost(uint32, tag = "1")]
+    pub min_version: u32,
+    #[prost(uint32, tag = "2")]
+    pub max_version: u32,
+    /// Names of registered codecs the client can decode, most preferred
+    /// first. Empty means protobuf.
+    #[prost(string, repeated, tag = "3")]
+    pub codecs: Vec<String>,
+    #[prost(message, optional, tag = "4")]
+    pub filters: Option<SubscribeRequest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct HandshakeResponse {
+    #[prost(oneof = "handshake_response::Result", tags = "1, 2")]
+    pub result: Option<handshake_response::Result>,
+}
+
+pub mod handshake_response {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Result {
+        #[prost(message, tag = "1")]
+        Accepted(super::Accepted),
+        #[prost(message, tag = "2")]
+        Rejected(super::Rejected),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Accepted {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(string, tag = "2")]
+    pub codec: String,
+    /// First slot whose events are all matched against the filters.
+    #[prost(uint64, tag = "3")]
+    pub slot: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Rejected {
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    /// Lets clients retry with a version the server speaks.
+    #[prost(uint32, tag = "2")]
+    pub min_version: u32,
+    #[prost(uint32, tag = "3")]
+    pub max_version: u32,
+    #[prost(string, repeated, tag = "4")]
+    pub codecs: Vec<String>,
+}
+
+/// Accept loops of the configured listeners, stopped on drop.
+#[derive(Debug)]
+pub struct StreamServer {
+    listeners: Vec<Task<()>>,
+    uds_path: Option<PathBuf>,
+}
+
+/// State shared by every connection of a [`StreamServer`].
+#[derive(Debug)]
+struct Connections {
+    subscribers: Arc<Subscribers>,
+    codecs: CodecRegistry,
+    channel_capacity: usize,
+}
+
+impl StreamServer {
+    pub fn start(
+        config: &StreamConfig,
+        subscribers: Arc<Subscribers>,
+        codecs: CodecRegistry,
+    ) -> GeyserResult<Self> {
+        let stream_error = |address: String, error: std::io::Error| {
+            GeyserPluginError::Custom(
+                format!("Failed to listen for streams on {}: {}", address, error).into(),
+            )
+        };
+        let connections = Arc::new(Connections {
+            subscribers,
+            codecs,
+            channel_capacity: config.channel_capacity,
+        });
+        let mut listeners = Vec::new();
+
+        if let Some(address) = config.tcp_address {
+            let listener = smol::block_on(TcpListener::bind(address))
+                .map_err(|error| stream_error(address.to_string(), error))?;
+            let connections = connections.clone();
+            listeners.push(smol::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            info!("Stream connection from {}", peer);
+                            spawn_connection(stream, connections.clone());
+                        }
+                        Err(error) => warn!("Failed to accept stream connection: {}", error),
+                    }
+                }
+            }));
+            info!("Streaming events on tcp://{}", address);
+        }
+
+        if let Some(path) = &config.uds_path {
+            // A socket left behind by a previous run would fail the bind.
+            let _ = std::fs::remove_file(path);
+            let listener = UnixListener::bind(path)
+                .map_err(|error| stream_error(format!("{:?}", path), error))?;
+            let connections = connections.clone();
+            listeners.push(smol::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => spawn_connection(stream, connections.clone()),
+                        Err(error) => warn!("Failed to accept stream connection: {}", error),
+                    }
+                }
+            }));
+            info!("Streaming events on unix://{:?}", path);
+        }
+
+        Ok(StreamServer {
+            listeners,
+            uds_path: config.uds_path.clone(),
+        })
+    }
+}
+
+impl Drop for StreamServer {
+    fn drop(&mut self) {
+        self.listeners.clear();
+        if let Some(path) = &self.uds_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn spawn_connection<S>(stream: S, connections: Arc<Connections>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    smol::spawn(async move {
+        if let Err(error) = connections.serve(stream).await {
+            warn!("Stream connection closed: {}", error);
+        }
+    })
+    .detach();
+}
+
+impl Connections {
+    async fn serve<S>(&self, mut stream: S) -> std::io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let hello = read_frame(&mut stream, MAX_HANDSHAKE_LEN).await?;
+        let hello = Hello::decode(hello.as_slice())
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        let (accepted, codec, filter) = match self.negotiate(&hello) {
+            Ok(negotiated) => negotiated,
+            Err(reason) => {
+                let rejected = handshake_response::Result::Rejected(Rejected {
+                    reason,
+                    min_version: MIN_PROTOCOL_VERSION,
+                    max_version: PROTOCOL_VERSION,
+                    codecs: self.codecs.names().map(str::to_string).collect(),
+                });
+                write_handshake(&mut stream, rejected).await?;
+                return Ok(());
+            }
+        };
+
+        let (sender, mut receiver) = mpsc::channel(self.channel_capacity);
+        let id = self.subscribers.add(sender);
+        let slot = self.subscribers.update(id, filter).unwrap_or_default();
+        let accepted = handshake_response::Result::Accepted(Accepted { slot, ..accepted });
+
+        let served = async {
+            write_handshake(&mut stream, accepted).await?;
+            while let Some(update) = receiver.recv().await {
+                if let SubscriberUpdate::Event(event) = update {
+                    if let Some(frame) = codec.encode(&event) {
+                        write_frame(&mut stream, &frame).await?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+        self.subscribers.remove(id);
+
+        served
+    }
+
+    fn negotiate(
+        &self,
+        hello: &Hello,
+    ) -> Result<(Accepted, Arc<dyn Codec>, SubscriptionFilter), String> {
+        let version = hello.max_version.min(PROTOCOL_VERSION);
+        if version < hello.min_version.max(MIN_PROTOCOL_VERSION) {
+            return Err(format!(
+                "No common protocol version: client speaks {}..={}, server {}..={}",
+                hello.min_version, hello.max_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ));
+        }
+
+        let codec_name = if hello.codecs.is_empty() {
+            DEFAULT_CODEC.to_string()
+        } else {
+            hello
+                .codecs
+                .iter()
+                .find(|codec| self.codecs.contains(codec))
+                .cloned()
+                .ok_or_else(|| format!("No supported codec in {:?}", hello.codecs))?
+        };
+        let codec = self
+            .codecs
+            .build(&SinkConfig::default(), &codec_name)
+            .map_err(|error| error.to_string())?;
+
+        let filter = SubscriptionFilter::new(&hello.filters.clone().unwrap_or_default())?;
+
+        Ok((
+            Accepted {
+                version,
+                codec: codec_name,
+                slot: 0,
+            },
+            codec,
+            filter,
+        ))
+    }
+}
+
+async fn write_handshake<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    result: handshake_response::Result,
+) -> std::io::Result<()> {
+    let response = HandshakeResponse {
+        result: Some(result),
+    };
+    write_frame(stream, &response.encode_to_vec()).await
+}
+
+async fn read_frame<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    max_len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds {} bytes", len, max_len),
+        ));
+    }
+
+    let mut frame = vec![0; len];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+async fn write_frame<S: AsyncWrite + Unpin>(stream: &mut S, frame: &[u8]) -> std::io::Result<()> {
+    stream
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await?;
+    stream.write_all(frame).await?;
+    stream.flush().await
+}
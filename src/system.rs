@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use solana_sdk::{
+    hash::Hash,
+    nonce::state::{State as NonceState, Versions as NonceVersions},
+    pubkey::Pubkey,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::SanitizedTransaction,
+};
+use solana_transaction_status::TransactionStatusMeta;
+
+use crate::{
+    instructions::{resolve_instructions, ResolvedInstruction},
+    AccTx, AccountUpdate, TransactionUpdate,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolTransfer {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub lamports: u64,
+}
+
+fn system_instructions<'a>(
+    instructions: &'a [ResolvedInstruction<'a>],
+) -> impl Iterator<Item = (&'a ResolvedInstruction<'a>, SystemInstruction)> {
+    instructions
+        .iter()
+        .filter(|instruction| system_program::check_id(instruction.program_id))
+        .filter_map(|instruction| {
+            bincode::deserialize(instruction.data)
+                .ok()
+                .map(|system_instruction| (instruction, system_instruction))
+        })
+}
+
+/// System Program `Transfer` and `TransferWithSeed` instructions of a
+/// successful transaction, including those issued through CPI.
+pub fn extract_sol_transfers(
+    transaction: &SanitizedTransaction,
+    meta: &TransactionStatusMeta,
+) -> Vec<SolTransfer> {
+    if meta.status.is_err() {
+        return Vec::new();
+    }
+
+    let instructions = resolve_instructions(transaction, meta);
+    system_instructions(&instructions)
+        .filter_map(|(instruction, system_instruction)| {
+            let (from, to, lamports) = match system_instruction {
+                SystemInstruction::Transfer { lamports } => {
+                    (instruction.account(0)?, instruction.account(1)?, lamports)
+                }
+                SystemInstruction::TransferWithSeed { lamports, .. } => {
+                    (instruction.account(0)?, instruction.account(2)?, lamports)
+                }
+                _ => return None,
+            };
+
+            Some(SolTransfer {
+                from: *from,
+                to: *to,
+                lamports,
+            })
+        })
+        .collect()
+}
+
+/// Reports durable nonce consumption, both from `AdvanceNonceAccount`
+/// instructions and from changes of the stored nonce of system accounts.
+#[derive(Debug, Default)]
+pub struct NonceTracker {
+    nonces: HashMap<Pubkey, Hash>,
+}
+
+impl NonceTracker {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                lamports,
+                data,
+                txn_signature,
+                slot,
+                is_startup,
+                ..
+            }) => {
+                let pubkey = match Pubkey::try_from(pubkey.as_slice()) {
+                    Ok(pubkey) => pubkey,
+                    Err(_) => return Vec::new(),
+                };
+                let is_system_account =
+                    *lamports > 0 && owner.as_slice() == system_program::id().as_ref();
+                let versions = is_system_account
+                    .then(|| bincode::deserialize::<NonceVersions>(data).ok())
+                    .flatten();
+                let nonce_data = match versions.as_ref().map(NonceVersions::state) {
+                    Some(NonceState::Initialized(nonce_data)) => Some(nonce_data.clone()),
+                    _ => None,
+                };
+                let nonce_data = match nonce_data {
+                    Some(nonce_data) => nonce_data,
+                    None => {
+                        self.nonces.remove(&pubkey);
+                        return Vec::new();
+                    }
+                };
+
+                let blockhash = nonce_data.blockhash();
+                let previous = self.nonces.insert(pubkey, blockhash);
+                if *is_startup || previous.is_none() || previous == Some(blockhash) {
+                    return Vec::new();
+                }
+
+                vec![AccTx::NonceAccountChanged {
+                    nonce_account: pubkey,
+                    authority: nonce_data.authority,
+                    blockhash,
+                    slot: *slot,
+                    txn_signature: *txn_signature,
+                }]
+            }
+            AccTx::Tx(TransactionUpdate {
+                slot,
+                signature,
+                transaction,
+                transaction_status_meta,
+                ..
+            }) => {
+                let instructions = resolve_instructions(transaction, transaction_status_meta);
+                system_instructions(&instructions)
+                    .filter_map(
+                        |(instruction, system_instruction)| match system_instruction {
+                            SystemInstruction::AdvanceNonceAccount => Some(AccTx::NonceAdvanced {
+                                nonce_account: *instruction.account(0)?,
+                                authority: instruction.account(2).copied(),
+                                failed: transaction_status_meta.status.is_err(),
+                                slot: *slot,
+                                signature: *signature,
+                            }),
+                            _ => None,
+                        },
+                    )
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{AccTx, PluginMetrics};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThroughputConfig {
+    /// Length of each reporting window in seconds; one event is emitted per
+    /// window as it closes.
+    pub windows_secs: Vec<u64>,
+}
+
+impl Default for ThroughputConfig {
+    fn default() -> Self {
+        ThroughputConfig {
+            windows_secs: vec![1, 5],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    transactions: u64,
+    account_updates: u64,
+    dropped_events: u64,
+}
+
+#[derive(Debug)]
+struct Window {
+    secs: u64,
+    elapsed_secs: u64,
+    start: Counts,
+}
+
+/// Turns the plugin counters into per-second rates over fixed windows,
+/// driven by the writer's one second tick.
+#[derive(Debug)]
+pub struct ThroughputMonitor {
+    metrics: Arc<PluginMetrics>,
+    windows: Vec<Window>,
+}
+
+impl ThroughputMonitor {
+    pub fn new(config: &ThroughputConfig, metrics: Arc<PluginMetrics>) -> Self {
+        let start = Self::counts(&metrics);
+        let windows = config
+            .windows_secs
+            .iter()
+            .filter(|secs| **secs > 0)
+            .map(|secs| Window {
+                secs: *secs,
+                elapsed_secs: 0,
+                start,
+            })
+            .collect();
+
+        ThroughputMonitor { metrics, windows }
+    }
+
+    fn counts(metrics: &PluginMetrics) -> Counts {
+        Counts {
+            transactions: PluginMetrics::get(&metrics.transactions),
+            account_updates: PluginMetrics::get(&metrics.account_updates),
+            dropped_events: PluginMetrics::get(&metrics.dropped_events),
+        }
+    }
+
+    pub fn tick(&mut self, slot: u64) -> Vec<AccTx> {
+        let now = Self::counts(&self.metrics);
+        let mut events = Vec::new();
+
+        for window in &mut self.windows {
+            window.elapsed_secs += 1;
+            if window.elapsed_secs < window.secs {
+                continue;
+            }
+
+            let rate = |end: u64, start: u64| end.saturating_sub(start) as f64 / window.secs as f64;
+            events.push(AccTx::Throughput {
+                slot,
+                window_secs: window.secs,
+                transactions_per_sec: rate(now.transactions, window.start.transactions),
+                account_updates_per_sec: rate(now.account_updates, window.start.account_updates),
+                dropped_events_per_sec: rate(now.dropped_events, window.start.dropped_events),
+            });
+            window.elapsed_secs = 0;
+            window.start = now;
+        }
+
+        events
+    }
+}
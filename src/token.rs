@@ -0,0 +1,48 @@
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+const STATE_OFFSET: usize = 108;
+
+pub fn is_token_program(program: &[u8]) -> bool {
+    program == TOKEN_PROGRAM_ID.as_ref() || program == TOKEN_2022_PROGRAM_ID.as_ref()
+}
+
+/// The fields of an initialized SPL Token (or Token-2022) account that the
+/// plugin cares about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+impl TokenAccount {
+    pub fn unpack(program: &[u8], data: &[u8]) -> Option<Self> {
+        if !is_token_program(program) {
+            return None;
+        }
+
+        // Token-2022 accounts with extensions carry their account type right
+        // after the base layout, which tells them apart from mints.
+        let is_account = match data.len() {
+            TOKEN_ACCOUNT_LEN => true,
+            len if len > TOKEN_ACCOUNT_LEN && program == TOKEN_2022_PROGRAM_ID.as_ref() => {
+                data[TOKEN_ACCOUNT_LEN] == ACCOUNT_TYPE_ACCOUNT
+            }
+            _ => false,
+        };
+        if !is_account || data[STATE_OFFSET] == 0 {
+            return None;
+        }
+
+        Some(TokenAccount {
+            mint: Pubkey::try_from(&data[0..32]).ok()?,
+            owner: Pubkey::try_from(&data[32..64]).ok()?,
+            amount: u64::from_le_bytes(data[64..72].try_into().ok()?),
+        })
+    }
+}
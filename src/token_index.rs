@@ -0,0 +1,111 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{AccTx, AccountUpdate, TokenAccount};
+
+/// Token accounts by the wallet owning them, kept from the token account
+/// updates seen so far, for the admin API to answer what a wallet holds.
+/// Changes of an account's owner after startup, including its creation and
+/// closing, are reported as [`AccTx::TokenOwnerChanged`].
+#[derive(Debug, Default)]
+pub struct TokenOwnerIndex {
+    inner: Mutex<Index>,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Token account to its owner and mint.
+    accounts: HashMap<Pubkey, (Pubkey, Pubkey)>,
+    /// Owner to its token accounts and their mints.
+    owners: HashMap<Pubkey, BTreeMap<Pubkey, Pubkey>>,
+}
+
+impl TokenOwnerIndex {
+    pub fn observe(&self, event: &AccTx) -> Vec<AccTx> {
+        let (pubkey, owner, data, slot, is_startup) = match event {
+            AccTx::Acc(AccountUpdate {
+                pubkey,
+                owner,
+                data,
+                slot,
+                is_startup,
+                ..
+            }) => (pubkey, owner, data, *slot, *is_startup),
+            _ => return Vec::new(),
+        };
+        let account = match Pubkey::try_from(pubkey.as_slice()) {
+            Ok(account) => account,
+            Err(_) => return Vec::new(),
+        };
+        let token_account = TokenAccount::unpack(owner, data);
+
+        let mut index = self.inner.lock().unwrap();
+        let previous = index.accounts.get(&account).copied();
+        let current = token_account.map(|token_account| (token_account.owner, token_account.mint));
+        if previous == current {
+            return Vec::new();
+        }
+
+        if let Some((previous_owner, _)) = previous {
+            index.remove(&account, &previous_owner);
+        }
+        if let Some((owner, mint)) = current {
+            index.accounts.insert(account, (owner, mint));
+            index.owners.entry(owner).or_default().insert(account, mint);
+        }
+        if is_startup || previous.map(|(owner, _)| owner) == current.map(|(owner, _)| owner) {
+            return Vec::new();
+        }
+
+        vec![AccTx::TokenOwnerChanged {
+            account,
+            mint: current
+                .or(previous)
+                .map(|(_, mint)| mint)
+                .unwrap_or_default(),
+            previous_owner: previous.map(|(owner, _)| owner),
+            owner: current.map(|(owner, _)| owner),
+            slot,
+        }]
+    }
+
+    /// Token accounts of `owner` with their mints.
+    pub fn holdings(&self, owner: &Pubkey) -> Vec<(Pubkey, Pubkey)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .owners
+            .get(owner)
+            .map(|accounts| {
+                accounts
+                    .iter()
+                    .map(|(account, mint)| (*account, *mint))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Index {
+    fn remove(&mut self, account: &Pubkey, owner: &Pubkey) {
+        self.accounts.remove(account);
+        if let Some(accounts) = self.owners.get_mut(owner) {
+            accounts.remove(account);
+            if accounts.is_empty() {
+                self.owners.remove(owner);
+            }
+        }
+    }
+}
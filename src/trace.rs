@@ -0,0 +1,54 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::{AccTx, AccountUpdate, TransactionUpdate};
+
+/// Identifies one captured event across sinks and logs, formatted like
+/// a W3C trace id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceId(pub u128);
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl fmt::Debug for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Assigns trace ids to accounts and transactions in the Geyser callbacks:
+/// a random prefix chosen at load followed by a counter.
+#[derive(Debug)]
+pub struct TraceIds {
+    prefix: u64,
+    next: u64,
+}
+
+impl Default for TraceIds {
+    fn default() -> Self {
+        TraceIds {
+            prefix: RandomState::new().build_hasher().finish(),
+            next: 0,
+        }
+    }
+}
+
+impl TraceIds {
+    pub fn stamp(&mut self, event: &mut AccTx) {
+        if let AccTx::Acc(AccountUpdate { trace_id, .. })
+        | AccTx::Tx(TransactionUpdate { trace_id, .. }) = event
+        {
+            self.next = self.next.wrapping_add(1);
+            *trace_id = Some(TraceId(
+                (u128::from(self.prefix) << 64) | u128::from(self.next),
+            ));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    ReplicaTransactionInfoVersions, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::parse_pubkey;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TransactionFilterConfig {
+    /// Vote transactions, most of the stream, are dropped unless set.
+    pub include_vote_transactions: bool,
+    pub include_failed_transactions: bool,
+    /// Only transactions whose account keys, including those loaded from
+    /// lookup tables, contain one of these programs or accounts are
+    /// emitted. Every transaction is emitted when empty.
+    pub mentions: Vec<String>,
+}
+
+impl Default for TransactionFilterConfig {
+    fn default() -> Self {
+        TransactionFilterConfig {
+            include_vote_transactions: false,
+            include_failed_transactions: true,
+            mentions: Vec::new(),
+        }
+    }
+}
+
+/// Decides which transactions are sent down the pipeline.
+#[derive(Debug, Default)]
+pub struct TransactionFilter {
+    include_vote_transactions: bool,
+    include_failed_transactions: bool,
+    mentions: HashSet<Pubkey>,
+}
+
+impl TransactionFilter {
+    pub fn new(config: &TransactionFilterConfig) -> GeyserResult<Self> {
+        let mentions = config
+            .mentions
+            .iter()
+            .map(|mention| parse_pubkey(mention))
+            .collect::<GeyserResult<_>>()?;
+
+        Ok(TransactionFilter {
+            include_vote_transactions: config.include_vote_transactions,
+            include_failed_transactions: config.include_failed_transactions,
+            mentions,
+        })
+    }
+
+    pub fn matches(&self, transaction: &ReplicaTransactionInfoVersions) -> bool {
+        let (is_vote, failed, message) = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(inner_tx) => (
+                inner_tx.is_vote,
+                inner_tx.transaction_status_meta.status.is_err(),
+                inner_tx.transaction.message(),
+            ),
+            ReplicaTransactionInfoVersions::V0_0_2(inner_tx) => (
+                inner_tx.is_vote,
+                inner_tx.transaction_status_meta.status.is_err(),
+                inner_tx.transaction.message(),
+            ),
+        };
+        if (is_vote && !self.include_vote_transactions)
+            || (failed && !self.include_failed_transactions)
+        {
+            return false;
+        }
+        if self.mentions.is_empty() {
+            return true;
+        }
+
+        message
+            .account_keys()
+            .iter()
+            .any(|pubkey| self.mentions.contains(pubkey))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_sdk::{
+        address_lookup_table::AddressLookupTableAccount,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{v0, SimpleAddressLoader, VersionedMessage},
+        signature::Signature,
+        transaction::{SanitizedTransaction, TransactionError, VersionedTransaction},
+    };
+    use solana_transaction_status::TransactionStatusMeta;
+
+    use super::*;
+
+    /// A v0 transaction invoking `program` with `loaded` from a lookup
+    /// table.
+    fn transaction(program: Pubkey, loaded: Pubkey) -> SanitizedTransaction {
+        let instruction = Instruction::new_with_bytes(
+            program,
+            &[],
+            vec![AccountMeta::new_readonly(loaded, false)],
+        );
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![loaded],
+        };
+        let message = v0::Message::try_compile(
+            &Pubkey::new_unique(),
+            &[instruction],
+            &[table],
+            Hash::new_unique(),
+        )
+        .unwrap();
+        assert_eq!(message.address_table_lookups.len(), 1);
+        SanitizedTransaction::try_create(
+            VersionedTransaction {
+                signatures: vec![Signature::new_unique()],
+                message: VersionedMessage::V0(message),
+            },
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Enabled(v0::LoadedAddresses {
+                writable: Vec::new(),
+                readonly: vec![loaded],
+            }),
+        )
+        .unwrap()
+    }
+
+    fn matches(
+        filter: &TransactionFilter,
+        transaction: &SanitizedTransaction,
+        is_vote: bool,
+        failed: bool,
+    ) -> bool {
+        let meta = TransactionStatusMeta {
+            status: if failed {
+                Err(TransactionError::AccountNotFound)
+            } else {
+                Ok(())
+            },
+            ..TransactionStatusMeta::default()
+        };
+        filter.matches(&ReplicaTransactionInfoVersions::V0_0_2(
+            &ReplicaTransactionInfoV2 {
+                signature: transaction.signature(),
+                is_vote,
+                transaction,
+                transaction_status_meta: &meta,
+                index: 0,
+            },
+        ))
+    }
+
+    fn build(config: TransactionFilterConfig) -> TransactionFilter {
+        TransactionFilter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn drops_votes_unless_included() {
+        let transaction = transaction(Pubkey::new_unique(), Pubkey::new_unique());
+        let filter = build(TransactionFilterConfig::default());
+        assert!(!matches(&filter, &transaction, true, false));
+        assert!(matches(&filter, &transaction, false, false));
+
+        let filter = build(TransactionFilterConfig {
+            include_vote_transactions: true,
+            ..TransactionFilterConfig::default()
+        });
+        assert!(matches(&filter, &transaction, true, false));
+    }
+
+    #[test]
+    fn drops_failed_when_excluded() {
+        let transaction = transaction(Pubkey::new_unique(), Pubkey::new_unique());
+        let filter = build(TransactionFilterConfig::default());
+        assert!(matches(&filter, &transaction, false, true));
+
+        let filter = build(TransactionFilterConfig {
+            include_failed_transactions: false,
+            ..TransactionFilterConfig::default()
+        });
+        assert!(!matches(&filter, &transaction, false, true));
+        assert!(matches(&filter, &transaction, false, false));
+    }
+
+    #[test]
+    fn mentions_match_lookup_table_keys() {
+        let (program, loaded) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = transaction(program, loaded);
+        let mentioning = |mention: Pubkey| {
+            let filter = build(TransactionFilterConfig {
+                mentions: vec![mention.to_string()],
+                ..TransactionFilterConfig::default()
+            });
+            matches(&filter, &transaction, false, false)
+        };
+        assert!(mentioning(loaded));
+        assert!(mentioning(program));
+        assert!(!mentioning(Pubkey::new_unique()));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, HashMap};
+
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_sdk::signature::Signature;
+
+use crate::{AccTx, AccountUpdate, SlotUpdate, TransactionUpdate};
+
+#[derive(Debug, Default)]
+struct Join {
+    transaction: Option<AccTx>,
+    accounts: Vec<AccTx>,
+}
+
+/// Joins transactions with the account updates carrying their signature
+/// into [`AccTx::TxWithEffects`] events.
+///
+/// Updates can be notified before or after their transaction, so a slot's
+/// joins are only emitted once it is confirmed or rooted. Joins of slots
+/// left behind by a root without being confirmed were on abandoned forks
+/// and are dropped.
+#[derive(Debug, Default)]
+pub struct TxEffectsJoiner {
+    slots: BTreeMap<u64, HashMap<Signature, Join>>,
+}
+
+impl TxEffectsJoiner {
+    pub fn observe(&mut self, event: &AccTx) -> Vec<AccTx> {
+        match event {
+            AccTx::Tx(TransactionUpdate {
+                slot, signature, ..
+            }) => {
+                self.join(*slot, *signature).transaction = Some(event.clone());
+                Vec::new()
+            }
+            AccTx::Acc(AccountUpdate {
+                slot,
+                txn_signature: Some(signature),
+                is_startup: false,
+                ..
+            }) => {
+                self.join(*slot, *signature).accounts.push(event.clone());
+                Vec::new()
+            }
+            AccTx::Slot(SlotUpdate {
+                slot,
+                status: status @ (SlotStatus::Confirmed | SlotStatus::Rooted),
+                ..
+            }) => {
+                let events = self.emit(*slot);
+                if let SlotStatus::Rooted = status {
+                    self.slots = self.slots.split_off(slot);
+                }
+                events
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn join(&mut self, slot: u64, signature: Signature) -> &mut Join {
+        self.slots
+            .entry(slot)
+            .or_default()
+            .entry(signature)
+            .or_default()
+    }
+
+    fn emit(&mut self, slot: u64) -> Vec<AccTx> {
+        let joins = match self.slots.remove(&slot) {
+            Some(joins) => joins,
+            None => return Vec::new(),
+        };
+
+        joins
+            .into_iter()
+            .filter_map(|(signature, join)| {
+                Some(AccTx::TxWithEffects {
+                    slot,
+                    signature,
+                    transaction: Box::new(join.transaction?),
+                    accounts: join.accounts,
+                })
+            })
+            .collect()
+    }
+}
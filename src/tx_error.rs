@@ -0,0 +1,55 @@
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+/// A transaction error as a stable code and category, for consumers that
+/// filter failures without decoding Rust enums.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxErrorClass {
+    /// Variant name, with the failing instruction's error for instruction
+    /// errors, e.g. `BlockhashNotFound` or `InstructionError:Custom(6001)`.
+    pub code: String,
+    /// One of `instruction`, `funds`, `blockhash`, `account`, `limits`,
+    /// `program` or `other`.
+    pub category: &'static str,
+}
+
+impl TxErrorClass {
+    pub fn of(error: &TransactionError) -> Self {
+        let code = match error {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+                format!("InstructionError:Custom({})", code)
+            }
+            TransactionError::InstructionError(_, error) => {
+                format!("InstructionError:{}", variant_name(error))
+            }
+            error => variant_name(error),
+        };
+        let category = category(&code);
+        TxErrorClass { code, category }
+    }
+}
+
+/// Name of an enum variant from its `Debug` output, without its fields.
+fn variant_name(error: &impl std::fmt::Debug) -> String {
+    let debug = format!("{:?}", error);
+    match debug.find(['(', ' ', '{']) {
+        Some(end) => debug[..end].to_string(),
+        None => debug,
+    }
+}
+
+fn category(code: &str) -> &'static str {
+    if code.starts_with("InstructionError:") {
+        return "instruction";
+    }
+    match code {
+        "InsufficientFundsForFee" | "InsufficientFundsForRent" => "funds",
+        "BlockhashNotFound" | "AlreadyProcessed" => "blockhash",
+        "ProgramAccountNotFound"
+        | "InvalidProgramForExecution"
+        | "UnsupportedVersion"
+        | "ProgramExecutionTemporarilyRestricted" => "program",
+        code if code.starts_with("WouldExceed") || code == "TooManyAccountLocks" => "limits",
+        code if code.contains("Account") => "account",
+        _ => "other",
+    }
+}
@@ -0,0 +1,193 @@
+//! Length-prefixed frames pushed to a consumer listening on a Unix domain
+//! socket on the same host. Unlike the stream server, which consumers
+//! connect to, this sink connects to the consumer, reconnects when it
+//! restarts and keeps a bounded backlog of frames meanwhile.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_geyser_plugin_interface::geyser_plugin_interface::Result as GeyserResult;
+
+use crate::{Codec, CodecRegistry, EncodedEvent, PluginMetrics, Sink, SinkConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UdsConfig {
+    /// Socket the consumer listens on.
+    pub path: PathBuf,
+    /// Frames kept while the consumer is away; the oldest are dropped
+    /// beyond this.
+    #[serde(default = "default_backlog")]
+    pub backlog: usize,
+    #[serde(default = "default_reconnect_ms")]
+    pub reconnect_ms: u64,
+    /// A consumer not taking a frame within this long is disconnected, so
+    /// it never holds up the writer for longer.
+    #[serde(default = "default_write_timeout_ms")]
+    pub write_timeout_ms: u64,
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+}
+
+fn default_backlog() -> usize {
+    100_000
+}
+
+fn default_reconnect_ms() -> u64 {
+    1_000
+}
+
+fn default_write_timeout_ms() -> u64 {
+    100
+}
+
+#[derive(Debug)]
+pub struct UdsSink {
+    path: PathBuf,
+    codec: Arc<dyn Codec>,
+    stream: Option<UnixStream>,
+    /// Frames not written yet, with their slots.
+    backlog: VecDeque<(u64, Arc<[u8]>)>,
+    capacity: usize,
+    reconnect: Duration,
+    write_timeout: Duration,
+    next_attempt: Instant,
+    dropped: u64,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl UdsSink {
+    /// Frames are always length-prefixed, whatever `framed` says.
+    pub fn start(
+        config: &UdsConfig,
+        codecs: &CodecRegistry,
+        metrics: Arc<PluginMetrics>,
+    ) -> GeyserResult<Self> {
+        let sink = SinkConfig {
+            framed: true,
+            ..config.sink.clone()
+        };
+        let mut uds = UdsSink {
+            path: config.path.clone(),
+            codec: codecs.build(&sink, "protobuf")?,
+            stream: None,
+            backlog: VecDeque::new(),
+            capacity: config.backlog.max(1),
+            reconnect: Duration::from_millis(config.reconnect_ms),
+            write_timeout: Duration::from_millis(config.write_timeout_ms.max(1)),
+            next_attempt: Instant::now(),
+            dropped: 0,
+            metrics,
+        };
+        uds.connect();
+        Ok(uds)
+    }
+
+    fn connect(&mut self) {
+        self.next_attempt = Instant::now() + self.reconnect;
+        let stream = UnixStream::connect(&self.path).and_then(|stream| {
+            stream.set_write_timeout(Some(self.write_timeout))?;
+            Ok(stream)
+        });
+        match stream {
+            Ok(stream) => {
+                info!(
+                    "Connected to consumer at {:?} with {} frames backlogged",
+                    self.path,
+                    self.backlog.len()
+                );
+                self.stream = Some(stream);
+            }
+            Err(error) if self.dropped == 0 && self.backlog.is_empty() => {
+                warn!("No consumer at {:?} yet: {}", self.path, error);
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Writes the backlog for as long as the consumer takes it. A frame cut
+    /// short stays at the front, to be sent whole on the next connection.
+    fn drain(&mut self) {
+        if self.stream.is_none() && Instant::now() >= self.next_attempt {
+            self.connect();
+        }
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return,
+        };
+        while let Some((_, frame)) = self.backlog.front() {
+            if let Err(error) = stream.write_all(frame) {
+                warn!("Consumer at {:?} disconnected: {}", self.path, error);
+                self.stream = None;
+                self.next_attempt = Instant::now() + self.reconnect;
+                return;
+            }
+            self.backlog.pop_front();
+        }
+    }
+}
+
+impl Sink for UdsSink {
+    fn name(&self) -> &'static str {
+        "uds"
+    }
+
+    fn write(&mut self, event: &EncodedEvent) -> io::Result<()> {
+        let frame = match event.encode(&self.codec) {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        if self.backlog.len() >= self.capacity {
+            if let Some((slot, _)) = self.backlog.pop_front() {
+                PluginMetrics::increment(&self.metrics.dropped_events);
+                self.metrics.gaps.record("uds", slot, slot, 1);
+                self.dropped += 1;
+                if self.dropped.is_power_of_two() {
+                    self.metrics.record_error(format!(
+                        "Consumer at {:?} away, {} frames dropped",
+                        self.path, self.dropped
+                    ));
+                }
+            }
+        }
+        self.backlog.push_back((event.event.slot(), frame));
+        self.drain();
+        Ok(())
+    }
+
+    /// Reconnects while idle too.
+    fn heartbeat(&mut self, _slot: u64) -> io::Result<()> {
+        self.drain();
+        Ok(())
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "path": self.path,
+            "connected": self.stream.is_some(),
+            "backlog": self.backlog.len(),
+            "dropped": self.dropped,
+        })
+    }
+
+    fn pending(&self) -> u64 {
+        self.backlog.len() as u64
+    }
+
+    fn undelivered_from(&self) -> Option<u64> {
+        self.backlog.iter().map(|(slot, _)| *slot).min()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.drain();
+        Ok(())
+    }
+}
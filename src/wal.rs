@@ -0,0 +1,215 @@
+//! Write-ahead log of the events between the plugin callbacks and the
+//! sinks, so that a crash in between loses no more than the log's flush
+//! interval.
+//!
+//! Entries are protobuf [`SubscribeUpdate`]s keyed by slot (`u64`,
+//! big-endian), kind (`u8`, 0 for accounts, 1 for transactions and 2 for
+//! slots) and identity (pubkey and write version, signature, or status), so
+//! the writer keys them from the event itself, removes them slot by slot
+//! as the sinks deliver, and recovery goes oldest slot first.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use log::{info, warn};
+use prost::Message;
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult, SlotStatus as GeyserSlotStatus,
+};
+use solana_sdk::{hash::Hash, signature::Signature};
+
+use crate::{
+    grpc::proto::{subscribe_update::Update, SlotStatus, SubscribeUpdate},
+    AccTx, AccountUpdate, SlotUpdate, TraceId, TransactionUpdate,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalConfig {
+    /// Directory of the log, created when missing.
+    pub path: PathBuf,
+    /// How often appends reach the disk; a crash loses those of the last
+    /// interval.
+    #[serde(default = "default_flush_every_ms")]
+    pub flush_every_ms: u64,
+}
+
+fn default_flush_every_ms() -> u64 {
+    50
+}
+
+/// Accounts, transactions and slot updates are appended before they are
+/// queued and removed once every sink delivered their slot, or gave up on
+/// it as a gap. What a previous run left behind is recovered at load.
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    db: sled::Db,
+}
+
+impl WriteAheadLog {
+    pub fn open(config: &WalConfig) -> GeyserResult<Self> {
+        let db = sled::Config::new()
+            .path(&config.path)
+            .flush_every_ms(Some(config.flush_every_ms))
+            .open()
+            .map_err(|error| {
+                GeyserPluginError::Custom(
+                    format!(
+                        "Failed to open write-ahead log {:?}: {}",
+                        config.path, error
+                    )
+                    .into(),
+                )
+            })?;
+        Ok(WriteAheadLog { db })
+    }
+
+    /// Key of `event`, `None` for events that are not logged.
+    pub fn key(event: &AccTx) -> Option<Vec<u8>> {
+        match event {
+            AccTx::Acc(AccountUpdate {
+                slot,
+                pubkey,
+                write_version,
+                ..
+            }) => Some(key(*slot, 0, &[pubkey, &write_version.to_be_bytes()])),
+            AccTx::Tx(TransactionUpdate {
+                slot, signature, ..
+            }) => Some(key(*slot, 1, &[signature.as_ref()])),
+            AccTx::Slot(SlotUpdate { slot, status, .. }) => {
+                Some(key(*slot, 2, &[&[SlotStatus::from(*status) as u8]]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Key of an archived `update`, as [`WriteAheadLog::key`].
+    pub fn update_key(update: &SubscribeUpdate) -> Option<Vec<u8>> {
+        match update.update.as_ref()? {
+            Update::Account(account) => Some(key(
+                account.slot,
+                0,
+                &[&account.pubkey, &account.write_version.to_be_bytes()],
+            )),
+            Update::Transaction(transaction) => {
+                Some(key(transaction.slot, 1, &[&transaction.signature]))
+            }
+            Update::Slot(slot) => Some(key(slot.slot, 2, &[&[slot.status as u8]])),
+            _ => None,
+        }
+    }
+
+    pub fn append(&self, event: &AccTx) {
+        let (key, update) = match (Self::key(event), SubscribeUpdate::from_event(event)) {
+            (Some(key), Some(update)) => (key, update),
+            _ => return,
+        };
+        if let Err(error) = self.db.insert(key, update.encode_to_vec()) {
+            warn!("Failed to append to the write-ahead log: {}", error);
+        }
+    }
+
+    /// Removes the entries among `keys`, those of events handed to the
+    /// sinks, whose slot is below `undelivered_from`, or all of them once
+    /// nothing is undelivered. The others stay in `keys`.
+    pub fn remove_delivered(&self, keys: &mut BTreeSet<Vec<u8>>, undelivered_from: Option<u64>) {
+        let undelivered = match undelivered_from {
+            Some(slot) => keys.split_off(&slot.to_be_bytes()[..]),
+            None => BTreeSet::new(),
+        };
+        let delivered = std::mem::replace(keys, undelivered);
+        if delivered.is_empty() {
+            return;
+        }
+        let mut batch = sled::Batch::default();
+        for key in delivered {
+            batch.remove(key);
+        }
+        if let Err(error) = self.db.apply_batch(batch) {
+            warn!("Failed to truncate the write-ahead log: {}", error);
+        }
+    }
+
+    /// Removes the entry of an event dropped before the writer took it,
+    /// which no sink will deliver.
+    pub fn remove(&self, event: &AccTx) {
+        if let Some(key) = Self::key(event) {
+            if let Err(error) = self.db.remove(key) {
+                warn!("Failed to remove from the write-ahead log: {}", error);
+            }
+        }
+    }
+
+    /// Entries left by a previous run, oldest slot first. They stay in the
+    /// log until the writer is done with them again.
+    pub fn recover(&self) -> Vec<SubscribeUpdate> {
+        let updates: Vec<SubscribeUpdate> = self
+            .db
+            .iter()
+            .values()
+            .filter_map(|value| SubscribeUpdate::decode(value.ok()?.as_ref()).ok())
+            .collect();
+        if !updates.is_empty() {
+            info!(
+                "Recovering {} events from the write-ahead log",
+                updates.len()
+            );
+        }
+        updates
+    }
+}
+
+fn key(slot: u64, kind: u8, identity: &[&[u8]]) -> Vec<u8> {
+    let mut key = slot.to_be_bytes().to_vec();
+    key.push(kind);
+    for part in identity {
+        key.extend_from_slice(part);
+    }
+    key
+}
+
+/// The event of a recovered account or slot update, to be queued again.
+/// Transactions only have their update, which sinks that support it replay.
+pub fn recovered_event(update: &SubscribeUpdate) -> Option<AccTx> {
+    match update.update.as_ref()? {
+        Update::Account(account) => Some(AccTx::Acc(AccountUpdate {
+            pubkey: account.pubkey.clone(),
+            lamports: account.lamports,
+            owner: account.owner.clone(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.clone(),
+            write_version: account.write_version,
+            txn_signature: account
+                .txn_signature
+                .as_deref()
+                .and_then(|signature| Signature::try_from(signature).ok()),
+            slot: account.slot,
+            is_startup: account.is_startup,
+            data_hash: account
+                .data_hash
+                .as_deref()
+                .and_then(|data_hash| <[u8; 32]>::try_from(data_hash).ok())
+                .map(Hash::new_from_array),
+            data_len: account.data_len,
+            capture_ns: None,
+            wall_clock_ms: None,
+            unix_timestamp: None,
+            trace_id: account
+                .trace_id
+                .as_deref()
+                .and_then(|trace_id| u128::from_str_radix(trace_id, 16).ok())
+                .map(TraceId),
+            labels: Vec::new(),
+        })),
+        Update::Slot(slot) => Some(AccTx::Slot(SlotUpdate {
+            slot: slot.slot,
+            parent: slot.parent,
+            status: match SlotStatus::try_from(slot.status).ok()? {
+                SlotStatus::Processed => GeyserSlotStatus::Processed,
+                SlotStatus::Confirmed => GeyserSlotStatus::Confirmed,
+                SlotStatus::Rooted => GeyserSlotStatus::Rooted,
+            },
+        })),
+        _ => None,
+    }
+}
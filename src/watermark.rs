@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Periodically writes [`AccTx::Watermark`](crate::AccTx::Watermark) records
+/// to every file and Kafka sink so batch consumers know which range of their
+/// data is complete.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    pub interval_secs: u64,
+}
+
+impl Default for WatermarkConfig {
+    fn default() -> Self {
+        WatermarkConfig { interval_secs: 60 }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct WatermarkTimer {
+    interval: Duration,
+    last_emitted: Instant,
+}
+
+impl WatermarkTimer {
+    pub(crate) fn new(config: &WatermarkConfig) -> Self {
+        WatermarkTimer {
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+            last_emitted: Instant::now(),
+        }
+    }
+
+    /// Whether watermarks are due, restarting the interval if so.
+    pub(crate) fn due(&mut self) -> bool {
+        if self.last_emitted.elapsed() < self.interval {
+            return false;
+        }
+
+        self.last_emitted = Instant::now();
+        true
+    }
+}
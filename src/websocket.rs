@@ -0,0 +1,202 @@
+//! Pushes events as JSON text messages to WebSocket clients.
+//!
+//! Clients receive nothing until they send a [`WebSocketSubscribe`]
+//! message, which is answered with `{"subscribed":{"slot":N}}` or
+//! `{"error":"..."}`. Sending another one replaces the filters.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use async_tungstenite::tungstenite::{Error as WsError, Message};
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use smol::{
+    lock::Mutex,
+    net::{TcpListener, TcpStream},
+    Task,
+};
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    grpc::{
+        proto::{AccountsFilter, SubscribeRequest, TransactionsFilter},
+        SubscriberUpdate,
+    },
+    Codec, CodecRegistry, SinkConfig, Subscribers, SubscriptionFilter,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebSocketConfig {
+    pub address: SocketAddr,
+    /// Events buffered per client; clients falling this far behind are
+    /// disconnected.
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+}
+
+fn default_channel_capacity() -> usize {
+    10_000
+}
+
+/// Filters of a client, in the spirit of the RPC pubsub subscriptions.
+/// Leaving a list out subscribes to none of its kind, an empty list to all.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WebSocketSubscribe {
+    /// Account updates of these pubkeys, like `accountSubscribe`.
+    pub accounts: Option<Vec<String>>,
+    /// Account updates of accounts owned by these programs, like
+    /// `programSubscribe`.
+    pub owners: Option<Vec<String>>,
+    /// Transactions mentioning these programs or accounts.
+    pub programs: Option<Vec<String>>,
+    pub include_votes: bool,
+    pub include_failed: bool,
+    pub slots: bool,
+}
+
+impl WebSocketSubscribe {
+    fn request(self) -> SubscribeRequest {
+        let accounts = match (self.accounts, self.owners) {
+            (None, None) => None,
+            (pubkeys, owners) => Some(AccountsFilter {
+                pubkeys: pubkeys.unwrap_or_default(),
+                owners: owners.unwrap_or_default(),
+            }),
+        };
+        SubscribeRequest {
+            accounts,
+            transactions: self.programs.map(|programs| TransactionsFilter {
+                include_votes: self.include_votes,
+                include_failed: self.include_failed,
+                account_include: programs,
+            }),
+            slots: self.slots,
+            group: String::new(),
+        }
+    }
+}
+
+/// Accept loop of the server, stopped on drop.
+#[derive(Debug)]
+pub struct WebSocketServer {
+    _listener: Task<()>,
+}
+
+/// State shared by every client of a [`WebSocketServer`].
+#[derive(Debug)]
+struct Clients {
+    subscribers: Arc<Subscribers>,
+    codec: Arc<dyn Codec>,
+    channel_capacity: usize,
+}
+
+impl WebSocketServer {
+    pub fn start(
+        config: &WebSocketConfig,
+        subscribers: Arc<Subscribers>,
+        codecs: &CodecRegistry,
+    ) -> GeyserResult<Self> {
+        let listener = smol::block_on(TcpListener::bind(config.address)).map_err(|error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to listen for WebSocket clients on {}: {}",
+                    config.address, error
+                )
+                .into(),
+            )
+        })?;
+        let clients = Arc::new(Clients {
+            subscribers,
+            codec: codecs.build(&SinkConfig::default(), "json")?,
+            channel_capacity: config.channel_capacity,
+        });
+        let listener = smol::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        info!("WebSocket client {}", peer);
+                        let clients = clients.clone();
+                        smol::spawn(async move {
+                            if let Err(error) = clients.serve(stream).await {
+                                warn!("WebSocket client {} closed: {}", peer, error);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(error) => warn!("Failed to accept WebSocket client: {}", error),
+                }
+            }
+        });
+        info!("Serving events on ws://{}", config.address);
+
+        Ok(WebSocketServer {
+            _listener: listener,
+        })
+    }
+}
+
+impl Clients {
+    async fn serve(&self, stream: TcpStream) -> Result<(), WsError> {
+        let (outgoing, mut incoming) = async_tungstenite::accept_async(stream).await?.split();
+        let outgoing = Mutex::new(outgoing);
+        let (sender, mut receiver) = mpsc::channel(self.channel_capacity);
+        let id = self.subscribers.add(sender);
+
+        let requests = async {
+            while let Some(message) = incoming.next().await {
+                let reply = match message? {
+                    Message::Text(text) => self.subscribe(id, &text),
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                outgoing
+                    .lock()
+                    .await
+                    .send(Message::Text(reply.to_string()))
+                    .await?;
+            }
+            Ok::<_, WsError>(())
+        };
+        let forward = async {
+            while let Some(update) = receiver.recv().await {
+                let event = match update {
+                    SubscriberUpdate::Event(event) => event,
+                    _ => continue,
+                };
+                let text = match self.codec.encode(&event).map(String::from_utf8) {
+                    Some(Ok(text)) => text,
+                    _ => continue,
+                };
+                outgoing
+                    .lock()
+                    .await
+                    .send(Message::Text(text.trim_end().to_string()))
+                    .await?;
+            }
+            Ok::<_, WsError>(())
+        };
+        let served = futures_lite::future::or(requests, forward).await;
+        self.subscribers.remove(id);
+
+        served
+    }
+
+    fn subscribe(&self, id: u64, text: &str) -> serde_json::Value {
+        let subscribe: WebSocketSubscribe = match serde_json::from_str(text) {
+            Ok(subscribe) => subscribe,
+            Err(error) => return json!({ "error": format!("Invalid subscription: {}", error) }),
+        };
+        match SubscriptionFilter::new(&subscribe.request()) {
+            Ok(filter) => {
+                let slot = self.subscribers.update(id, filter).unwrap_or_default();
+                json!({ "subscribed": { "slot": slot } })
+            }
+            Err(error) => json!({ "error": error }),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! zstd compression of sink records with a dictionary per account owner.
+//! The accounts of one program, like token accounts, are small and share
+//! most of their bytes, so they compress several times better against a
+//! dictionary trained on earlier records of the same owner.
+//!
+//! Every dictionary is kept as `<owner>.<unix secs>.zdict`. zstd frames name
+//! the id of the dictionary they need, so readers load all of the files,
+//! including those replaced by retraining since.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::{
+    GeyserPluginError, Result as GeyserResult,
+};
+use solana_sdk::pubkey::Pubkey;
+use zstd::bulk::Compressor;
+
+use crate::{config::parse_pubkey, AccTx, AccountUpdate, Codec};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ZstdDictionaryConfig {
+    /// Dictionaries are loaded from here on startup, so ones trained
+    /// offline, e.g. with `zstd --train` and named `<owner>.zdict`, are used
+    /// as well.
+    pub dir: PathBuf,
+    /// Train dictionaries from the latest records of each owner this often;
+    /// only those in `dir` are used when unset.
+    pub retrain_interval_secs: Option<u64>,
+    /// Latest records kept per owner for training.
+    pub samples_per_owner: usize,
+    /// Owners with fewer records are not trained yet.
+    pub min_samples: usize,
+    /// Owners to train, the first `max_owners` seen when empty.
+    pub owners: Vec<String>,
+    pub max_owners: usize,
+    /// Bytes per dictionary.
+    pub dictionary_size: usize,
+    pub level: i32,
+}
+
+impl Default for ZstdDictionaryConfig {
+    fn default() -> Self {
+        ZstdDictionaryConfig {
+            dir: PathBuf::from("zstd_dictionaries"),
+            retrain_interval_secs: None,
+            samples_per_owner: 1000,
+            min_samples: 100,
+            owners: Vec::new(),
+            max_owners: 64,
+            dictionary_size: 16 * 1024,
+            level: 3,
+        }
+    }
+}
+
+/// The dictionaries of every owner, shared by the zstd codecs of all sinks.
+#[derive(Default)]
+pub struct ZstdDictionaries {
+    config: ZstdDictionaryConfig,
+    /// Only sampled when set.
+    owners: Option<HashSet<Pubkey>>,
+    training: bool,
+    compressors: RwLock<HashMap<Pubkey, Mutex<Compressor<'static>>>>,
+    /// For records without a dictionary, created on first use.
+    plain: Mutex<Option<Compressor<'static>>>,
+    samples: Mutex<HashMap<Pubkey, VecDeque<Vec<u8>>>>,
+}
+
+impl fmt::Debug for ZstdDictionaries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionaries")
+            .field("dir", &self.config.dir)
+            .field("owners", &self.compressors.read().unwrap().len())
+            .finish()
+    }
+}
+
+impl ZstdDictionaries {
+    pub fn open(config: &ZstdDictionaryConfig) -> GeyserResult<Self> {
+        let io_error = |error: std::io::Error| {
+            GeyserPluginError::Custom(
+                format!(
+                    "Failed to read zstd dictionaries {:?}: {}",
+                    config.dir, error
+                )
+                .into(),
+            )
+        };
+        fs::create_dir_all(&config.dir).map_err(io_error)?;
+
+        // The newest dictionary of each owner wins.
+        let mut newest: HashMap<Pubkey, (u64, Vec<u8>)> = HashMap::new();
+        for entry in fs::read_dir(&config.dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("zdict") {
+                continue;
+            }
+            let stem = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("");
+            let (owner, trained_at) = match stem.split_once('.') {
+                Some((owner, trained_at)) => (owner, trained_at.parse().unwrap_or_default()),
+                None => (stem, 0),
+            };
+            let owner = match owner.parse::<Pubkey>() {
+                Ok(owner) => owner,
+                Err(_) => {
+                    warn!("Ignoring zstd dictionary {:?} not named by owner", path);
+                    continue;
+                }
+            };
+            if newest
+                .get(&owner)
+                .is_some_and(|(newest, _)| *newest >= trained_at)
+            {
+                continue;
+            }
+            newest.insert(owner, (trained_at, fs::read(&path).map_err(io_error)?));
+        }
+
+        let mut compressors = HashMap::new();
+        for (owner, (_, dictionary)) in newest {
+            let compressor =
+                Compressor::with_dictionary(config.level, &dictionary).map_err(|error| {
+                    GeyserPluginError::ConfigFileReadError {
+                        msg: format!("Invalid zstd dictionary of {}: {}", owner, error),
+                    }
+                })?;
+            compressors.insert(owner, Mutex::new(compressor));
+        }
+        info!(
+            "Loaded zstd dictionaries of {} owners from {:?}",
+            compressors.len(),
+            config.dir
+        );
+
+        let owners = match config.owners.is_empty() {
+            true => None,
+            false => Some(
+                config
+                    .owners
+                    .iter()
+                    .map(|owner| parse_pubkey(owner))
+                    .collect::<GeyserResult<_>>()?,
+            ),
+        };
+        Ok(ZstdDictionaries {
+            config: config.clone(),
+            owners,
+            training: config.retrain_interval_secs.is_some(),
+            compressors: RwLock::new(compressors),
+            plain: Mutex::default(),
+            samples: Mutex::default(),
+        })
+    }
+
+    /// With the dictionary of `owner` when there is one.
+    fn compress(&self, owner: Option<&Pubkey>, record: &[u8]) -> Option<Vec<u8>> {
+        if let Some(owner) = owner {
+            if let Some(compressor) = self.compressors.read().unwrap().get(owner) {
+                return compressor.lock().unwrap().compress(record).ok();
+            }
+        }
+        let mut plain = self.plain.lock().unwrap();
+        if plain.is_none() {
+            *plain = Some(Compressor::new(self.config.level).ok()?);
+        }
+        plain.as_mut()?.compress(record).ok()
+    }
+
+    fn sample(&self, owner: Pubkey, record: &[u8]) {
+        if !self.training
+            || self
+                .owners
+                .as_ref()
+                .is_some_and(|owners| !owners.contains(&owner))
+        {
+            return;
+        }
+        let mut samples = self.samples.lock().unwrap();
+        if !samples.contains_key(&owner) && samples.len() >= self.config.max_owners {
+            return;
+        }
+        let samples = samples.entry(owner).or_default();
+        if samples.len() >= self.config.samples_per_owner {
+            samples.pop_front();
+        }
+        samples.push_back(record.to_vec());
+    }
+
+    /// Trains the owners with enough new records and returns how many got
+    /// a new dictionary. Their records are used up, so each dictionary
+    /// reflects the accounts since the previous one.
+    pub fn train(&self) -> usize {
+        let ready: Vec<(Pubkey, VecDeque<Vec<u8>>)> = {
+            let mut samples = self.samples.lock().unwrap();
+            let owners: Vec<Pubkey> = samples
+                .iter()
+                .filter(|(_, records)| records.len() >= self.config.min_samples)
+                .map(|(owner, _)| *owner)
+                .collect();
+            owners
+                .into_iter()
+                .filter_map(|owner| samples.remove_entry(&owner))
+                .collect()
+        };
+
+        let trained_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let mut trained = 0;
+        for (owner, records) in ready {
+            let records: Vec<Vec<u8>> = records.into();
+            let dictionary = match zstd::dict::from_samples(&records, self.config.dictionary_size) {
+                Ok(dictionary) => dictionary,
+                Err(error) => {
+                    warn!("Failed to train zstd dictionary of {}: {}", owner, error);
+                    continue;
+                }
+            };
+            // Written before use, so no record needs a dictionary readers
+            // cannot find.
+            let path = self
+                .config
+                .dir
+                .join(format!("{}.{}.zdict", owner, trained_at));
+            if let Err(error) = fs::write(&path, &dictionary) {
+                warn!("Failed to write zstd dictionary {:?}: {}", path, error);
+                continue;
+            }
+            match Compressor::with_dictionary(self.config.level, &dictionary) {
+                Ok(compressor) => {
+                    self.compressors
+                        .write()
+                        .unwrap()
+                        .insert(owner, Mutex::new(compressor));
+                    trained += 1;
+                }
+                Err(error) => warn!("Failed to load zstd dictionary {:?}: {}", path, error),
+            }
+        }
+        trained
+    }
+}
+
+/// Compresses the records of another codec, each into a zstd frame of its
+/// own.
+#[derive(Debug)]
+pub(crate) struct ZstdCodec {
+    pub(crate) inner: Arc<dyn Codec>,
+    pub(crate) dictionaries: Arc<ZstdDictionaries>,
+}
+
+impl Codec for ZstdCodec {
+    fn encode(&self, event: &AccTx) -> Option<Vec<u8>> {
+        let record = self.inner.encode(event)?;
+        let owner = match event {
+            AccTx::Acc(AccountUpdate { owner, .. }) => Pubkey::try_from(owner.as_slice()).ok(),
+            _ => None,
+        };
+        if let Some(owner) = owner {
+            self.dictionaries.sample(owner, &record);
+        }
+        self.dictionaries.compress(owner.as_ref(), &record)
+    }
+}
+
+/// Retrains [`ZstdDictionaries`] from a thread of its own until dropped.
+#[derive(Debug)]
+pub struct DictionaryTrainer {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DictionaryTrainer {
+    pub fn start(dictionaries: Arc<ZstdDictionaries>, interval: Duration) -> GeyserResult<Self> {
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("fusionZstdTrain".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let trained = dictionaries.train();
+                    if trained > 0 {
+                        info!("Trained zstd dictionaries of {} owners", trained);
+                    }
+                }
+            })
+            .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
+
+        Ok(DictionaryTrainer {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DictionaryTrainer {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}